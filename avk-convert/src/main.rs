@@ -6,6 +6,10 @@ use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 
+mod midi;
+mod sfx;
+mod wav;
+
 fn generate_image_palette(
 	img: &[[u8; 4]; IMAGE_SIZE as usize * IMAGE_SIZE as usize],
 	gen_palette: &mut Palette,
@@ -45,6 +49,22 @@ fn generate_image_palette(
 
 fn main() {
 	let args: Vec<String> = args().collect();
+	match args.get(1).map(String::as_str) {
+		Some("wav") => {
+			wav::convert(&args[2..]);
+			return;
+		}
+		Some("midi") => {
+			midi::convert(&args[2..]);
+			return;
+		}
+		Some("sfx") => {
+			sfx::convert(&args[2..]);
+			return;
+		}
+		_ => {}
+	}
+
 	let filename = Path::new(&args[1]);
 	let mut img = image::open(filename).unwrap();
 
@@ -0,0 +1,499 @@
+//! `avk-convert midi <file.mid> [options]` converts a Standard MIDI File to an AVK song.
+//!
+//! Each MIDI channel is mapped to one of the four PSG channels. Those can only play one note at
+//! a time, so overlapping notes cut each other off, and a warning says where it happened.
+
+use avk_types::prelude::*;
+use avk_types::{
+	PatternCell, INSTRUMENT_VOLUME, MAX_SONG_SIZE, NOTE_OFF, NO_LOOP, SONG_HEADER_SIZE, SONG_MAGIC,
+	SONG_VERSION,
+};
+use midly::{Format, MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use std::fs;
+use std::path::Path;
+
+const USAGE: &str = "Usage: avk-convert midi <file.mid> [options]
+  --map <midi channel>=<pulse1|pulse2|wave|noise>
+      plays a MIDI channel (1-16) on a PSG channel, can be repeated
+      (default: 1=pulse1 2=pulse2 3=wave 10=noise)
+  --instrument <midi channel>=<duty>[,<envelope>[,<sweep>]]
+      register values the MIDI channel's notes are played with (default: 2,0,0)
+  --rows-per-beat <rows>      quantization, in rows per quarter note (default: 4)
+  --rows-per-pattern <rows>   (default: 64)
+  --loop                      loops back to the start at the end";
+
+/// Sound settings of a MIDI channel's notes.
+#[derive(Copy, Clone)]
+pub struct InstrumentSpec {
+	pub duty: u8,
+	pub envelope: u16,
+	pub sweep: u16,
+}
+
+struct Options {
+	/// The PSG channel each MIDI channel plays on.
+	map: [Option<AudioChannel>; 16],
+	instruments: [InstrumentSpec; 16],
+	rows_per_beat: u32,
+	rows_per_pattern: usize,
+	looping: bool,
+}
+
+pub type Row = [PatternCell; AudioChannel::COUNT];
+
+pub const EMPTY_CELL: PatternCell = PatternCell {
+	note: 0,
+	instrument: 0,
+	volume: INSTRUMENT_VOLUME,
+};
+
+pub fn convert(args: &[String]) {
+	let Some(filename) = args.first() else {
+		eprintln!("{USAGE}");
+		return;
+	};
+	let filename = Path::new(filename);
+	let Some(options) = parse_options(&args[1..]) else {
+		eprintln!("{USAGE}");
+		return;
+	};
+
+	let data = fs::read(filename).unwrap();
+	let smf = match Smf::parse(&data) {
+		Ok(smf) => smf,
+		Err(err) => {
+			eprintln!("Failed to read {}: {err}", filename.display());
+			return;
+		}
+	};
+	let Timing::Metrical(ticks_per_beat) = smf.header.timing else {
+		eprintln!("MIDI files timed in SMPTE frames aren't supported!");
+		return;
+	};
+	if smf.header.format == Format::Sequential {
+		eprintln!("Sequential (format 2) MIDI files aren't supported!");
+		return;
+	}
+	let ticks_per_beat = ticks_per_beat.as_int() as u64;
+
+	// merge the tracks, with note offs first so a note can be followed by one at the same tick
+	let mut events = Vec::new();
+	for track in &smf.tracks {
+		let mut tick = 0;
+		for event in track {
+			tick += event.delta.as_int() as u64;
+			events.push((tick, event.kind));
+		}
+	}
+	events.sort_by_key(|&(tick, kind)| (tick, !is_note_off(kind)));
+
+	let Some(arrangement) = arrange(&events, ticks_per_beat, &options) else {
+		eprintln!("The song is too long! Try fewer rows per beat.");
+		return;
+	};
+	let Arrangement {
+		mut grid,
+		instruments,
+		tempo,
+		unmapped,
+		overlaps,
+	} = arrangement;
+
+	for (midi_channel, _) in unmapped.iter().enumerate().filter(|(_, &notes)| notes) {
+		eprintln!(
+			"Warning: MIDI channel {} isn't mapped to a PSG channel, its notes were left out.",
+			midi_channel + 1
+		);
+	}
+	for (channel, (count, first)) in AudioChannel::ALL.iter().zip(overlaps) {
+		if count > 0 {
+			eprintln!(
+				"Warning: {count} notes on {} started while another was playing, cutting it off (first at row {first}).",
+				channel_name(*channel)
+			);
+		}
+	}
+	if instruments.len() > u8::MAX as usize {
+		eprintln!("The song uses too many instruments!");
+		return;
+	}
+
+	// 120 BPM unless the file says otherwise
+	let us_per_beat = tempo.unwrap_or(500_000) as u64;
+	let rows_per_minute =
+		(60_000_000 * options.rows_per_beat as u64 + us_per_beat / 2) / us_per_beat;
+	if rows_per_minute == 0 || rows_per_minute > u16::MAX as u64 {
+		eprintln!("The tempo is out of range! Try a different number of rows per beat.");
+		return;
+	}
+
+	// the last pattern is padded with empty rows
+	let rows = grid.len().div_ceil(options.rows_per_pattern).max(1) * options.rows_per_pattern;
+	grid.resize(rows, [EMPTY_CELL; AudioChannel::COUNT]);
+
+	let instruments: Vec<InstrumentSpec> = instruments
+		.iter()
+		.map(|&c| options.instruments[c])
+		.collect();
+	let Some(encoded) = encode(
+		&grid,
+		&instruments,
+		rows_per_minute as u16,
+		options.rows_per_pattern,
+		options.looping,
+	) else {
+		return;
+	};
+	debug_assert!(Song::parse(&encoded).is_some());
+
+	let output_filename = String::from(filename.file_stem().unwrap().to_str().unwrap()) + ".avksng";
+	fs::write(&output_filename, &encoded).unwrap();
+	println!(
+		"{output_filename}: {} rows at {rows_per_minute} rows per minute, {} bytes",
+		grid.len(),
+		encoded.len()
+	);
+}
+
+/// The notes of a MIDI file, laid out on rows.
+struct Arrangement {
+	grid: Vec<Row>,
+	/// MIDI channels in the order their instruments were added to the song.
+	instruments: Vec<usize>,
+	/// Microseconds per beat, if the file sets the tempo.
+	tempo: Option<u32>,
+	/// MIDI channels that have notes but aren't mapped to a PSG channel.
+	unmapped: [bool; 16],
+	/// How many notes on each PSG channel cut another off, and the row of the first one.
+	overlaps: [(usize, usize); AudioChannel::COUNT],
+}
+
+/// The row a tick is quantized to, the closest one.
+/// Ticks too far into the song for the math saturate, to be rejected as too long.
+fn quantize(tick: u64, ticks_per_beat: u64, rows_per_beat: u32) -> usize {
+	let ticks = tick.saturating_mul(rows_per_beat as u64);
+	(ticks.saturating_add(ticks_per_beat / 2) / ticks_per_beat) as usize
+}
+
+/// Lays the notes of the merged tracks out on rows, or returns None if they don't fit in a song.
+fn arrange(
+	events: &[(u64, TrackEventKind)],
+	ticks_per_beat: u64,
+	options: &Options,
+) -> Option<Arrangement> {
+	// every pattern has a different order, and the last order is NO_LOOP
+	let max_rows = (NO_LOOP as usize - 1) * options.rows_per_pattern;
+
+	let mut tempo = None;
+	let mut unmapped = [false; 16];
+	let mut grid: Vec<Row> = Vec::new();
+	// the note each PSG channel is playing, as its MIDI channel and key, and the row it started on
+	let mut playing: [Option<(usize, u8, usize)>; AudioChannel::COUNT] = [None; 4];
+	let mut overlaps = [(0, 0); AudioChannel::COUNT];
+	let mut instruments = Vec::new();
+
+	for &(tick, kind) in events {
+		let row = quantize(tick, ticks_per_beat, options.rows_per_beat);
+		let (midi_channel, key, velocity) = match kind {
+			TrackEventKind::Meta(MetaMessage::Tempo(us_per_beat)) => {
+				let us_per_beat = us_per_beat.as_int();
+				match tempo {
+					None => tempo = Some(us_per_beat),
+					Some(tempo) if tempo != us_per_beat => {
+						eprintln!(
+							"Warning: the tempo changes at row {row}, which isn't supported."
+						);
+					}
+					Some(_) => {}
+				}
+				continue;
+			}
+			TrackEventKind::Midi {
+				channel,
+				message: MidiMessage::NoteOn { key, vel },
+			} => (channel.as_int() as usize, key.as_int(), vel.as_int()),
+			TrackEventKind::Midi {
+				channel,
+				message: MidiMessage::NoteOff { key, .. },
+			} => (channel.as_int() as usize, key.as_int(), 0),
+			_ => continue,
+		};
+		let Some(channel) = options.map[midi_channel] else {
+			unmapped[midi_channel] |= velocity > 0;
+			continue;
+		};
+		let voice = channel as usize;
+		// a note off can land on the row after, and huge ticks would take gigabytes of rows
+		if row.saturating_add(2) > max_rows {
+			return None;
+		}
+		if grid.len() <= row + 1 {
+			grid.resize(row + 2, [EMPTY_CELL; AudioChannel::COUNT]);
+		}
+
+		if velocity == 0 {
+			let start = match playing[voice] {
+				Some((c, k, start)) if (c, k) == (midi_channel, key) => start,
+				// notes that were cut off have already stopped
+				_ => continue,
+			};
+			playing[voice] = None;
+			// notes shorter than a row still get to play for one
+			let row = if start == row { row + 1 } else { row };
+			if grid[row][voice].note == 0 {
+				grid[row][voice].note = NOTE_OFF;
+			}
+			continue;
+		}
+
+		if playing[voice].is_some() {
+			let (count, first) = &mut overlaps[voice];
+			if *count == 0 {
+				*first = row;
+			}
+			*count += 1;
+		}
+		let instrument = match instruments.iter().position(|&c| c == midi_channel) {
+			Some(index) => index,
+			None => {
+				instruments.push(midi_channel);
+				instruments.len() - 1
+			}
+		};
+		grid[row][voice] = PatternCell {
+			note: key,
+			instrument: instrument as u8,
+			volume: ((velocity as u16 * 15 + 63) / 127).max(1) as u8,
+		};
+		playing[voice] = Some((midi_channel, key, row));
+	}
+
+	Some(Arrangement {
+		grid,
+		instruments,
+		tempo,
+		unmapped,
+		overlaps,
+	})
+}
+
+fn is_note_off(kind: TrackEventKind) -> bool {
+	match kind {
+		TrackEventKind::Midi { message, .. } => match message {
+			MidiMessage::NoteOff { .. } => true,
+			MidiMessage::NoteOn { vel, .. } => vel == 0,
+			_ => false,
+		},
+		_ => false,
+	}
+}
+
+/// Splits the rows into patterns, reusing repeated ones, and encodes the song.
+/// There must be a whole number of patterns' worth of rows.
+pub fn encode(
+	grid: &[Row],
+	instruments: &[InstrumentSpec],
+	rows_per_minute: u16,
+	rows_per_pattern: usize,
+	looping: bool,
+) -> Option<Vec<u8>> {
+	let mut patterns: Vec<&[Row]> = Vec::new();
+	let mut orders = Vec::new();
+	for pattern in grid.chunks_exact(rows_per_pattern) {
+		let index = match patterns.iter().position(|&p| p == pattern) {
+			Some(index) => index,
+			None => {
+				patterns.push(pattern);
+				patterns.len() - 1
+			}
+		};
+		orders.push(index);
+	}
+	if patterns.len() > u8::MAX as usize || orders.len() >= NO_LOOP as usize {
+		eprintln!("The song is too long! Try fewer rows per beat.");
+		return None;
+	}
+
+	let mut encoded = Vec::with_capacity(SONG_HEADER_SIZE);
+	encoded.extend_from_slice(&SONG_MAGIC);
+	encoded.push(SONG_VERSION);
+	encoded.push(rows_per_pattern as u8);
+	encoded.push(instruments.len() as u8);
+	encoded.push(patterns.len() as u8);
+	encoded.push(orders.len() as u8);
+	encoded.push(if looping { 0 } else { NO_LOOP });
+	encoded.extend_from_slice(&rows_per_minute.to_le_bytes());
+	for instrument in instruments {
+		encoded.push(15);
+		encoded.push(instrument.duty);
+		encoded.extend_from_slice(&instrument.envelope.to_le_bytes());
+		encoded.extend_from_slice(&instrument.sweep.to_le_bytes());
+	}
+	for cell in patterns.iter().flat_map(|pattern| pattern.iter().flatten()) {
+		encoded.extend_from_slice(&[cell.note, cell.instrument, cell.volume]);
+	}
+	encoded.extend(orders.iter().map(|&order| order as u8));
+
+	if encoded.len() > MAX_SONG_SIZE {
+		eprintln!(
+			"The song is too big! It takes {} bytes, but songs can be at most {MAX_SONG_SIZE}. Try fewer rows per beat.",
+			encoded.len()
+		);
+		return None;
+	}
+	Some(encoded)
+}
+
+fn parse_options(args: &[String]) -> Option<Options> {
+	const DEFAULT_INSTRUMENT: InstrumentSpec = InstrumentSpec {
+		duty: 2,
+		envelope: 0,
+		sweep: 0,
+	};
+	let mut options = Options {
+		map: [None; 16],
+		instruments: [DEFAULT_INSTRUMENT; 16],
+		rows_per_beat: 4,
+		rows_per_pattern: 64,
+		looping: false,
+	};
+	let mut mapped = false;
+
+	let mut args = args.iter();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--map" => {
+				let (midi_channel, channel) = args.next()?.split_once('=')?;
+				let channel = AudioChannel::ALL
+					.into_iter()
+					.find(|&c| channel_name(c) == channel)?;
+				options.map[parse_midi_channel(midi_channel)?] = Some(channel);
+				mapped = true;
+			}
+			"--instrument" => {
+				let (midi_channel, spec) = args.next()?.split_once('=')?;
+				let mut values = spec.split(',');
+				let instrument = &mut options.instruments[parse_midi_channel(midi_channel)?];
+				instrument.duty = values.next()?.parse().ok().filter(|&duty| duty < 4)?;
+				instrument.envelope = values.next().map_or(Some(0), |v| v.parse().ok())?;
+				instrument.sweep = values.next().map_or(Some(0), |v| v.parse().ok())?;
+			}
+			"--rows-per-beat" => {
+				options.rows_per_beat = args.next()?.parse().ok().filter(|&rows| rows > 0)?;
+			}
+			"--rows-per-pattern" => {
+				options.rows_per_pattern = args
+					.next()?
+					.parse()
+					.ok()
+					.filter(|&rows| rows > 0 && rows <= u8::MAX as usize)?;
+			}
+			"--loop" => options.looping = true,
+			_ => return None,
+		}
+	}
+
+	if !mapped {
+		options.map[0] = Some(AudioChannel::Pulse1);
+		options.map[1] = Some(AudioChannel::Pulse2);
+		options.map[2] = Some(AudioChannel::Wave);
+		// the General MIDI percussion channel
+		options.map[9] = Some(AudioChannel::Noise);
+	}
+	Some(options)
+}
+
+/// Parses a MIDI channel numbered from 1 to 16, returning its index.
+fn parse_midi_channel(s: &str) -> Option<usize> {
+	let channel: usize = s.parse().ok()?;
+	(1..=16).contains(&channel).then_some(channel - 1)
+}
+
+fn channel_name(channel: AudioChannel) -> &'static str {
+	match channel {
+		AudioChannel::Pulse1 => "pulse1",
+		AudioChannel::Pulse2 => "pulse2",
+		AudioChannel::Wave => "wave",
+		AudioChannel::Noise => "noise",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use midly::num::{u4, u7};
+
+	fn note(tick: u64, channel: u8, key: u8, vel: u8) -> (u64, TrackEventKind<'static>) {
+		let message = MidiMessage::NoteOn {
+			key: u7::new(key),
+			vel: u7::new(vel),
+		};
+		let channel = u4::new(channel);
+		(tick, TrackEventKind::Midi { channel, message })
+	}
+
+	#[test]
+	fn ticks_round_to_the_closest_row() {
+		// 96 ticks per beat and 4 rows per beat make rows 24 ticks long
+		assert_eq!(quantize(0, 96, 4), 0);
+		assert_eq!(quantize(11, 96, 4), 0);
+		assert_eq!(quantize(12, 96, 4), 1);
+		assert_eq!(quantize(24, 96, 4), 1);
+		assert_eq!(quantize(96, 96, 4), 4);
+		assert_eq!(quantize(u64::MAX, 96, 4), (u64::MAX / 96) as usize);
+	}
+
+	#[test]
+	fn note_offs_stop_notes_on_their_row() {
+		let options = parse_options(&[]).unwrap();
+		// the first note ends on the row the second starts on, and the second is shorter than one
+		let events = [
+			note(0, 0, 60, 127),
+			note(96, 0, 60, 0),
+			note(96, 0, 62, 64),
+			note(100, 0, 62, 0),
+		];
+		let arrangement = arrange(&events, 96, &options).unwrap();
+		let voice = AudioChannel::Pulse1 as usize;
+		assert_eq!(arrangement.grid[0][voice].note, 60);
+		assert_eq!(arrangement.grid[0][voice].volume, 15);
+		assert_eq!(arrangement.grid[4][voice].note, 62);
+		assert_eq!(arrangement.grid[4][voice].volume, 8);
+		assert_eq!(arrangement.grid[5][voice].note, NOTE_OFF);
+		assert_eq!(arrangement.overlaps[voice].0, 0);
+	}
+
+	#[test]
+	fn overlapping_and_unmapped_notes_are_reported() {
+		let options = parse_options(&[]).unwrap();
+		let events = [
+			note(0, 1, 60, 100),
+			note(48, 1, 64, 100),
+			note(96, 1, 67, 100),
+			// the old note's note off doesn't stop the new one
+			note(96, 1, 64, 0),
+			note(0, 4, 60, 100),
+		];
+		let arrangement = arrange(&events, 96, &options).unwrap();
+		let voice = AudioChannel::Pulse2 as usize;
+		assert_eq!(arrangement.overlaps[voice], (2, 2));
+		assert_eq!(arrangement.grid[4][voice].note, 67);
+		assert_eq!(arrangement.grid[5][voice].note, 0);
+		assert!(arrangement.unmapped[4]);
+		assert_eq!(
+			arrangement.unmapped.iter().filter(|&&notes| notes).count(),
+			1
+		);
+		assert_eq!(arrangement.instruments, [1]);
+	}
+
+	#[test]
+	fn songs_longer_than_the_orders_are_rejected() {
+		let options = parse_options(&[]).unwrap();
+		let rows = (NO_LOOP as u64 - 1) * options.rows_per_pattern as u64;
+		// a row is 24 ticks long, and the note off lands on the row after
+		assert!(arrange(&[note((rows - 2) * 24, 0, 60, 100)], 96, &options).is_some());
+		assert!(arrange(&[note((rows - 1) * 24, 0, 60, 100)], 96, &options).is_none());
+		assert!(arrange(&[note(u64::MAX / 2, 0, 60, 100)], 96, &options).is_none());
+	}
+}
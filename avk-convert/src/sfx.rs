@@ -0,0 +1,649 @@
+//! `avk-convert sfx <file.sfx>` renders a sound effect described by a parameter file to an AVK
+//! sample resource, sfxr style.
+//!
+//! `avk-convert sfx preset <pickup|jump|hit|explosion> [seed]` rolls random parameters for a
+//! kind of sound effect, and writes both the parameter file and the sample, so a preset can be
+//! tweaked and rendered again.
+//!
+//! Parameter files hold one `name = value` per line, and `#` starts a comment:
+//! ```text
+//! wave = square           # square, triangle, sawtooth, sine or noise
+//! frequency = 440         # starting pitch, in Hz
+//! frequency_slide = 0     # pitch change, in octaves per second
+//! frequency_min = 20      # the sound stops once it slides below this pitch, in Hz
+//! vibrato_depth = 0       # as a fraction of the pitch
+//! vibrato_speed = 0       # in Hz
+//! duty = 0.5              # square wave duty cycle
+//! duty_slide = 0          # duty cycle change, per second
+//! attack = 0              # in seconds
+//! sustain = 0.1           # in seconds
+//! punch = 0               # extra volume at the start of the sustain, fading out during it
+//! decay = 0.2             # in seconds
+//! volume = 0.8            # from 0 to 1
+//! sample_rate = 22050     # in Hz
+//! seed = 1                # for the noise
+//! ```
+//! Any of them can be left out to use the values above.
+//!
+//! With `--psg`, the sound effect is written as a one-shot song for `Avk::play_effect` instead,
+//! playing on a single PSG channel: the first pulse channel for square waves, the noise channel
+//! for noise, and the wave channel, with its triangle, for the others. The pitch and volume are
+//! updated 60 times a second, rounded to the closest note and volume step, and the duty cycle to
+//! the closest one the pulse channel has. `sample_rate` doesn't apply.
+
+use crate::midi::{self, InstrumentSpec, EMPTY_CELL};
+use crate::wav;
+use avk_types::prelude::*;
+use avk_types::{
+	PatternCell, MAX_SAMPLE_SIZE, MAX_SONG_SIZE, NOTE_OFF, SAMPLE_HEADER_SIZE, SONG_CELL_SIZE,
+	SONG_HEADER_SIZE, SONG_INSTRUMENT_SIZE,
+};
+use std::f32::consts::TAU;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "Usage: avk-convert sfx [--psg] <file.sfx>
+       avk-convert sfx [--psg] preset <pickup|jump|hit|explosion> [seed]
+  --psg   writes a song for Avk::play_effect instead of a sample";
+
+/// Values the noise wave picks from each period.
+const NOISE_STEPS: usize = 32;
+
+/// The most PCM samples a sample resource holds, two per byte.
+const MAX_LEN: usize = (MAX_SAMPLE_SIZE - SAMPLE_HEADER_SIZE) * 2;
+
+/// Rows per second of PSG sound effects.
+const PSG_ROW_RATE: usize = 60;
+const PSG_ROWS_PER_PATTERN: usize = 64;
+/// The most rows a song holds, leaving room for the header, the instruments and the orders.
+const MAX_PSG_ROWS: usize = (MAX_SONG_SIZE - SONG_HEADER_SIZE - 4 * SONG_INSTRUMENT_SIZE - 0xFF)
+	/ (AudioChannel::COUNT * SONG_CELL_SIZE);
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Wave {
+	Square,
+	Triangle,
+	Sawtooth,
+	Sine,
+	Noise,
+}
+
+impl Wave {
+	const ALL: [Wave; 5] = [
+		Wave::Square,
+		Wave::Triangle,
+		Wave::Sawtooth,
+		Wave::Sine,
+		Wave::Noise,
+	];
+
+	fn name(self) -> &'static str {
+		match self {
+			Wave::Square => "square",
+			Wave::Triangle => "triangle",
+			Wave::Sawtooth => "sawtooth",
+			Wave::Sine => "sine",
+			Wave::Noise => "noise",
+		}
+	}
+
+	/// The PSG channel that sounds the most like the wave.
+	fn channel(self) -> AudioChannel {
+		match self {
+			Wave::Square => AudioChannel::Pulse1,
+			Wave::Noise => AudioChannel::Noise,
+			Wave::Triangle | Wave::Sawtooth | Wave::Sine => AudioChannel::Wave,
+		}
+	}
+}
+
+/// Describes a sound effect. See the module documentation for what each parameter does.
+#[derive(Copy, Clone)]
+struct Params {
+	wave: Wave,
+	frequency: f32,
+	frequency_slide: f32,
+	frequency_min: f32,
+	vibrato_depth: f32,
+	vibrato_speed: f32,
+	duty: f32,
+	duty_slide: f32,
+	attack: f32,
+	sustain: f32,
+	punch: f32,
+	decay: f32,
+	volume: f32,
+	sample_rate: u16,
+	seed: u32,
+}
+
+impl Default for Params {
+	fn default() -> Self {
+		Self {
+			wave: Wave::Square,
+			frequency: 440.0,
+			frequency_slide: 0.0,
+			frequency_min: 20.0,
+			vibrato_depth: 0.0,
+			vibrato_speed: 0.0,
+			duty: 0.5,
+			duty_slide: 0.0,
+			attack: 0.0,
+			sustain: 0.1,
+			punch: 0.0,
+			decay: 0.2,
+			volume: 0.8,
+			sample_rate: 22050,
+			seed: 1,
+		}
+	}
+}
+
+impl Params {
+	fn parse(text: &str) -> Result<Self, String> {
+		let mut params = Self::default();
+		for (i, line) in text.lines().enumerate() {
+			let line = line.split('#').next().unwrap().trim();
+			if line.is_empty() {
+				continue;
+			}
+			let error = |message: &str| format!("line {}: {message}", i + 1);
+			let (name, value) = line
+				.split_once('=')
+				.ok_or_else(|| error("expected `name = value`"))?;
+			let (name, value) = (name.trim(), value.trim());
+			let number = || {
+				value
+					.parse::<f32>()
+					.ok()
+					.filter(|value| value.is_finite())
+					.ok_or_else(|| error(&format!("`{value}` isn't a number")))
+			};
+			let duration = || {
+				number().and_then(|value| {
+					if value < 0.0 {
+						return Err(error("durations can't be negative"));
+					}
+					Ok(value)
+				})
+			};
+			match name {
+				"wave" => {
+					params.wave = Wave::ALL
+						.into_iter()
+						.find(|wave| wave.name() == value)
+						.ok_or_else(|| error(&format!("unknown wave `{value}`")))?;
+				}
+				"frequency" => params.frequency = number()?,
+				"frequency_slide" => params.frequency_slide = number()?,
+				"frequency_min" => params.frequency_min = number()?,
+				"vibrato_depth" => params.vibrato_depth = number()?,
+				"vibrato_speed" => params.vibrato_speed = number()?,
+				"duty" => params.duty = number()?,
+				"duty_slide" => params.duty_slide = number()?,
+				"attack" => params.attack = duration()?,
+				"sustain" => params.sustain = duration()?,
+				"punch" => params.punch = number()?,
+				"decay" => params.decay = duration()?,
+				"volume" => params.volume = number()?,
+				"sample_rate" => {
+					params.sample_rate =
+						value.parse().ok().filter(|&rate| rate > 0).ok_or_else(|| {
+							error("the sample rate must be between 1 and 65535 Hz")
+						})?;
+				}
+				"seed" => {
+					params.seed = value
+						.parse()
+						.map_err(|_| error(&format!("`{value}` isn't a seed")))?;
+				}
+				_ => return Err(error(&format!("unknown parameter `{name}`"))),
+			}
+		}
+		// rendering allocates for the whole duration up front
+		let duration = params.attack as f64 + params.sustain as f64 + params.decay as f64;
+		if duration * params.sample_rate as f64 > MAX_LEN as f64 {
+			return Err(format!(
+				"the sound effect lasts {duration:.3} seconds, but samples can hold at most {:.3} at {} Hz",
+				MAX_LEN as f64 / params.sample_rate as f64,
+				params.sample_rate
+			));
+		}
+		Ok(params)
+	}
+
+	/// Rolls random parameters for a kind of sound effect, or returns None if there's no such
+	/// preset.
+	fn preset(name: &str, rng: &mut Rng) -> Option<Self> {
+		let mut params = Self {
+			seed: rng.next(),
+			..Self::default()
+		};
+		match name {
+			"pickup" => {
+				params.wave = rng.pick(&[Wave::Square, Wave::Sawtooth]);
+				params.frequency = rng.range(600.0, 1400.0);
+				let slide = rng.range(0.5, 2.0);
+				params.frequency_slide = rng.pick(&[0.0, slide]);
+				params.duty = rng.range(0.25, 0.5);
+				params.sustain = rng.range(0.02, 0.08);
+				params.punch = rng.range(0.3, 0.6);
+				params.decay = rng.range(0.1, 0.3);
+			}
+			"jump" => {
+				params.wave = Wave::Square;
+				params.frequency = rng.range(250.0, 600.0);
+				params.frequency_slide = rng.range(1.0, 3.0);
+				params.duty = rng.range(0.2, 0.5);
+				params.sustain = rng.range(0.05, 0.15);
+				params.decay = rng.range(0.1, 0.25);
+			}
+			"hit" => {
+				params.wave = rng.pick(&[Wave::Square, Wave::Sawtooth, Wave::Noise]);
+				params.frequency = rng.range(150.0, 600.0);
+				params.frequency_slide = -rng.range(3.0, 6.0);
+				params.duty = rng.range(0.2, 0.5);
+				params.sustain = rng.range(0.01, 0.05);
+				params.punch = rng.range(0.0, 0.5);
+				params.decay = rng.range(0.05, 0.2);
+			}
+			"explosion" => {
+				params.wave = Wave::Noise;
+				params.frequency = rng.range(40.0, 300.0);
+				params.frequency_slide = -rng.range(0.5, 2.0);
+				params.frequency_min = 5.0;
+				if rng.range(0.0, 1.0) < 0.5 {
+					params.vibrato_depth = rng.range(0.1, 0.4);
+					params.vibrato_speed = rng.range(5.0, 20.0);
+				}
+				params.sustain = rng.range(0.1, 0.3);
+				params.punch = rng.range(0.2, 0.6);
+				params.decay = rng.range(0.3, 0.6);
+			}
+			_ => return None,
+		}
+		Some(params)
+	}
+
+	/// Renders the sound effect to 16-bit PCM.
+	fn render(&self) -> Vec<i16> {
+		let rate = self.sample_rate as f32;
+		// parse checked the duration, this only guards against f32 rounding
+		let len = (((self.attack + self.sustain + self.decay) * rate) as usize).min(MAX_LEN);
+		let mut rng = Rng::new(self.seed);
+		let mut noise = [0.0; NOISE_STEPS];
+		rng.fill(&mut noise);
+		let mut phase = 0.0f32;
+
+		let mut pcm = Vec::with_capacity(len);
+		for i in 0..len {
+			let t = i as f32 / rate;
+			let Some(frequency) = self.pitch(t) else {
+				break;
+			};
+			phase += frequency / rate;
+			if phase >= 1.0 {
+				phase = phase.fract();
+				if self.wave == Wave::Noise {
+					rng.fill(&mut noise);
+				}
+			}
+
+			let sample = match self.wave {
+				Wave::Square => {
+					if phase < self.duty(t) {
+						1.0
+					} else {
+						-1.0
+					}
+				}
+				Wave::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+				Wave::Sawtooth => 1.0 - 2.0 * phase,
+				Wave::Sine => (TAU * phase).sin(),
+				Wave::Noise => noise[(phase * NOISE_STEPS as f32) as usize % NOISE_STEPS],
+			};
+			let sample = (sample * self.envelope(t) * self.volume).clamp(-1.0, 1.0);
+			pcm.push((sample * i16::MAX as f32) as i16);
+		}
+		pcm
+	}
+
+	/// Renders the sound effect to a song playing it once, or returns None if it doesn't fit.
+	fn render_song(&self) -> Option<Vec<u8>> {
+		let duration = self.attack + self.sustain + self.decay;
+		let len = (duration * PSG_ROW_RATE as f32).ceil() as usize;
+		// and a row to silence it
+		if len + 1 > MAX_PSG_ROWS {
+			eprintln!(
+				"The sound effect is too long! It lasts {duration:.3} seconds, but songs can hold at most {:.3}.",
+				MAX_PSG_ROWS as f32 / PSG_ROW_RATE as f32
+			);
+			return None;
+		}
+
+		let channel = self.wave.channel() as usize;
+		let mut grid = Vec::with_capacity(len + 1);
+		let mut last = None;
+		for i in 0..len {
+			let t = i as f32 / PSG_ROW_RATE as f32;
+			let Some(frequency) = self.pitch(t) else {
+				break;
+			};
+			let volume = (self.envelope(t) * self.volume * 15.0)
+				.round()
+				.clamp(0.0, 15.0) as u8;
+			let cell = if volume == 0 {
+				PatternCell {
+					note: NOTE_OFF,
+					..EMPTY_CELL
+				}
+			} else {
+				PatternCell {
+					note: note(frequency),
+					instrument: self.duty_register(t),
+					volume,
+				}
+			};
+			let mut row = [EMPTY_CELL; AudioChannel::COUNT];
+			// the PSG keeps playing until told otherwise
+			if last != Some(cell) {
+				row[channel] = cell;
+				last = Some(cell);
+			}
+			grid.push(row);
+		}
+		let mut row = [EMPTY_CELL; AudioChannel::COUNT];
+		row[channel].note = NOTE_OFF;
+		grid.push(row);
+		grid.resize(
+			grid.len().div_ceil(PSG_ROWS_PER_PATTERN) * PSG_ROWS_PER_PATTERN,
+			[EMPTY_CELL; AudioChannel::COUNT],
+		);
+
+		// one instrument per duty cycle, the noise channel only uses white noise
+		let instruments = [0, 1, 2, 3].map(|duty| InstrumentSpec {
+			duty,
+			envelope: 0,
+			sweep: 0,
+		});
+		let rows_per_minute = (PSG_ROW_RATE * 60) as u16;
+		midi::encode(
+			&grid,
+			&instruments,
+			rows_per_minute,
+			PSG_ROWS_PER_PATTERN,
+			false,
+		)
+	}
+
+	/// Returns the volume at `t` seconds, from 0 to 1 plus the punch.
+	fn envelope(&self, t: f32) -> f32 {
+		if t < self.attack {
+			t / self.attack
+		} else if t < self.attack + self.sustain {
+			1.0 + self.punch * (1.0 - (t - self.attack) / self.sustain)
+		} else {
+			1.0 - (t - self.attack - self.sustain) / self.decay
+		}
+	}
+
+	/// Returns the pitch at `t` seconds, in Hz, or None once it's slid below the minimum.
+	fn pitch(&self, t: f32) -> Option<f32> {
+		let frequency = self.frequency * 2f32.powf(self.frequency_slide * t);
+		if frequency < self.frequency_min {
+			return None;
+		}
+		let vibrato = 1.0 + self.vibrato_depth * (TAU * self.vibrato_speed * t).sin();
+		Some(frequency * vibrato)
+	}
+
+	/// Returns the square wave's duty cycle at `t` seconds.
+	fn duty(&self, t: f32) -> f32 {
+		(self.duty + self.duty_slide * t).clamp(0.05, 0.95)
+	}
+
+	/// Returns the [`AudioRegister::Duty`] value closest to the duty cycle at `t` seconds.
+	fn duty_register(&self, t: f32) -> u8 {
+		if self.wave != Wave::Square {
+			return 0;
+		}
+		match self.duty(t) {
+			duty if duty < 0.1875 => 0,
+			duty if duty < 0.375 => 1,
+			duty if duty < 0.625 => 2,
+			_ => 3,
+		}
+	}
+}
+
+/// Returns the MIDI note closest to a frequency, in Hz.
+fn note(frequency: f32) -> u8 {
+	(69.0 + 12.0 * (frequency / 440.0).log2())
+		.round()
+		.clamp(1.0, 127.0) as u8
+}
+
+impl Display for Params {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		writeln!(f, "wave = {}", self.wave.name())?;
+		for (name, value) in [
+			("frequency", self.frequency),
+			("frequency_slide", self.frequency_slide),
+			("frequency_min", self.frequency_min),
+			("vibrato_depth", self.vibrato_depth),
+			("vibrato_speed", self.vibrato_speed),
+			("duty", self.duty),
+			("duty_slide", self.duty_slide),
+			("attack", self.attack),
+			("sustain", self.sustain),
+			("punch", self.punch),
+			("decay", self.decay),
+			("volume", self.volume),
+		] {
+			writeln!(f, "{name} = {value:.3}")?;
+		}
+		writeln!(f, "sample_rate = {}", self.sample_rate)?;
+		writeln!(f, "seed = {}", self.seed)
+	}
+}
+
+/// A xorshift generator, so the same seed always gives the same sound.
+struct Rng(u32);
+
+impl Rng {
+	fn new(seed: u32) -> Self {
+		// xorshift gets stuck at 0
+		Self(seed.max(1))
+	}
+
+	fn next(&mut self) -> u32 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 17;
+		self.0 ^= self.0 << 5;
+		self.0
+	}
+
+	fn range(&mut self, min: f32, max: f32) -> f32 {
+		min + (max - min) * (self.next() as f32 / u32::MAX as f32)
+	}
+
+	/// Fills `values` with random values from -1 to 1.
+	fn fill(&mut self, values: &mut [f32]) {
+		for value in values {
+			*value = self.range(-1.0, 1.0);
+		}
+	}
+
+	fn pick<T: Copy>(&mut self, choices: &[T]) -> T {
+		choices[self.next() as usize % choices.len()]
+	}
+}
+
+pub fn convert(args: &[String]) {
+	let psg = args.first().is_some_and(|arg| arg == "--psg");
+	let args = if psg { &args[1..] } else { args };
+	let (params, name) = match args {
+		[preset, kind, rest @ ..] if preset == "preset" && rest.len() <= 1 => {
+			let seed = match rest.first() {
+				Some(seed) => match seed.parse() {
+					Ok(seed) => seed,
+					Err(_) => {
+						eprintln!("{USAGE}");
+						return;
+					}
+				},
+				None => SystemTime::now()
+					.duration_since(UNIX_EPOCH)
+					.unwrap()
+					.subsec_nanos(),
+			};
+			let Some(params) = Params::preset(kind, &mut Rng::new(seed)) else {
+				eprintln!("{USAGE}");
+				return;
+			};
+			// the values are rounded in the file, so render what it says
+			let text = params.to_string();
+			let params = Params::parse(&text).unwrap();
+			let params_filename = format!("{kind}.sfx");
+			fs::write(&params_filename, text).unwrap();
+			println!("{params_filename}: preset rolled with seed {seed}");
+			(params, kind.clone())
+		}
+		[filename] => {
+			let filename = Path::new(filename);
+			let text = fs::read_to_string(filename).unwrap();
+			let params = match Params::parse(&text) {
+				Ok(params) => params,
+				Err(err) => {
+					eprintln!("{}: {err}", filename.display());
+					return;
+				}
+			};
+			let name = filename.file_stem().unwrap().to_str().unwrap();
+			(params, String::from(name))
+		}
+		_ => {
+			eprintln!("{USAGE}");
+			return;
+		}
+	};
+
+	if psg {
+		let Some(encoded) = params.render_song() else {
+			return;
+		};
+		debug_assert!(Song::parse(&encoded).is_some());
+		let output_filename = name + ".avksng";
+		fs::write(&output_filename, &encoded).unwrap();
+		println!("{output_filename}: {} bytes", encoded.len());
+		return;
+	}
+
+	let pcm = params.render();
+	let encoded = wav::encode(&pcm, params.sample_rate);
+	debug_assert!(Sample::parse(&encoded).is_some());
+
+	let output_filename = name + ".avksmp";
+	fs::write(&output_filename, &encoded).unwrap();
+	println!(
+		"{output_filename}: {} samples at {} Hz, {} bytes",
+		pcm.len(),
+		params.sample_rate,
+		encoded.len()
+	);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_parameters() {
+		let params = Params::parse(
+			"# a comment\n\nwave = noise # trailing\n  frequency=220\nsustain = 0.5\nseed = 7\n",
+		)
+		.unwrap();
+		assert!(params.wave == Wave::Noise);
+		assert_eq!(params.frequency, 220.0);
+		assert_eq!(params.sustain, 0.5);
+		assert_eq!(params.seed, 7);
+		// left out, so the default
+		assert_eq!(params.decay, Params::default().decay);
+	}
+
+	#[test]
+	fn round_trips_through_display() {
+		let params = Params::preset("explosion", &mut Rng::new(3)).unwrap();
+		let text = params.to_string();
+		assert_eq!(Params::parse(&text).unwrap().to_string(), text);
+	}
+
+	#[test]
+	fn rejects_bad_lines() {
+		for (text, error) in [
+			("wave square", "line 1: expected `name = value`"),
+			("\nwave = pulse", "line 2: unknown wave `pulse`"),
+			("frequency = fast", "line 1: `fast` isn't a number"),
+			("frequency = inf", "line 1: `inf` isn't a number"),
+			("attack = -1", "line 1: durations can't be negative"),
+			(
+				"sample_rate = 0",
+				"line 1: the sample rate must be between 1 and 65535 Hz",
+			),
+			(
+				"sample_rate = 70000",
+				"line 1: the sample rate must be between 1 and 65535 Hz",
+			),
+			("seed = -1", "line 1: `-1` isn't a seed"),
+			("pitch = 1", "line 1: unknown parameter `pitch`"),
+		] {
+			assert_eq!(Params::parse(text).err().as_deref(), Some(error), "{text}");
+		}
+	}
+
+	#[test]
+	fn rejects_effects_too_long_for_a_sample() {
+		assert!(Params::parse("sustain = 1e9").is_err());
+		let max = MAX_LEN as f32 / 22050.0;
+		assert!(Params::parse(&format!("sustain = {}\ndecay = 0", max * 0.99)).is_ok());
+		assert!(Params::parse(&format!("sustain = {}\ndecay = 0", max * 1.01)).is_err());
+	}
+
+	#[test]
+	fn renders_songs_playing_once() {
+		for kind in ["pickup", "jump", "hit", "explosion"] {
+			let params = Params::preset(kind, &mut Rng::new(5)).unwrap();
+			let encoded = params.render_song().unwrap();
+			let song = Song::parse(&encoded).unwrap();
+			assert_eq!(song.loop_order(), None);
+			// the sound starts on the first row, on the wave's channel only
+			let channel = params.wave.channel();
+			assert!(![0, NOTE_OFF].contains(&song.cell(0, 0, channel).note));
+			for other in AudioChannel::ALL.into_iter().filter(|&c| c != channel) {
+				assert_eq!(song.cell(0, 0, other).note, 0);
+			}
+		}
+	}
+
+	#[test]
+	fn rounds_to_notes() {
+		assert_eq!(note(440.0), 69);
+		assert_eq!(note(450.0), 69);
+		assert_eq!(note(261.63), 60);
+		assert_eq!(note(1.0), 1);
+		assert_eq!(note(1e6), 127);
+	}
+
+	#[test]
+	fn renders_the_longest_effect_into_a_sample() {
+		let params = Params::parse(&format!(
+			"sustain = {}\ndecay = 0",
+			MAX_LEN as f32 / 22050.0 * 0.999
+		))
+		.unwrap();
+		let encoded = wav::encode(&params.render(), params.sample_rate);
+		assert!(Sample::parse(&encoded).is_some());
+	}
+}
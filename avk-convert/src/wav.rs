@@ -0,0 +1,147 @@
+//! `avk-convert wav <file.wav> [sample rate]` encodes a WAV file as an AVK sample resource.
+
+use avk_types::prelude::*;
+use avk_types::{AdpcmState, MAX_SAMPLE_SIZE, SAMPLE_HEADER_SIZE, SAMPLE_MAGIC, SAMPLE_VERSION};
+use hound::{SampleFormat, WavReader};
+use std::fs;
+use std::path::Path;
+
+pub fn convert(args: &[String]) {
+	let Some(filename) = args.first() else {
+		eprintln!("Usage: avk-convert wav <file.wav> [sample rate]");
+		return;
+	};
+	let filename = Path::new(filename);
+	let mut reader = WavReader::open(filename).unwrap();
+	let spec = reader.spec();
+
+	// read it as floats from -1 to 1
+	let samples: Vec<f32> = match spec.sample_format {
+		SampleFormat::Float => reader.samples::<f32>().map(Result::unwrap).collect(),
+		SampleFormat::Int => {
+			let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+			reader
+				.samples::<i32>()
+				.map(|sample| sample.unwrap() as f32 * scale)
+				.collect()
+		}
+	};
+	// samples are mono
+	let mono: Vec<f32> = samples
+		.chunks(spec.channels as usize)
+		.map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+		.collect();
+
+	let sample_rate = match args.get(1) {
+		Some(rate) => match rate.parse::<u16>() {
+			Ok(rate) if rate > 0 => rate,
+			_ => {
+				eprintln!("The sample rate must be between 1 and {} Hz!", u16::MAX);
+				return;
+			}
+		},
+		None => spec.sample_rate.min(u16::MAX as u32) as u16,
+	};
+	let pcm = resample(&mono, spec.sample_rate, sample_rate as u32);
+
+	let encoded = encode(&pcm, sample_rate);
+	if encoded.len() > MAX_SAMPLE_SIZE {
+		eprintln!(
+			"The sample is too long! It takes {} bytes, but samples can be at most {MAX_SAMPLE_SIZE}. Try a lower sample rate.",
+			encoded.len()
+		);
+		return;
+	}
+	debug_assert!(Sample::parse(&encoded).is_some());
+
+	let output_filename = String::from(filename.file_stem().unwrap().to_str().unwrap()) + ".avksmp";
+	fs::write(&output_filename, &encoded).unwrap();
+	println!(
+		"{output_filename}: {} samples at {sample_rate} Hz, {} bytes",
+		pcm.len(),
+		encoded.len()
+	);
+}
+
+/// Converts between sample rates, interpolating linearly.
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<i16> {
+	let len = (samples.len() as u64 * to as u64 / from as u64) as usize;
+	(0..len)
+		.map(|i| {
+			let position = i as f64 * from as f64 / to as f64;
+			let index = position as usize;
+			let a = samples[index];
+			let b = samples.get(index + 1).copied().unwrap_or(a);
+			let sample = a + (b - a) * position.fract() as f32;
+			(sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+		})
+		.collect()
+}
+
+/// Encodes 16-bit PCM as an AVK sample resource.
+pub fn encode(pcm: &[i16], sample_rate: u16) -> Vec<u8> {
+	let mut state = AdpcmState::new(pcm.first().copied().unwrap_or(0), 0);
+
+	let mut encoded = Vec::with_capacity(SAMPLE_HEADER_SIZE + pcm.len().div_ceil(2));
+	encoded.extend_from_slice(&SAMPLE_MAGIC);
+	encoded.push(SAMPLE_VERSION);
+	encoded.push(state.step_index);
+	encoded.extend_from_slice(&sample_rate.to_le_bytes());
+	encoded.extend_from_slice(&state.predictor.to_le_bytes());
+	encoded.extend_from_slice(&0u16.to_le_bytes());
+	encoded.extend_from_slice(&(pcm.len() as u32).to_le_bytes());
+
+	for pair in pcm.chunks(2) {
+		let low = state.encode(pair[0]);
+		let high = pair.get(1).map_or(0, |&sample| state.encode(sample));
+		encoded.push(low | high << 4);
+	}
+	encoded
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn encodes_samples_the_runner_decodes() {
+		// a 440 Hz tone, with an odd sample count
+		let pcm: Vec<i16> = (0..2205)
+			.map(|i| ((i as f32 / 22050.0 * 440.0 * std::f32::consts::TAU).sin() * 16000.0) as i16)
+			.collect();
+		let encoded = encode(&pcm, 22050);
+		assert_eq!(encoded.len(), SAMPLE_HEADER_SIZE + pcm.len().div_ceil(2));
+
+		let sample = Sample::parse(&encoded).unwrap();
+		assert_eq!(sample.sample_rate(), 22050);
+		let decoded: Vec<i16> = sample.decode().collect();
+		assert_eq!(decoded.len(), pcm.len());
+		// the step size takes a few samples to grow from the smallest one
+		let error = pcm[32..]
+			.iter()
+			.zip(&decoded[32..])
+			.map(|(&a, &b)| (a as i32 - b as i32).abs())
+			.max()
+			.unwrap();
+		assert!(error < 600, "off by up to {error}");
+	}
+
+	#[test]
+	fn encodes_silence() {
+		let encoded = encode(&[], 8000);
+		let sample = Sample::parse(&encoded).unwrap();
+		assert!(sample.is_empty());
+		assert_eq!(sample.decode().count(), 0);
+	}
+
+	#[test]
+	fn resamples() {
+		let samples = [0.0, 0.5, 1.0, -1.0];
+		assert_eq!(resample(&samples, 100, 100).len(), 4);
+		let up = resample(&samples, 100, 200);
+		assert_eq!(up.len(), 8);
+		// interpolated halfway between the first two
+		assert_eq!(up[1], (0.25 * i16::MAX as f32) as i16);
+		assert_eq!(resample(&samples, 200, 100), [0, i16::MAX]);
+	}
+}
@@ -0,0 +1,188 @@
+//! Generates avk.h, the C SDK header, from the #[repr(C)] types and constants of the avk crate.
+//! The `AVK_*` function pointers and the `avk_*` wrappers calling them come from the native
+//! declarations in avk/src/avk.rs, so a call only has to be declared once.
+//! Run it with `cargo run -p avk-header` after changing them or avk/cbindgen.toml. Its test fails
+//! while the checked-in header is out of date.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use syn::{FnArg, Item, ItemFn, Pat, ReturnType, Type};
+
+/// Calls whose C wrapper does more than forward its arguments, written by hand in the trailer.
+const HAND_WRITTEN: &[&str] = &["avk_get_pointer", "avk_report_panic"];
+
+/// The workspace, which holds both the avk crate and avk.h.
+fn workspace_dir() -> &'static Path {
+	Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap()
+}
+
+/// Whether an item is only compiled for native ROMs, which call the runner through pointers.
+fn is_native(item: &ItemFn) -> bool {
+	item.attrs.iter().any(|attr| {
+		attr.path().is_ident("cfg")
+			&& attr
+				.meta
+				.require_list()
+				.is_ok_and(|list| list.tokens.to_string() == r#"not (target_arch = "wasm32")"#)
+	})
+}
+
+/// The C spelling of a type the runner's calls take or return.
+fn c_type(ty: &Type, func: &str) -> String {
+	match ty {
+		Type::Ptr(ptr) if ptr.const_token.is_some() => {
+			format!("const {} *", c_type(&ptr.elem, func))
+		}
+		Type::Ptr(ptr) => format!("{} *", c_type(&ptr.elem, func)),
+		Type::Path(path) => {
+			let Some(ident) = path.path.get_ident() else {
+				panic!("{func} takes a type avk.h can't name!");
+			};
+			match ident.to_string().as_str() {
+				"u8" => "uint8_t",
+				"u16" => "uint16_t",
+				"u32" => "uint32_t",
+				"u64" => "uint64_t",
+				"i8" => "int8_t",
+				"i16" => "int16_t",
+				"i32" => "int32_t",
+				"i64" => "int64_t",
+				"usize" => "uintptr_t",
+				"isize" => "intptr_t",
+				"f32" => "float",
+				"f64" => "double",
+				name => name,
+			}
+			.to_string()
+		}
+		_ => panic!("{func} takes a type avk.h can't name!"),
+	}
+}
+
+/// Declares `name` with a C type, keeping pointer stars next to the name like cbindgen does.
+fn declare(ty: &str, name: &str) -> String {
+	if ty.ends_with('*') {
+		format!("{ty}{name}")
+	} else {
+		format!("{ty} {name}")
+	}
+}
+
+/// Writes the inline function calling the pointer the runner wrote for a native call.
+fn write_wrapper(out: &mut String, func: &ItemFn) {
+	let name = func.sig.ident.to_string();
+	let pointer = name.to_uppercase();
+
+	for attr in &func.attrs {
+		if let Ok(syn::Expr::Lit(syn::ExprLit {
+			lit: syn::Lit::Str(doc),
+			..
+		})) = attr.meta.require_name_value().map(|doc| &doc.value)
+		{
+			let doc = doc.value();
+			writeln!(out, "//{doc}").unwrap();
+		}
+	}
+
+	let mut params = Vec::new();
+	let mut types = Vec::new();
+	let mut args = Vec::new();
+	for input in &func.sig.inputs {
+		let FnArg::Typed(input) = input else {
+			panic!("{name} takes self!");
+		};
+		let Pat::Ident(arg) = &*input.pat else {
+			panic!("{name} has an argument without a name!");
+		};
+		let ty = c_type(&input.ty, &name);
+		params.push(declare(&ty, &arg.ident.to_string()));
+		args.push(arg.ident.to_string());
+		types.push(ty);
+	}
+	let (params, types, args) = (params.join(", "), types.join(", "), args.join(", "));
+	let ret = match &func.sig.output {
+		ReturnType::Default => "void".to_string(),
+		ReturnType::Type(_, ty) => c_type(ty, &name),
+	};
+
+	writeln!(out, "inline static {}({params}) {{", declare(&ret, &name)).unwrap();
+	writeln!(
+		out,
+		"    {}({types}) = ({}({types})){pointer};",
+		declare(&ret, "(*fp)"),
+		declare(&ret, "(*)")
+	)
+	.unwrap();
+	if ret == "void" {
+		writeln!(out, "    fp({args});").unwrap();
+	} else {
+		writeln!(out, "    return fp({args});").unwrap();
+	}
+	writeln!(out, "}}\n").unwrap();
+}
+
+/// The pointers and wrappers for every native call in avk/src/avk.rs, along with their names, so
+/// cbindgen can be kept from exporting them a second time.
+fn runner_calls() -> (String, Vec<String>) {
+	let path = workspace_dir().join("avk/src/avk.rs");
+	let source = fs::read_to_string(&path)
+		.unwrap_or_else(|err| panic!("Failed to read {}: {err}", path.display()));
+	let file = syn::parse_file(&source)
+		.unwrap_or_else(|err| panic!("Failed to parse {}: {err}", path.display()));
+
+	let mut pointers = String::from("// Function pointers to be filled in by the AVK loader.\n");
+	let mut wrappers = String::new();
+	let mut names = Vec::new();
+	for item in &file.items {
+		match item {
+			Item::Static(item) if item.ident.to_string().starts_with("AVK_") => {
+				writeln!(pointers, "AVK_EXPORT void *{} = 0;", item.ident).unwrap();
+				names.push(item.ident.to_string());
+			}
+			Item::Fn(item) if is_native(item) && item.sig.ident.to_string().starts_with("avk_") => {
+				if !HAND_WRITTEN.contains(&item.sig.ident.to_string().as_str()) {
+					write_wrapper(&mut wrappers, item);
+				}
+				names.push(item.sig.ident.to_string());
+			}
+			_ => {}
+		}
+	}
+	(format!("{pointers}\n{wrappers}"), names)
+}
+
+fn generate() -> Vec<u8> {
+	let crate_dir = workspace_dir().join("avk");
+	let mut config = cbindgen::Config::from_root_or_default(&crate_dir);
+	let (calls, names) = runner_calls();
+	config.trailer = Some(calls + config.trailer.as_deref().unwrap_or_default());
+	config.export.exclude.extend(names);
+
+	let bindings = cbindgen::generate_with_config(&crate_dir, config)
+		.unwrap_or_else(|err| panic!("Failed to generate avk.h: {err}"));
+	let mut header = Vec::new();
+	bindings.write(&mut header);
+	header
+}
+
+fn main() {
+	let path = workspace_dir().join("avk.h");
+	fs::write(&path, generate())
+		.unwrap_or_else(|err| panic!("Failed to write {}: {err}", path.display()));
+	println!("Wrote {}", path.display());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn header_is_up_to_date() {
+		let header = fs::read(workspace_dir().join("avk.h")).unwrap();
+		assert!(
+			generate() == header,
+			"avk.h is out of date, regenerate it with `cargo run -p avk-header`"
+		);
+	}
+}
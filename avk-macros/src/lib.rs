@@ -0,0 +1,289 @@
+//! Procedural macros for Rust ROMs, re-exported by `avk_types`.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::Parser;
+use syn::spanned::Spanned;
+use syn::{Error, Expr, Item, ItemFn, Path, ReturnType};
+
+/// Makes a function the ROM's entry point.
+///
+/// The function takes the console's `Avk` handle, which it gets once the runner has started the
+/// console with the ROM's resources:
+/// ```ignore
+/// #[avk_types::main(images = images(), palettes = palettes(), report_panics)]
+/// fn main(mut avk: Avk) {
+///     while avk.update() {}
+/// }
+/// ```
+/// - `images` and `palettes` are expressions evaluating to the ROM's resources. Both default to
+///   empty ones.
+/// - `report_panics` installs a panic hook that sends panic messages to the runner. It needs `std`.
+/// - `crate` is the path of the `avk_types` crate, if it's been renamed.
+///
+/// - `frame` puts the attribute on a type implementing `FrameRom` instead, whose main loop the
+///   runner runs:
+///   ```ignore
+///   #[avk_types::main(frame, images = images())]
+///   struct Game { score: u32 }
+///
+///   impl FrameRom for Game {
+///       fn start(console: &mut Console) -> Self { Game { score: 0 } }
+///       fn frame(&mut self, console: &mut Console) { /* draws, but can't update */ }
+///   }
+///   ```
+///
+/// The ROM's entry points and `AVK_ABI_VERSION` symbol are exported from it, so the ROM still has
+/// to be built with `crate-type = ["cdylib"]`.
+#[proc_macro_attribute]
+pub fn main(
+	args: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	expand_main(args.into(), item.into())
+		.unwrap_or_else(Error::into_compile_error)
+		.into()
+}
+
+/// Expands `#[main]`, taking the attribute's arguments and the item it's on.
+fn expand_main(args: TokenStream, item: TokenStream) -> Result<TokenStream, Error> {
+	let mut krate: Path = syn::parse_quote!(::avk_types);
+	let mut images = None;
+	let mut palettes = None;
+	let mut report_panics = false;
+	let mut frame = false;
+
+	let parser = syn::meta::parser(|meta| {
+		if meta.path.is_ident("images") {
+			images = Some(meta.value()?.parse::<Expr>()?);
+		} else if meta.path.is_ident("palettes") {
+			palettes = Some(meta.value()?.parse::<Expr>()?);
+		} else if meta.path.is_ident("report_panics") {
+			report_panics = true;
+		} else if meta.path.is_ident("frame") {
+			frame = true;
+		} else if meta.path.is_ident("crate") {
+			krate = meta.value()?.parse::<syn::LitStr>()?.parse()?;
+		} else {
+			return Err(
+				meta.error("expected `images`, `palettes`, `report_panics`, `frame` or `crate`")
+			);
+		}
+		Ok(())
+	});
+	parser.parse2(args)?;
+
+	let images = images.unwrap_or_else(
+		|| syn::parse_quote!([#krate::prelude::Image::empty(); #krate::MAX_IMAGES]),
+	);
+	let palettes = palettes.unwrap_or_else(
+		|| syn::parse_quote!([#krate::prelude::Palette::empty(); #krate::MAX_PALETTES]),
+	);
+	let hook = report_panics.then(|| {
+		quote! {
+			::std::panic::set_hook(::std::boxed::Box::new(|info| #krate::__report_panic(info)));
+		}
+	});
+
+	if frame {
+		let rom = syn::parse2::<Item>(item)?;
+		let name = match &rom {
+			Item::Struct(rom) => &rom.ident,
+			Item::Enum(rom) => &rom.ident,
+			Item::Union(rom) => &rom.ident,
+			_ => {
+				return Err(Error::new(
+					rom.span(),
+					"`frame` goes on the ROM's type, which implements `FrameRom`",
+				))
+			}
+		};
+		return Ok(quote! {
+			#rom
+
+			#krate::avk_frame_rom!(#images, #palettes, #name, || {
+				#hook
+			});
+		});
+	}
+
+	let main = syn::parse2::<ItemFn>(item)?;
+	check_signature(&main)?;
+	let name = &main.sig.ident;
+
+	Ok(quote! {
+		#main
+
+		#krate::avk_main!(#images, #palettes, |avk| {
+			#hook
+			#name(avk)
+		});
+	})
+}
+
+/// The entry point gets called through a plain function pointer, so it has to be one.
+fn check_signature(main: &ItemFn) -> Result<(), Error> {
+	let sig = &main.sig;
+	if let Some(asyncness) = sig.asyncness {
+		return Err(Error::new(
+			asyncness.span(),
+			"the entry point can't be async",
+		));
+	}
+	if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+		return Err(Error::new(
+			sig.generics.span(),
+			"the entry point can't be generic",
+		));
+	}
+	if sig.inputs.len() != 1 {
+		return Err(Error::new(
+			sig.inputs.span(),
+			"the entry point takes exactly one argument, the `Avk` handle",
+		));
+	}
+	if let ReturnType::Type(_, ty) = &sig.output {
+		return Err(Error::new(
+			ty.span(),
+			"the entry point can't return anything",
+		));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn error(args: TokenStream, item: TokenStream) -> String {
+		expand_main(args, item).unwrap_err().to_string()
+	}
+
+	#[test]
+	fn expands_entry_points() {
+		let expanded = expand_main(
+			quote!(),
+			quote!(
+				fn main(avk: Avk) {}
+			),
+		)
+		.unwrap();
+		let expected = quote! {
+			fn main(avk: Avk) {}
+
+			::avk_types::avk_main!(
+				[::avk_types::prelude::Image::empty(); ::avk_types::MAX_IMAGES],
+				[::avk_types::prelude::Palette::empty(); ::avk_types::MAX_PALETTES],
+				|avk| { main(avk) }
+			);
+		};
+		assert_eq!(expanded.to_string(), expected.to_string());
+	}
+
+	#[test]
+	fn expands_frame_roms() {
+		let expanded = expand_main(
+			quote!(
+				frame,
+				images = IMAGES,
+				palettes = PALETTES,
+				crate = "avk",
+				report_panics
+			),
+			quote!(
+				struct Game;
+			),
+		)
+		.unwrap();
+		let expected = quote! {
+			struct Game;
+
+			avk::avk_frame_rom!(IMAGES, PALETTES, Game, || {
+				::std::panic::set_hook(::std::boxed::Box::new(|info| avk::__report_panic(info)));
+			});
+		};
+		assert_eq!(expanded.to_string(), expected.to_string());
+	}
+
+	#[test]
+	fn rejects_unknown_options() {
+		assert_eq!(
+			error(
+				quote!(image = IMAGES),
+				quote!(
+					fn main(avk: Avk) {}
+				)
+			),
+			"expected `images`, `palettes`, `report_panics`, `frame` or `crate`"
+		);
+		assert!(expand_main(
+			quote!(images),
+			quote!(
+				fn main(avk: Avk) {}
+			)
+		)
+		.is_err());
+	}
+
+	#[test]
+	fn rejects_frame_on_functions() {
+		assert_eq!(
+			error(
+				quote!(frame),
+				quote!(
+					fn main(avk: Avk) {}
+				)
+			),
+			"`frame` goes on the ROM's type, which implements `FrameRom`"
+		);
+		// and entry points on types
+		assert!(expand_main(
+			quote!(),
+			quote!(
+				struct Game;
+			)
+		)
+		.is_err());
+	}
+
+	#[test]
+	fn rejects_wrong_entry_signatures() {
+		let wrong = [
+			(
+				quote!(
+					async fn main(avk: Avk) {}
+				),
+				"the entry point can't be async",
+			),
+			(
+				quote!(
+					fn main<T>(avk: Avk) {}
+				),
+				"the entry point can't be generic",
+			),
+			(
+				quote!(
+					fn main() {}
+				),
+				"the entry point takes exactly one argument, the `Avk` handle",
+			),
+			(
+				quote!(
+					fn main(avk: Avk, extra: u32) {}
+				),
+				"the entry point takes exactly one argument, the `Avk` handle",
+			),
+			(
+				quote!(
+					fn main(avk: Avk) -> u32 {
+						0
+					}
+				),
+				"the entry point can't return anything",
+			),
+		];
+		for (item, message) in wrong {
+			assert_eq!(error(quote!(), item), message);
+		}
+	}
+}
@@ -46,8 +46,8 @@ impl GameState {
 	fn update(&mut self, avk: &mut Avk) {}
 }
 
-fn draw_ball(avk: &mut Avk, cx: i16, cy: i16) {
-	let fg = avk.get_foreground();
+fn draw_ball(frame: &mut Frame, cx: i16, cy: i16) {
+	let fg = frame.foreground();
 
 	let tl = &mut fg[6];
 	tl.x = cx - IMAGE_SIZE;
@@ -66,31 +66,51 @@ fn draw_ball(avk: &mut Avk, cx: i16, cy: i16) {
 	br.y = cy - 16;
 }
 
+const ICON_TOP: Image = Image::from_resource(include_bytes!("icon0.avkres"));
+const ICON_BOTTOM: Image = Image::from_resource(include_bytes!("icon1.avkres"));
+
 #[no_mangle]
-pub extern "C" fn avk_main() {
-	let mut avk = {
-		let mut palette: [Palette; 16] = [Palette::empty(); 16];
-		palette[0] = Palette::empty();
-		// generated with avk-convert
-		palette[1] = Palette([
-			0, 15, 65535, 64767, 27903, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
-		]);
-		palette[2] = Palette([
-			0, 65535, 8751, 58927, 17487, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-		]);
-
-		let mut images: [Image; 256] = [Image::empty(); 256];
-		images[0] = Image::empty();
-		images[1] = Image::from_resource(include_bytes!("paddle0.avkres"));
-		images[2] = Image::from_resource(include_bytes!("paddle1.avkres"));
-		images[3] = Image::from_resource(include_bytes!("icon0.avkres"));
-		images[4] = Image::from_resource(include_bytes!("icon1.avkres"));
-
-		Avk::init(images, palette)
-	};
+pub static AVK_METADATA: Metadata =
+	Metadata::new("Pong", "addiment", "1.0.0", "computer.living.pong").with_icon_32(
+		[
+			ICON_TOP,
+			ICON_TOP.flipped_x(),
+			ICON_BOTTOM,
+			ICON_BOTTOM.flipped_x(),
+		],
+		Palette([
+			0, 15, 65535, 64767, 27903, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+		]),
+	);
+
+fn palettes() -> [Palette; 16] {
+	let mut palette: [Palette; 16] = [Palette::empty(); 16];
+	palette[0] = Palette::empty();
+	// generated with avk-convert
+	palette[1] = Palette([
+		0, 15, 65535, 64767, 27903, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+	]);
+	palette[2] = Palette([
+		0, 65535, 8751, 58927, 17487, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+	]);
+	palette
+}
+
+fn images() -> [Image; 256] {
+	let mut images: [Image; 256] = [Image::empty(); 256];
+	images[0] = Image::empty();
+	images[1] = Image::from_resource(include_bytes!("paddle0.avkres"));
+	images[2] = Image::from_resource(include_bytes!("paddle1.avkres"));
+	images[3] = ICON_TOP;
+	images[4] = ICON_BOTTOM;
+	images
+}
 
+#[avk_types::main(images = images(), palettes = palettes(), report_panics)]
+fn run(mut avk: Avk) {
 	{
-		let fg = avk.get_foreground();
+		let mut frame = avk.frame();
+		let fg = frame.foreground();
 
 		let x = 8;
 		fg[0] = Sprite {
@@ -194,7 +214,8 @@ pub extern "C" fn avk_main() {
 		state.left_y = state.left_y.clamp(min_y as f32, max_y as f32);
 		state.right_y = state.right_y.clamp(min_y as f32, max_y as f32);
 
-		let fg = avk.get_foreground();
+		let mut frame = avk.frame();
+		let fg = frame.foreground();
 
 		let ly = roundf(state.left_y) as i16;
 		let ry = roundf(state.right_y) as i16;
@@ -209,6 +230,6 @@ pub extern "C" fn avk_main() {
 
 		let cx = (cosf(s_time * 2.0 * PI) * 32.0) as i16 + RESOLUTION_WIDTH / 2;
 		let cy = (sinf(s_time * 2.0 * PI) * 32.0) as i16 + RESOLUTION_HEIGHT / 2;
-		draw_ball(&mut avk, cx, cy);
+		draw_ball(&mut frame, cx, cy);
 	}
 }
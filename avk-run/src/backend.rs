@@ -1,11 +1,43 @@
 use avk_types::prelude::*;
 use avk_types::{AvkRaw, MAX_IMAGES, MAX_PALETTES};
+use log::warn;
 use std::array;
 use std::collections::HashMap;
+use std::mem;
 use std::ptr::null_mut;
 
-use crate::render::AvkRenderManager;
-use crate::sdl::SdlManager;
+use crate::headless::Headless;
+use crate::metadata;
+use crate::movie::{self, Movie};
+use crate::reload;
+use crate::render::{self, AvkRenderManager};
+use crate::save::SaveData;
+use crate::script::Script;
+use crate::sdl::audio::Synth;
+use crate::sdl::pointer;
+use crate::sdl::window::Window;
+use crate::sdl::{RunnerControls, SdlManager};
+
+/// Frames per second assumed by the frame clock.
+pub const FRAME_RATE: u64 = 60;
+
+/// Deadzones of the analog controls, until the ROM sets its own.
+const STICK_DEADZONE: f32 = 0.25;
+const TRIGGER_DEADZONE: f32 = 0.05;
+/// How far the left stick has to be pushed past its deadzone to press a direction input, so
+/// small deadzones don't turn every wobble of the stick into a press.
+const STICK_PRESS_THRESHOLD: f32 = 0.5;
+
+/// Where the console's picture and sound go.
+// the backend itself is boxed, so there's no point boxing the window too
+#[allow(clippy::large_enum_variant)]
+enum Output {
+	Window {
+		sdl_manager: SdlManager,
+		render_manager: AvkRenderManager,
+	},
+	Headless(Headless),
+}
 
 pub struct AvkBackend {
 	pub raw: *mut AvkRaw,
@@ -15,24 +47,64 @@ pub struct AvkBackend {
 	pub images: [Image; MAX_IMAGES],
 
 	pub input_state: [HashMap<AvkGamepadInput, bool>; 4],
+	/// Frames each input has been held for, as of the current frame, or 0 if it's up.
+	held_frames: [[u32; AvkGamepadInput::COUNT]; 4],
+	/// Inputs that went up on the current frame.
+	released: [[bool; AvkGamepadInput::COUNT]; 4],
+	/// Set by advance_frame, so the next update knows the runner already moved on the inputs.
+	frame_advanced: bool,
+	/// Frames ended so far, which input scripts count in.
+	frames: u64,
+	/// Positions of the analog controls, with the deadzones taken out.
+	axes: [[f32; AvkGamepadAxis::COUNT]; 4],
+	deadzones: [[f32; AvkGamepadAxis::COUNT]; 4],
+	/// Players with a gamepad plugged in.
+	connected: [bool; 4],
+	pointers: [AvkPointer; 4],
+	/// Typed during the current frame, while the ROM has text input on.
+	text: String,
+
+	movie: Option<Movie>,
+	/// The time the ROM sees while a movie is recorded or played, which only moves between
+	/// frames so every frame sees a single one.
+	movie_time: Option<u64>,
+	/// Set once the movie being played is over.
+	movie_finished: bool,
+	script: Option<Script>,
 
-	sdl_manager: SdlManager,
-	render_manager: AvkRenderManager,
+	save: SaveData,
+	/// Frames run so far, when the runner owns the main loop.
+	/// The ROM's clock is derived from it, so pausing and fast-forwarding affect it too.
+	frame_clock: Option<u64>,
+	output: Output,
 }
 
 impl AvkBackend {
 	pub fn init(images: &[Image; MAX_IMAGES], palettes: &[Palette; MAX_PALETTES]) -> Self {
 		let mut images = images.clone();
 		let mut palettes = palettes.clone();
-		let sdl_manager = SdlManager::new(
-			"AVK Virtual Console",
-			"1.0.0",
-			"computer.living.avk"
-		);
-		let render_manager =
-			AvkRenderManager::init(&mut images, &mut palettes, SdlManager::gl_loader);
+		let output = match Headless::new() {
+			Some(headless) => Output::Headless(headless),
+			None => {
+				let metadata = metadata::get();
+				let mut sdl_manager = SdlManager::new(
+					metadata.title(),
+					metadata.author(),
+					metadata.version(),
+					metadata.id(),
+				);
+				sdl_manager.window.set_title(metadata.title());
+				sdl_manager.window.set_icon(&metadata.icon);
+				let render_manager =
+					AvkRenderManager::init(&mut images, &mut palettes, SdlManager::gl_loader);
+				Output::Window {
+					sdl_manager,
+					render_manager,
+				}
+			}
+		};
 
-		Self {
+		let mut backend = Self {
 			palettes,
 			images,
 
@@ -52,15 +124,12 @@ impl AvkBackend {
 					AvkGamepadInput::DirDown,
 					AvkGamepadInput::DirLeft,
 					AvkGamepadInput::DirRight,
-
 					AvkGamepadInput::FaceUp,
 					AvkGamepadInput::FaceDown,
 					AvkGamepadInput::FaceLeft,
 					AvkGamepadInput::FaceRight,
-
 					AvkGamepadInput::TriggerLeft,
 					AvkGamepadInput::TriggerRight,
-
 					AvkGamepadInput::Menu,
 				] {
 					hm.insert(e, false);
@@ -68,16 +137,206 @@ impl AvkBackend {
 
 				hm
 			}),
+			held_frames: [[0; AvkGamepadInput::COUNT]; 4],
+			released: [[false; AvkGamepadInput::COUNT]; 4],
+			frame_advanced: false,
+			frames: 0,
+			axes: [[0.0; AvkGamepadAxis::COUNT]; 4],
+			deadzones: default_deadzones(),
+			connected: [false; 4],
+			pointers: [AvkPointer::default(); 4],
+			text: String::new(),
+			movie: None,
+			movie_time: None,
+			movie_finished: false,
+			script: Script::new(),
+			save: SaveData::load(),
+			frame_clock: None,
+			output,
+		};
+		if let Some(movie) = Movie::new(&mut backend.save) {
+			backend.movie = Some(movie);
+			backend.movie_time = Some(backend.output_time());
+			// the first frame starts right away, before the ROM's first update
+			if backend.is_playing_movie() {
+				backend.play_movie_frame();
+			}
+		}
+		backend
+	}
+
+	/// Replaces the images and palettes with the ones provided by a newly loaded ROM.
+	pub fn reload_resources(
+		&mut self,
+		images: &[Image; MAX_IMAGES],
+		palettes: &[Palette; MAX_PALETTES],
+	) {
+		self.images = *images;
+		self.palettes = *palettes;
+		self.frame_clock = None;
+		self.deadzones = default_deadzones();
+		if let Output::Window {
 			sdl_manager,
 			render_manager,
+		} = &mut self.output
+		{
+			render_manager.reload_images(&mut self.images);
+			// the new ROM may not be asking for text, nor be driven by the runner
+			sdl_manager.set_text_input(false);
+			sdl_manager.set_runner_controls(false);
 		}
+		// notes left playing by the previous ROM would never be stopped
+		self.with_synth(|synth| *synth = Synth::new());
 	}
 
 	fn update_input_state(&mut self) {
+		// ROMs calling update themselves see a frame per update, otherwise the runner ends them
+		// in advance_frame
+		if self.frame_clock.is_none() && !self.frame_advanced {
+			self.end_frame();
+		}
+		self.frame_advanced = false;
+		// the movie's frames are played as each frame starts
+		if self.is_playing_movie() {
+			return;
+		}
+
+		if let Output::Window { sdl_manager, .. } = &mut self.output {
+			self.text = sdl_manager.text_input.take_text();
+			for player in Player::ALL {
+				for axis in AvkGamepadAxis::ALL {
+					let (p, a) = (player.index(), axis as usize);
+					let value = normalize_axis(sdl_manager.axis_state_gp[p][a]);
+					self.axes[p][a] = apply_deadzone(value, self.deadzones[p][a]);
+				}
+				self.connected[player.index()] = sdl_manager.is_connected(player);
+			}
+
+			let pointer = &sdl_manager.pointer;
+			let window = &sdl_manager.window;
+			let (width, height) = (window.get_width(), window.get_height());
+			let (x, y) = render::window_to_screen(pointer.x, pointer.y, width, height);
+			self.pointers = [AvkPointer::default(); 4];
+			self.pointers[pointer::player().index()] = AvkPointer {
+				x,
+				y,
+				trigger: pointer.trigger,
+				on_screen: pointer.on_screen,
+			};
+		}
+		if let Some(script) = &mut self.script {
+			script.update(self.frames);
+		}
+		Self::poll_input_state(
+			&mut self.input_state,
+			&self.output,
+			self.script.as_ref(),
+			&self.axes,
+		);
+		if self.movie_time.is_some() {
+			self.movie_time = Some(self.output_time());
+		}
+		self.update_held_frames();
+	}
+
+	/// Ends the frame the ROM just ran: records it into the movie being recorded, moves the
+	/// input snapshots on, and plays the next frame of the movie being played.
+	fn end_frame(&mut self) {
+		let frame = movie::Frame {
+			time: self.get_time(),
+			inputs: Player::ALL
+				.map(|player| AvkGamepadInput::ALL.map(|input| self.get_input(player, input))),
+			connected: self.connected,
+			axes: self.axes,
+			pointers: self.pointers,
+			text: mem::take(&mut self.text),
+		};
+		if let Some(movie) = &mut self.movie {
+			if !movie.record(&frame) {
+				self.movie = None;
+				self.movie_time = None;
+			}
+		}
+		self.age_inputs();
+		self.frames += 1;
+		if self.is_playing_movie() {
+			self.play_movie_frame();
+		}
+	}
+
+	fn is_playing_movie(&self) -> bool {
+		self.movie.as_ref().is_some_and(Movie::is_playing)
+	}
+
+	/// Shows the ROM the next frame of the movie being played, or hands the inputs back to the
+	/// player once it's over.
+	fn play_movie_frame(&mut self) {
+		let Some(frame) = self.movie.as_mut().and_then(Movie::next_frame) else {
+			self.movie = None;
+			self.movie_time = None;
+			self.movie_finished = true;
+			return;
+		};
+		for player in Player::ALL {
+			for input in AvkGamepadInput::ALL {
+				let held = frame.inputs[player.index()][input as usize];
+				self.input_state[player.index()].insert(input, held);
+			}
+		}
+		self.connected = frame.connected;
+		self.axes = frame.axes;
+		self.pointers = frame.pointers;
+		self.text = frame.text;
+		self.movie_time = Some(frame.time);
+		self.update_held_frames();
+	}
+
+	/// Brings the input snapshots up to date with the inputs.
+	fn update_held_frames(&mut self) {
+		// taken from the merged state, so keyboards and gamepads behave the same
+		for player in Player::ALL {
+			for input in AvkGamepadInput::ALL {
+				let held = self.get_input(player, input);
+				let frames = &mut self.held_frames[player.index()][input as usize];
+				if !held {
+					self.released[player.index()][input as usize] = *frames > 0;
+					*frames = 0;
+				} else if *frames == 0 {
+					*frames = 1;
+				}
+			}
+		}
+	}
+
+	/// Moves the input snapshots on to the next frame, in which the held inputs have been held
+	/// for a frame longer and nothing has been released yet.
+	fn age_inputs(&mut self) {
+		for frames in self.held_frames.iter_mut().flatten() {
+			if *frames > 0 {
+				*frames = frames.saturating_add(1);
+			}
+		}
+		self.released = [[false; AvkGamepadInput::COUNT]; 4];
+	}
+
+	/// Merges the inputs held on the keyboard, the gamepads and by the input script.
+	fn poll_input_state(
+		input_state: &mut [HashMap<AvkGamepadInput, bool>; 4],
+		output: &Output,
+		script: Option<&Script>,
+		axes: &[[f32; AvkGamepadAxis::COUNT]; 4],
+	) {
+		let mut sources = Vec::with_capacity(3);
+		if let Output::Window { sdl_manager, .. } = output {
+			sources.push(&sdl_manager.action_state_kb);
+			sources.push(&sdl_manager.action_state_gp);
+		}
+		if let Some(script) = script {
+			sources.push(&script.state);
+		}
+
 		for player in [Player::Alpha, Player::Bravo, Player::Charlie, Player::Delta] {
 			let idx = player.index();
-			let kb = &self.sdl_manager.action_state_kb[idx];
-			let gp = &self.sdl_manager.action_state_gp[idx];
 
 			for input in [
 				AvkGamepadInput::DirUp,
@@ -92,8 +351,11 @@ impl AvkBackend {
 				AvkGamepadInput::TriggerRight,
 				AvkGamepadInput::Menu,
 			] {
-				let state = *kb.get(&input).unwrap_or(&false) || *gp.get(&input).unwrap_or(&false);
-				self.input_state[idx].insert(input, state);
+				let state = sources
+					.iter()
+					.any(|source| *source[idx].get(&input).unwrap_or(&false))
+					|| stick_direction(&axes[idx], input);
+				input_state[idx].insert(input, state);
 			}
 		}
 	}
@@ -102,16 +364,30 @@ impl AvkBackend {
 		// silly!!! breaking mutability rules!!! I don't care!!!
 		// TODO: I do actually care about mutability rules...
 		let this = self as *mut Self;
-		self.render_manager.update(
-			this,
-			self.sdl_manager.window.get_width(),
-			self.sdl_manager.window.get_height(),
-		);
-		let should_not_quit = self.sdl_manager.update();
+		let should_not_quit = match &mut self.output {
+			Output::Window {
+				sdl_manager,
+				render_manager,
+			} => {
+				let (width, height) = sdl_manager.window.get_pixel_size();
+				render_manager.update(this, width, height);
+				if let Some(keyboard) = sdl_manager.text_input.keyboard_overlay() {
+					render_manager.draw_overlay(keyboard);
+				}
+				let running = sdl_manager.update();
+				// ROMs running their own main loop can't be paused, but Escape still quits them
+				let quit = !sdl_manager.has_runner_controls() && sdl_manager.take_controls().quit;
+				running && !quit
+			}
+			Output::Headless(headless) => headless.update(),
+		};
 
 		self.update_input_state();
+		// a movie played headless is a test run, which is over with the movie
+		let movie_over = self.movie_finished && matches!(self.output, Output::Headless(_));
 
-		should_not_quit
+		// in --watch mode, a rebuilt ROM is asked to quit so it can be swapped out
+		should_not_quit && !movie_over && !reload::poll(self.get_time())
 	}
 
 	pub fn get_input(&self, player: Player, input: AvkGamepadInput) -> bool {
@@ -126,6 +402,256 @@ impl AvkBackend {
 
 	/// Returns the current time, in milliseconds.
 	pub fn get_time(&self) -> u64 {
-		self.sdl_manager.get_ticks_ms()
+		match (self.frame_clock, self.movie_time) {
+			(Some(frames), _) => frames * 1000 / FRAME_RATE,
+			(None, Some(time)) => time,
+			(None, None) => self.output_time(),
+		}
+	}
+
+	/// Returns the time kept by the window or the headless console, in milliseconds.
+	fn output_time(&self) -> u64 {
+		match &self.output {
+			Output::Window { sdl_manager, .. } => sdl_manager.get_ticks_ms(),
+			Output::Headless(headless) => headless.get_time(),
+		}
+	}
+
+	/// Switches the ROM's clock over to counting the frames run by the runner.
+	pub fn use_frame_clock(&mut self) {
+		self.frame_clock = Some(0);
+	}
+
+	/// Returns how many frames an input has been held for, counting the current one, or 0 if
+	/// it's up.
+	pub fn held_frames(&self, player: Player, input: AvkGamepadInput) -> u32 {
+		self.held_frames[player.index()][input as usize]
+	}
+
+	/// Returns true if an input went up on the current frame.
+	pub fn just_released(&self, player: Player, input: AvkGamepadInput) -> bool {
+		self.released[player.index()][input as usize]
+	}
+
+	/// Returns true if a player has a gamepad plugged in.
+	pub fn is_connected(&self, player: Player) -> bool {
+		self.connected[player.index()]
+	}
+
+	pub fn rumble(&mut self, player: Player, low: u16, high: u16, duration_ms: u32) {
+		if let Output::Window { sdl_manager, .. } = &mut self.output {
+			sdl_manager.rumble(player, low, high, duration_ms);
+		}
+	}
+
+	pub fn get_pointer(&self, player: Player) -> AvkPointer {
+		self.pointers[player.index()]
+	}
+
+	pub fn set_text_input(&mut self, enabled: bool) {
+		if let Output::Window { sdl_manager, .. } = &mut self.output {
+			sdl_manager.set_text_input(enabled);
+		}
+	}
+
+	/// Copies the text typed during the current frame into `buf`, cut short at a character
+	/// boundary if it doesn't fit. Returns the number of bytes copied.
+	pub fn read_text(&self, buf: &mut [u8]) -> usize {
+		let mut len = self.text.len().min(buf.len());
+		while !self.text.is_char_boundary(len) {
+			len -= 1;
+		}
+		buf[..len].copy_from_slice(&self.text.as_bytes()[..len]);
+		len
+	}
+
+	/// Returns the position of an analog control, with its deadzone taken out.
+	pub fn get_axis(&self, player: Player, axis: AvkGamepadAxis) -> f32 {
+		self.axes[player.index()][axis as usize]
+	}
+
+	pub fn deadzone(&self, player: Player, axis: AvkGamepadAxis) -> f32 {
+		self.deadzones[player.index()][axis as usize]
+	}
+
+	/// Sets the deadzone of an analog control, which applies from the next update on.
+	pub fn set_deadzone(&mut self, player: Player, axis: AvkGamepadAxis, deadzone: f32) {
+		if deadzone.is_nan() {
+			warn!("Ignored a NaN deadzone!");
+			return;
+		}
+		self.deadzones[player.index()][axis as usize] = deadzone.clamp(0.0, 1.0);
+	}
+
+	/// Advances the frame clock and the input snapshots by one frame.
+	pub fn advance_frame(&mut self) {
+		if let Some(frames) = &mut self.frame_clock {
+			*frames += 1;
+		}
+		self.end_frame();
+		self.frame_advanced = true;
+	}
+
+	/// Returns the window, or None when running headless.
+	pub fn window(&self) -> Option<&Window> {
+		match &self.output {
+			Output::Window { sdl_manager, .. } => Some(&sdl_manager.window),
+			Output::Headless(_) => None,
+		}
+	}
+
+	/// Hands the pause, step and fast-forward hotkeys to a runner-owned main loop, which has to
+	/// act on [`take_controls`](Self::take_controls).
+	pub fn use_runner_controls(&mut self) {
+		if let Output::Window { sdl_manager, .. } = &mut self.output {
+			sdl_manager.set_runner_controls(true);
+		}
+	}
+
+	/// Returns the runner hotkeys pressed since the last call.
+	pub fn take_controls(&mut self) -> RunnerControls {
+		match &mut self.output {
+			Output::Window { sdl_manager, .. } => sdl_manager.take_controls(),
+			Output::Headless(_) => RunnerControls::default(),
+		}
+	}
+
+	/// Runs `f` on the synth, returning None if there's no sound output.
+	fn with_synth<R>(&mut self, f: impl FnOnce(&mut Synth) -> R) -> Option<R> {
+		match &mut self.output {
+			Output::Window { sdl_manager, .. } => sdl_manager
+				.audio
+				.as_ref()
+				.map(|audio| f(&mut audio.synth())),
+			Output::Headless(headless) => Some(f(headless.synth())),
+		}
+	}
+
+	pub fn save_read(&self, offset: usize, buf: &mut [u8]) -> usize {
+		self.save.read(offset, buf)
+	}
+
+	pub fn save_write(&mut self, offset: usize, data: &[u8]) -> usize {
+		self.save.write(offset, data)
+	}
+
+	pub fn audio_write(&mut self, channel: AudioChannel, register: AudioRegister, value: u16) {
+		self.with_synth(|synth| synth.write(channel, register, value));
+	}
+
+	pub fn play_music(&mut self, data: &[u8]) {
+		if self.with_synth(|synth| synth.play_music(data)) == Some(false) {
+			warn!("The ROM tried to play an invalid song!");
+		}
+	}
+
+	pub fn stop_music(&mut self) {
+		self.with_synth(Synth::stop_music);
+	}
+
+	pub fn set_music_volume(&mut self, volume: u8) {
+		self.with_synth(|synth| synth.set_music_volume(volume));
+	}
+
+	pub fn register_sample(&mut self, id: u8, data: &[u8]) {
+		if self.with_synth(|synth| synth.register_sample(id, data)) == Some(false) {
+			warn!("The ROM tried to register an invalid sample as sample {id}!");
+		}
+	}
+
+	pub fn play_sample(&mut self, id: u8, pitch: u16, volume: u8, pan: i8) {
+		if self.with_synth(|synth| synth.play_sample(id, pitch, volume, pan)) == Some(false) {
+			warn!("The ROM tried to play sample {id}, which isn't registered!");
+		}
+	}
+
+	pub fn stop_samples(&mut self) {
+		self.with_synth(Synth::stop_samples);
+	}
+
+	pub fn play_effect(&mut self, data: &[u8], priority: u8) {
+		if self.with_synth(|synth| synth.play_effect(data, priority)) == Some(false) {
+			warn!("The ROM tried to play an invalid song as a sound effect!");
+		}
+	}
+
+	pub fn stop_effects(&mut self) {
+		self.with_synth(Synth::stop_effects);
+	}
+
+	pub fn set_sfx_volume(&mut self, volume: u8) {
+		self.with_synth(|synth| synth.set_sfx_volume(volume));
+	}
+}
+
+fn default_deadzones() -> [[f32; AvkGamepadAxis::COUNT]; 4] {
+	[AvkGamepadAxis::ALL.map(|axis| match axis {
+		AvkGamepadAxis::LeftTrigger | AvkGamepadAxis::RightTrigger => TRIGGER_DEADZONE,
+		_ => STICK_DEADZONE,
+	}); 4]
+}
+
+/// Converts a raw position from SDL to the -1.0 to 1.0 range.
+fn normalize_axis(value: i16) -> f32 {
+	// i16::MIN would be just past -1.0
+	(value as f32 / i16::MAX as f32).max(-1.0)
+}
+
+/// Zeroes a control's position inside its deadzone, and rescales it outside so it still goes
+/// smoothly from 0.0 to 1.0.
+fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+	if value.abs() <= deadzone {
+		0.0
+	} else {
+		value.signum() * (value.abs() - deadzone) / (1.0 - deadzone)
+	}
+}
+
+/// Returns true if the left stick is pushed towards a direction input.
+fn stick_direction(axes: &[f32; AvkGamepadAxis::COUNT], input: AvkGamepadInput) -> bool {
+	let x = axes[AvkGamepadAxis::LeftX as usize];
+	let y = axes[AvkGamepadAxis::LeftY as usize];
+	match input {
+		AvkGamepadInput::DirUp => y <= -STICK_PRESS_THRESHOLD,
+		AvkGamepadInput::DirRight => x >= STICK_PRESS_THRESHOLD,
+		AvkGamepadInput::DirDown => y >= STICK_PRESS_THRESHOLD,
+		AvkGamepadInput::DirLeft => x <= -STICK_PRESS_THRESHOLD,
+		_ => false,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn deadzones_are_cut_out() {
+		assert_eq!(apply_deadzone(0.2, 0.25), 0.0);
+		assert_eq!(apply_deadzone(-0.25, 0.25), 0.0);
+		assert_eq!(apply_deadzone(0.625, 0.25), 0.5);
+		assert_eq!(apply_deadzone(-1.0, 0.25), -1.0);
+		assert_eq!(apply_deadzone(0.01, 0.0), 0.01);
+	}
+
+	#[test]
+	fn stick_presses_directions_past_the_threshold() {
+		let mut axes = [0.0; AvkGamepadAxis::COUNT];
+		// without a deadzone, any touch of the stick is past it
+		axes[AvkGamepadAxis::LeftX as usize] = apply_deadzone(0.01, 0.0);
+		axes[AvkGamepadAxis::LeftY as usize] = apply_deadzone(-0.4, 0.0);
+		for input in AvkGamepadInput::ALL {
+			assert!(!stick_direction(&axes, input), "{input:?}");
+		}
+
+		axes[AvkGamepadAxis::LeftX as usize] = -0.5;
+		axes[AvkGamepadAxis::LeftY as usize] = 0.9;
+		assert!(stick_direction(&axes, AvkGamepadInput::DirLeft));
+		assert!(stick_direction(&axes, AvkGamepadInput::DirDown));
+		assert!(!stick_direction(&axes, AvkGamepadInput::DirRight));
+		assert!(!stick_direction(&axes, AvkGamepadInput::DirUp));
+		// only the left stick presses directions
+		axes = [0.0; AvkGamepadAxis::COUNT];
+		axes[AvkGamepadAxis::RightX as usize] = 1.0;
+		assert!(!stick_direction(&axes, AvkGamepadInput::DirRight));
 	}
 }
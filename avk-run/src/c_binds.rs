@@ -1,13 +1,57 @@
 use crate::backend::AvkBackend;
-use avk_types::prelude::{Image, Palette};
-use avk_types::{AvkGamepadInput, AvkRaw, Player, BACKGROUND_CANVAS_SIZE, MAX_SPRITES};
+use crate::reload;
+use avk_types::prelude::{AudioChannel, AudioRegister, Image, Palette};
+use avk_types::{
+	AvkGamepadAxis, AvkGamepadInput, AvkPointer, AvkRaw, Player, BACKGROUND_CANVAS_SIZE,
+	MAX_IMAGES, MAX_PALETTES, MAX_SPRITES,
+};
+use log::error;
+use std::cell::Cell;
 use std::ffi::c_void;
-use std::mem;
+use std::ptr::null_mut;
+use std::{mem, slice};
+
+thread_local! {
+	/// The console created by the running ROM, for runner-driven main loops.
+	static CONSOLE: Cell<*mut AvkRaw> = const { Cell::new(null_mut()) };
+}
+
+/// Returns the backend behind a runner-side AvkRaw.
+pub unsafe fn backend<'a>(raw: *mut AvkRaw) -> &'a mut AvkBackend {
+	&mut *((*raw).internal as *mut AvkBackend)
+}
+
+/// Borrows the bytes a ROM passed to a call, or returns None if it passed none.
+/// ROMs may pass null along with a length of 0, which `slice::from_raw_parts` doesn't allow.
+pub unsafe fn rom_bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+	(!data.is_null() && len > 0).then(|| slice::from_raw_parts(data, len))
+}
+
+/// Mutable version of [`rom_bytes`], for buffers the runner fills in.
+pub unsafe fn rom_bytes_mut<'a>(buf: *mut u8, len: usize) -> Option<&'a mut [u8]> {
+	(!buf.is_null() && len > 0).then(|| slice::from_raw_parts_mut(buf, len))
+}
+
+/// Returns the console created by the running ROM, or null if it hasn't called `avk_init`.
+pub fn console() -> *mut AvkRaw {
+	CONSOLE.get()
+}
 
 #[no_mangle]
 pub extern "C" fn avk_init(images: *const Image, palettes: *const Palette) -> *mut AvkRaw {
 	// this function should probably undergo SERIOUS review...
 	unsafe {
+		// a reloaded ROM takes over the console left behind by the previous one
+		if let Some(raw) = reload::reuse() {
+			let avk = &mut *((*raw).internal as *mut AvkBackend);
+			avk.reload_resources(
+				&*(images as *const [Image; MAX_IMAGES]),
+				&*(palettes as *const [Palette; MAX_PALETTES]),
+			);
+			CONSOLE.set(raw);
+			return raw;
+		}
+
 		let mut avk: Box<AvkBackend> = Box::new(AvkBackend::init(
 			mem::transmute(images),
 			mem::transmute(palettes),
@@ -21,14 +65,24 @@ pub extern "C" fn avk_init(images: *const Image, palettes: *const Palette) -> *m
 		});
 		avk.raw = raw.as_mut() as *mut AvkRaw;
 		Box::leak::<'static>(avk);
-		Box::leak::<'static>(raw)
+		let raw = Box::leak::<'static>(raw);
+		reload::register(raw);
+		CONSOLE.set(raw);
+		raw
 	}
 }
 
 pub extern "C" fn avk_drop(avk: *mut AvkRaw) {
+	if reload::keep_alive(avk) {
+		return;
+	}
+	if CONSOLE.get() == avk {
+		CONSOLE.set(null_mut());
+	}
 	unsafe {
-		// TODO: make sure this actually frees the object...
-		drop(Box::from_raw(avk));
+		// free the backend too, which flushes the save data and closes the window
+		let raw = Box::from_raw(avk);
+		drop(Box::from_raw(raw.internal as *mut AvkBackend));
 	}
 }
 
@@ -56,3 +110,208 @@ pub extern "C" fn avk_get_input(
 		avk.get_input(player, input)
 	}
 }
+
+pub extern "C" fn avk_held_frames(
+	avk: *const AvkRaw,
+	player: Player,
+	input: AvkGamepadInput,
+) -> u32 {
+	unsafe {
+		let avk = &*((*avk).internal as *const AvkBackend);
+		avk.held_frames(player, input)
+	}
+}
+
+pub extern "C" fn avk_just_released(
+	avk: *const AvkRaw,
+	player: Player,
+	input: AvkGamepadInput,
+) -> bool {
+	unsafe {
+		let avk = &*((*avk).internal as *const AvkBackend);
+		avk.just_released(player, input)
+	}
+}
+
+pub extern "C" fn avk_is_connected(avk: *const AvkRaw, player: Player) -> bool {
+	unsafe {
+		let avk = &*((*avk).internal as *const AvkBackend);
+		avk.is_connected(player)
+	}
+}
+
+pub extern "C" fn avk_rumble(
+	avk: *mut AvkRaw,
+	player: Player,
+	low: u16,
+	high: u16,
+	duration_ms: u32,
+) {
+	unsafe {
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.rumble(player, low, high, duration_ms)
+	}
+}
+
+pub extern "C" fn avk_get_pointer(avk: *const AvkRaw, player: Player, pointer: *mut AvkPointer) {
+	unsafe {
+		let avk = &*((*avk).internal as *const AvkBackend);
+		*pointer = avk.get_pointer(player)
+	}
+}
+
+pub extern "C" fn avk_set_text_input(avk: *mut AvkRaw, enabled: bool) {
+	unsafe {
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.set_text_input(enabled)
+	}
+}
+
+pub extern "C" fn avk_read_text(avk: *const AvkRaw, buf: *mut u8, len: usize) -> usize {
+	unsafe {
+		let Some(buf) = rom_bytes_mut(buf, len) else {
+			return 0;
+		};
+		let avk = &*((*avk).internal as *const AvkBackend);
+		avk.read_text(buf)
+	}
+}
+
+pub extern "C" fn avk_get_axis(avk: *const AvkRaw, player: Player, axis: AvkGamepadAxis) -> f32 {
+	unsafe {
+		let avk = &*((*avk).internal as *const AvkBackend);
+		avk.get_axis(player, axis)
+	}
+}
+
+pub extern "C" fn avk_set_deadzone(
+	avk: *mut AvkRaw,
+	player: Player,
+	axis: AvkGamepadAxis,
+	deadzone: f32,
+) {
+	unsafe {
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.set_deadzone(player, axis, deadzone)
+	}
+}
+
+pub extern "C" fn avk_save_read(
+	avk: *const AvkRaw,
+	offset: usize,
+	buf: *mut u8,
+	len: usize,
+) -> usize {
+	unsafe {
+		let Some(buf) = rom_bytes_mut(buf, len) else {
+			return 0;
+		};
+		let avk = &*((*avk).internal as *const AvkBackend);
+		avk.save_read(offset, buf)
+	}
+}
+
+pub extern "C" fn avk_save_write(
+	avk: *mut AvkRaw,
+	offset: usize,
+	data: *const u8,
+	len: usize,
+) -> usize {
+	unsafe {
+		let Some(data) = rom_bytes(data, len) else {
+			return 0;
+		};
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.save_write(offset, data)
+	}
+}
+
+pub extern "C" fn avk_audio_write(
+	avk: *mut AvkRaw,
+	channel: AudioChannel,
+	register: AudioRegister,
+	value: u16,
+) {
+	unsafe {
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.audio_write(channel, register, value)
+	}
+}
+
+pub extern "C" fn avk_play_music(avk: *mut AvkRaw, data: *const u8, len: usize) {
+	unsafe {
+		let Some(data) = rom_bytes(data, len) else {
+			return;
+		};
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.play_music(data)
+	}
+}
+
+pub extern "C" fn avk_stop_music(avk: *mut AvkRaw) {
+	unsafe {
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.stop_music()
+	}
+}
+
+pub extern "C" fn avk_set_music_volume(avk: *mut AvkRaw, volume: u8) {
+	unsafe {
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.set_music_volume(volume)
+	}
+}
+
+pub extern "C" fn avk_register_sample(avk: *mut AvkRaw, id: u8, data: *const u8, len: usize) {
+	unsafe {
+		let Some(data) = rom_bytes(data, len) else {
+			return;
+		};
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.register_sample(id, data)
+	}
+}
+
+pub extern "C" fn avk_play_sample(avk: *mut AvkRaw, id: u8, pitch: u16, volume: u8, pan: i8) {
+	unsafe {
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.play_sample(id, pitch, volume, pan)
+	}
+}
+
+pub extern "C" fn avk_stop_samples(avk: *mut AvkRaw) {
+	unsafe {
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.stop_samples()
+	}
+}
+
+pub extern "C" fn avk_play_effect(avk: *mut AvkRaw, data: *const u8, len: usize, priority: u8) {
+	unsafe {
+		let Some(data) = rom_bytes(data, len) else {
+			return;
+		};
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.play_effect(data, priority)
+	}
+}
+
+pub extern "C" fn avk_stop_effects(avk: *mut AvkRaw) {
+	unsafe {
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.stop_effects()
+	}
+}
+
+pub extern "C" fn avk_set_sfx_volume(avk: *mut AvkRaw, volume: u8) {
+	unsafe {
+		let avk = &mut *((*avk).internal as *mut AvkBackend);
+		avk.set_sfx_volume(volume)
+	}
+}
+
+#[no_mangle]
+pub extern "C" fn avk_report_panic(message: *const u8, len: usize) {
+	let message = unsafe { rom_bytes(message, len) }.unwrap_or_default();
+	error!("ROM panicked: {}", String::from_utf8_lossy(message));
+}
@@ -0,0 +1,69 @@
+//! The runner-owned main loop, for ROMs exporting `avk_start`, `avk_frame` and `avk_stop`
+//! instead of `avk_main`. The runner calls `avk_frame` once per frame, which lets it pause,
+//! step and fast-forward the ROM, and shut it down cleanly.
+
+use crate::c_binds::{avk_update, backend, console};
+use log::{error, info};
+
+/// Frames run per update while fast-forwarding.
+pub const FAST_FORWARD_FRAMES: u32 = 4;
+
+/// A ROM using the per-frame entry points.
+pub trait FrameRom {
+	/// Calls `avk_start`. Returns false if the ROM failed.
+	fn start(&mut self) -> bool;
+	/// Calls `avk_frame`. Returns false if the ROM failed.
+	fn frame(&mut self) -> bool;
+	/// Calls `avk_stop`.
+	fn stop(&mut self);
+}
+
+/// Runs the ROM until the window is closed, Escape is pressed or the ROM fails.
+pub fn run(rom: &mut impl FrameRom) {
+	if !rom.start() {
+		return;
+	}
+
+	let raw = console();
+	if raw.is_null() {
+		error!("ROM did not initialize AVK in avk_start!");
+		rom.stop();
+		return;
+	}
+	unsafe {
+		backend(raw).use_frame_clock();
+		backend(raw).use_runner_controls();
+	}
+
+	let mut paused = false;
+	'running: loop {
+		let controls = unsafe { backend(raw).take_controls() };
+		if controls.quit {
+			break;
+		}
+		if controls.pause {
+			paused = !paused;
+			info!("{}", if paused { "Paused" } else { "Resumed" });
+		}
+
+		let frames = if paused {
+			controls.step as u32
+		} else if controls.fast_forward {
+			FAST_FORWARD_FRAMES
+		} else {
+			1
+		};
+		for _ in 0..frames {
+			if !rom.frame() {
+				break 'running;
+			}
+			unsafe { backend(raw).advance_frame() };
+		}
+
+		if !avk_update(raw) {
+			break;
+		}
+	}
+
+	rom.stop();
+}
@@ -0,0 +1,115 @@
+//! Runs without a window or audio device, for `--headless` mode.
+//! The console is driven by its frame clock alone, so a run is deterministic: every frame
+//! renders exactly `SAMPLES_PER_FRAME` of audio, which can be recorded to a WAV file and
+//! compared against a known good recording.
+
+use crate::backend::FRAME_RATE;
+use crate::sdl::audio::{Synth, SAMPLE_RATE};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use log::{error, info};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// Stereo samples rendered per frame.
+pub const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE as u64 / FRAME_RATE) as usize;
+
+#[derive(Clone, Default)]
+pub struct Config {
+	/// Stops the ROM after this many frames.
+	pub frames: Option<u64>,
+	/// Records the audio to this WAV file.
+	pub record_audio: Option<PathBuf>,
+}
+
+thread_local! {
+	static CONFIG: RefCell<Option<Config>> = const { RefCell::new(None) };
+}
+
+/// Makes consoles created from now on headless.
+pub fn enable(config: Config) {
+	CONFIG.set(Some(config));
+}
+
+pub fn is_enabled() -> bool {
+	CONFIG.with_borrow(Option::is_some)
+}
+
+/// Stands in for the window and audio device of a headless console.
+pub struct Headless {
+	frames: u64,
+	frame_limit: Option<u64>,
+	synth: Synth,
+	recording: Option<WavWriter<BufWriter<File>>>,
+	buffer: Vec<f32>,
+}
+
+impl Headless {
+	/// Returns None if headless mode isn't enabled.
+	pub fn new() -> Option<Self> {
+		let config = CONFIG.with_borrow(Clone::clone)?;
+		Some(Self {
+			frames: 0,
+			frame_limit: config.frames,
+			synth: Synth::new(),
+			recording: config.record_audio.as_deref().map(create_recording),
+			buffer: vec![0.0; SAMPLES_PER_FRAME * 2],
+		})
+	}
+
+	pub fn synth(&mut self) -> &mut Synth {
+		&mut self.synth
+	}
+
+	/// Returns the time since the console was created, in milliseconds.
+	pub fn get_time(&self) -> u64 {
+		self.frames * 1000 / FRAME_RATE
+	}
+
+	/// Renders a frame's worth of audio.
+	/// Returns false once the frame limit is reached, or if the recording failed.
+	pub fn update(&mut self) -> bool {
+		self.synth.render(&mut self.buffer);
+		if let Some(recording) = &mut self.recording {
+			let written = self
+				.buffer
+				.iter()
+				.try_for_each(|&sample| {
+					recording.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+				})
+				// keeps the header up to date in case the ROM never frees its console
+				.and_then(|()| recording.flush());
+			if let Err(err) = written {
+				error!("Failed to record audio: {err}");
+				self.recording = None;
+				return false;
+			}
+		}
+
+		self.frames += 1;
+		self.frame_limit.is_none_or(|limit| self.frames < limit)
+	}
+}
+
+impl Drop for Headless {
+	fn drop(&mut self) {
+		if let Some(recording) = self.recording.take() {
+			match recording.finalize() {
+				Ok(()) => info!("Recorded {} frames of audio", self.frames),
+				Err(err) => error!("Failed to record audio: {err}"),
+			}
+		}
+	}
+}
+
+fn create_recording(path: &Path) -> WavWriter<BufWriter<File>> {
+	let spec = WavSpec {
+		channels: 2,
+		sample_rate: SAMPLE_RATE,
+		bits_per_sample: 16,
+		sample_format: SampleFormat::Int,
+	};
+	WavWriter::create(path, spec)
+		.unwrap_or_else(|err| panic!("Failed to create {}: {err}", path.display()))
+}
@@ -0,0 +1,1089 @@
+//! Process isolation for native ROMs (`--isolate`).
+//! The ROM runs in a child avk-run process, so crashing it can't take the window down with it.
+//! Its AvkRaw, input and save block live in shared memory, and every `update` is a
+//! synchronization point: the ROM process waits there while the runner presents the frame.
+
+use crate::c_binds::{avk_drop, avk_init, avk_update, backend, rom_bytes, rom_bytes_mut};
+use crate::frame::{FrameRom, FAST_FORWARD_FRAMES};
+use crate::headless;
+use crate::metadata;
+use crate::native::{self, AvkBinds, NativeFrameRom};
+use crate::sdl;
+use avk_types::prelude::*;
+use avk_types::{
+	AvkRaw, MAX_IMAGES, MAX_PALETTES, MAX_SAMPLE_SIZE, MAX_SONG_SIZE, MAX_TEXT_INPUT, SAVE_SIZE,
+};
+use libc::{c_int, sem_t};
+use log::{error, info, warn};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::env::current_exe;
+use std::ffi::c_void;
+use std::io::{BufRead, BufReader};
+use std::mem::{self, size_of};
+use std::path::Path;
+use std::process::{self, Child, Command, ExitStatus, Stdio};
+use std::ptr::null_mut;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{io, thread};
+
+/// Lines of the ROM's stderr kept around to explain a crash.
+const STDERR_TAIL_LINES: usize = 8;
+
+/// How long to wait on the ROM process before checking whether it's still alive.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a ROM gets to quit after being told to, before it is killed.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Audio commands the ROM process can queue up between two updates.
+const MAX_AUDIO_COMMANDS: usize = 1024;
+
+// What the ROM process last asked the music to do.
+const MUSIC_PLAY: u32 = 1;
+const MUSIC_STOP: u32 = 2;
+
+// What the ROM process is waiting on at a synchronization point.
+const REQUEST_INIT: u32 = 1;
+const REQUEST_UPDATE: u32 = 2;
+/// Registering a sample can't wait for the next update, samples are too big to queue up.
+const REQUEST_REGISTER_SAMPLE: u32 = 3;
+
+// What a queued up audio command does.
+const AUDIO_WRITE: u8 = 1;
+const AUDIO_PLAY_SAMPLE: u8 = 2;
+const AUDIO_STOP_SAMPLES: u8 = 3;
+const AUDIO_PLAY_EFFECT: u8 = 4;
+const AUDIO_STOP_EFFECTS: u8 = 5;
+const AUDIO_SET_SFX_VOLUME: u8 = 6;
+
+/// The console state shared between the runner and the ROM process.
+/// The ROM process can write anything into it, so it holds plain numbers rather than bools and
+/// enums, which the runner checks as it reads them.
+#[repr(C)]
+struct SharedConsole {
+	/// Posted by the ROM process when it reaches a synchronization point.
+	rom_ready: sem_t,
+	/// Posted by the runner when the ROM process may continue.
+	runner_ready: sem_t,
+	request: u32,
+	/// Set by the ROM process if it exports the per-frame entry points.
+	frame_driven: u8,
+	/// Cleared by the runner to make `update` return false.
+	running: u8,
+	time: u64,
+	input: [[u8; AvkGamepadInput::COUNT]; 4],
+	held_frames: [[u32; AvkGamepadInput::COUNT]; 4],
+	just_released: [[u8; AvkGamepadInput::COUNT]; 4],
+	axes: [[f32; AvkGamepadAxis::COUNT]; 4],
+	connected: [u8; 4],
+	pointers: [AvkPointer; 4],
+	/// Handed to the ROM process every frame, and taken back with its changes afterwards.
+	deadzones: [[f32; AvkGamepadAxis::COUNT]; 4],
+	/// Set by the ROM process when it rumbles a player's gamepad.
+	rumble_dirty: [u8; 4],
+	/// The last rumble asked for on each player's gamepad, earlier ones would be cut off anyway.
+	rumble: [Rumble; 4],
+	text_len: usize,
+	text: [u8; MAX_TEXT_INPUT],
+	/// Set by the ROM process when it turns text input on or off.
+	text_input_dirty: u8,
+	text_input: u8,
+	/// Set by the ROM process when it writes to the save block.
+	save_dirty: u8,
+	save: [u8; SAVE_SIZE],
+	images: [Image; MAX_IMAGES],
+	palettes: [Palette; MAX_PALETTES],
+	raw: AvkRaw,
+	/// Set by the ROM process if the ROM exports metadata.
+	has_metadata: u8,
+	metadata: Metadata,
+	/// Audio calls made by the ROM since the last synchronization point.
+	audio_command_count: usize,
+	audio_commands: [RawAudioCommand; MAX_AUDIO_COMMANDS],
+	/// Set by the ROM process when it starts or stops the music, 0 if it didn't.
+	music_request: u32,
+	music_len: usize,
+	music: [u8; MAX_SONG_SIZE],
+	/// Set by the ROM process when it changes the music volume.
+	music_volume_dirty: u8,
+	music_volume: u8,
+	/// The sample being registered, with REQUEST_REGISTER_SAMPLE.
+	sample_id: u8,
+	sample_len: usize,
+	sample: [u8; MAX_SAMPLE_SIZE],
+	/// The songs of the effects played since the last synchronization point, back to back.
+	effects_len: usize,
+	effects: [u8; MAX_SONG_SIZE],
+}
+
+/// An audio call queued up by the ROM process.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum AudioCommand {
+	Write {
+		channel: AudioChannel,
+		register: AudioRegister,
+		value: u16,
+	},
+	PlaySample {
+		id: u8,
+		pitch: u16,
+		volume: u8,
+		pan: i8,
+	},
+	StopSamples,
+	/// Plays the song at `offset` in the effect buffer.
+	PlayEffect {
+		offset: usize,
+		len: usize,
+		priority: u8,
+	},
+	StopEffects,
+	SetSfxVolume {
+		volume: u8,
+	},
+}
+
+/// An [`AudioCommand`] as it's stored in shared memory: one of the `AUDIO_*` codes, and
+/// arguments whose meaning depends on it.
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct RawAudioCommand {
+	kind: u8,
+	args: [u16; 4],
+	offset: usize,
+	len: usize,
+}
+
+impl From<AudioCommand> for RawAudioCommand {
+	fn from(command: AudioCommand) -> Self {
+		let (kind, args, offset, len) = match command {
+			AudioCommand::Write {
+				channel,
+				register,
+				value,
+			} => (
+				AUDIO_WRITE,
+				[channel as u16, register as u16, value, 0],
+				0,
+				0,
+			),
+			AudioCommand::PlaySample {
+				id,
+				pitch,
+				volume,
+				pan,
+			} => (
+				AUDIO_PLAY_SAMPLE,
+				[id as u16, pitch, volume as u16, pan as u8 as u16],
+				0,
+				0,
+			),
+			AudioCommand::StopSamples => (AUDIO_STOP_SAMPLES, [0; 4], 0, 0),
+			AudioCommand::PlayEffect {
+				offset,
+				len,
+				priority,
+			} => (AUDIO_PLAY_EFFECT, [priority as u16, 0, 0, 0], offset, len),
+			AudioCommand::StopEffects => (AUDIO_STOP_EFFECTS, [0; 4], 0, 0),
+			AudioCommand::SetSfxVolume { volume } => {
+				(AUDIO_SET_SFX_VOLUME, [volume as u16, 0, 0, 0], 0, 0)
+			}
+		};
+		Self {
+			kind,
+			args,
+			offset,
+			len,
+		}
+	}
+}
+
+/// Decodes a command queued up by the ROM process, failing if it isn't one.
+impl TryFrom<RawAudioCommand> for AudioCommand {
+	type Error = ();
+
+	fn try_from(raw: RawAudioCommand) -> Result<Self, ()> {
+		let [a, b, c, d] = raw.args;
+		Ok(match raw.kind {
+			AUDIO_WRITE => AudioCommand::Write {
+				channel: AudioChannel::try_from(a as u32)?,
+				register: AudioRegister::try_from(b as u32)?,
+				value: c,
+			},
+			AUDIO_PLAY_SAMPLE => AudioCommand::PlaySample {
+				id: a as u8,
+				pitch: b,
+				volume: c as u8,
+				pan: d as u8 as i8,
+			},
+			AUDIO_STOP_SAMPLES => AudioCommand::StopSamples,
+			AUDIO_PLAY_EFFECT => AudioCommand::PlayEffect {
+				offset: raw.offset,
+				len: raw.len,
+				priority: a as u8,
+			},
+			AUDIO_STOP_EFFECTS => AudioCommand::StopEffects,
+			AUDIO_SET_SFX_VOLUME => AudioCommand::SetSfxVolume { volume: a as u8 },
+			_ => return Err(()),
+		})
+	}
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+struct Rumble {
+	low: u16,
+	high: u16,
+	duration_ms: u32,
+}
+
+/// A mapping of a memfd holding the [`SharedConsole`].
+struct SharedMapping {
+	fd: c_int,
+	console: *mut SharedConsole,
+}
+
+impl SharedMapping {
+	/// Creates a new zeroed console. The memfd is inherited by child processes.
+	fn create() -> Self {
+		unsafe {
+			let fd = libc::memfd_create(c"avk-console".as_ptr(), 0);
+			if fd < 0 {
+				panic!(
+					"Failed to create shared memory: {}",
+					io::Error::last_os_error()
+				);
+			}
+			if libc::ftruncate(fd, size_of::<SharedConsole>() as libc::off_t) != 0 {
+				panic!(
+					"Failed to size shared memory: {}",
+					io::Error::last_os_error()
+				);
+			}
+			let this = Self::open(fd);
+			libc::sem_init(&mut (*this.console).rom_ready, 1, 0);
+			libc::sem_init(&mut (*this.console).runner_ready, 1, 0);
+			this
+		}
+	}
+
+	/// Maps a console created by the runner process.
+	fn open(fd: c_int) -> Self {
+		unsafe {
+			let console = libc::mmap(
+				null_mut(),
+				size_of::<SharedConsole>(),
+				libc::PROT_READ | libc::PROT_WRITE,
+				libc::MAP_SHARED,
+				fd,
+				0,
+			);
+			if console == libc::MAP_FAILED {
+				panic!(
+					"Failed to map shared memory: {}",
+					io::Error::last_os_error()
+				);
+			}
+			Self {
+				fd,
+				console: console as *mut SharedConsole,
+			}
+		}
+	}
+}
+
+impl Drop for SharedMapping {
+	fn drop(&mut self) {
+		unsafe {
+			libc::munmap(self.console as *mut c_void, size_of::<SharedConsole>());
+			libc::close(self.fd);
+		}
+	}
+}
+
+/// Waits on a semaphore, retrying if interrupted by a signal.
+unsafe fn sem_wait(sem: *mut sem_t) {
+	while libc::sem_wait(sem) != 0 {}
+}
+
+// ---- ROM process side ----
+
+thread_local! {
+	static SHARED: Cell<*mut SharedConsole> = const { Cell::new(null_mut()) };
+}
+
+/// Hands control to the runner until it has presented the frame.
+unsafe fn sync(request: u32) {
+	let shared = SHARED.get();
+	(*shared).request = request;
+	libc::sem_post(&mut (*shared).rom_ready);
+	sem_wait(&mut (*shared).runner_ready);
+}
+
+extern "C" fn rom_avk_init(images: *const Image, palettes: *const Palette) -> *mut AvkRaw {
+	unsafe {
+		let shared = SHARED.get();
+		(*shared).images = *(images as *const [Image; MAX_IMAGES]);
+		(*shared).palettes = *(palettes as *const [Palette; MAX_PALETTES]);
+		sync(REQUEST_INIT);
+		&mut (*shared).raw
+	}
+}
+
+extern "C" fn rom_avk_drop(_avk: *mut AvkRaw) {
+	// the console belongs to the runner process
+}
+
+extern "C" fn rom_avk_update(_avk: *mut AvkRaw) -> bool {
+	unsafe {
+		sync(REQUEST_UPDATE);
+		(*SHARED.get()).running != 0
+	}
+}
+
+extern "C" fn rom_avk_get_time(_avk: *const AvkRaw) -> u64 {
+	unsafe { (*SHARED.get()).time }
+}
+
+extern "C" fn rom_avk_get_input(
+	_avk: *const AvkRaw,
+	player: Player,
+	input: AvkGamepadInput,
+) -> bool {
+	unsafe { (*SHARED.get()).input[player.index()][input as usize] != 0 }
+}
+
+extern "C" fn rom_avk_held_frames(
+	_avk: *const AvkRaw,
+	player: Player,
+	input: AvkGamepadInput,
+) -> u32 {
+	unsafe { (*SHARED.get()).held_frames[player.index()][input as usize] }
+}
+
+extern "C" fn rom_avk_just_released(
+	_avk: *const AvkRaw,
+	player: Player,
+	input: AvkGamepadInput,
+) -> bool {
+	unsafe { (*SHARED.get()).just_released[player.index()][input as usize] != 0 }
+}
+
+extern "C" fn rom_avk_is_connected(_avk: *const AvkRaw, player: Player) -> bool {
+	unsafe { (*SHARED.get()).connected[player.index()] != 0 }
+}
+
+extern "C" fn rom_avk_get_pointer(_avk: *const AvkRaw, player: Player, pointer: *mut AvkPointer) {
+	unsafe { *pointer = (*SHARED.get()).pointers[player.index()] }
+}
+
+extern "C" fn rom_avk_get_axis(_avk: *const AvkRaw, player: Player, axis: AvkGamepadAxis) -> f32 {
+	unsafe { (*SHARED.get()).axes[player.index()][axis as usize] }
+}
+
+extern "C" fn rom_avk_set_deadzone(
+	_avk: *mut AvkRaw,
+	player: Player,
+	axis: AvkGamepadAxis,
+	deadzone: f32,
+) {
+	unsafe { (*SHARED.get()).deadzones[player.index()][axis as usize] = deadzone }
+}
+
+extern "C" fn rom_avk_rumble(
+	_avk: *mut AvkRaw,
+	player: Player,
+	low: u16,
+	high: u16,
+	duration_ms: u32,
+) {
+	let rumble = Rumble {
+		low,
+		high,
+		duration_ms,
+	};
+	unsafe {
+		let shared = SHARED.get();
+		(*shared).rumble[player.index()] = rumble;
+		(*shared).rumble_dirty[player.index()] = 1;
+	}
+}
+
+extern "C" fn rom_avk_set_text_input(_avk: *mut AvkRaw, enabled: bool) {
+	unsafe {
+		let shared = SHARED.get();
+		(*shared).text_input = enabled as u8;
+		(*shared).text_input_dirty = 1;
+	}
+}
+
+extern "C" fn rom_avk_read_text(_avk: *const AvkRaw, buf: *mut u8, len: usize) -> usize {
+	unsafe {
+		let Some(buf) = rom_bytes_mut(buf, len) else {
+			return 0;
+		};
+		let shared = &*SHARED.get();
+		let text = &shared.text[..shared.text_len.min(MAX_TEXT_INPUT)];
+		let text = std::str::from_utf8(text).unwrap_or_default();
+		// cut short at a character boundary, like the runner does
+		let mut len = buf.len().min(text.len());
+		while !text.is_char_boundary(len) {
+			len -= 1;
+		}
+		buf[..len].copy_from_slice(&text.as_bytes()[..len]);
+		len
+	}
+}
+
+extern "C" fn rom_avk_save_read(
+	_avk: *const AvkRaw,
+	offset: usize,
+	buf: *mut u8,
+	len: usize,
+) -> usize {
+	unsafe {
+		let Some(buf) = rom_bytes_mut(buf, len) else {
+			return 0;
+		};
+		let Some(src) = (*SHARED.get()).save.get(offset..) else {
+			return 0;
+		};
+		let len = buf.len().min(src.len());
+		buf[..len].copy_from_slice(&src[..len]);
+		len
+	}
+}
+
+extern "C" fn rom_avk_save_write(
+	_avk: *mut AvkRaw,
+	offset: usize,
+	data: *const u8,
+	len: usize,
+) -> usize {
+	unsafe {
+		let Some(data) = rom_bytes(data, len) else {
+			return 0;
+		};
+		let shared = SHARED.get();
+		let Some(dst) = (*shared).save.get_mut(offset..) else {
+			return 0;
+		};
+		let len = data.len().min(dst.len());
+		dst[..len].copy_from_slice(&data[..len]);
+		(*shared).save_dirty = 1;
+		len
+	}
+}
+
+/// Queues up an audio call for the runner to make at the next update.
+unsafe fn queue_audio(command: AudioCommand) {
+	let shared = SHARED.get();
+	let count = (*shared).audio_command_count;
+	// a ROM making this many calls without updating can't be heard anyway
+	if count < MAX_AUDIO_COMMANDS {
+		(*shared).audio_commands[count] = command.into();
+		(*shared).audio_command_count = count + 1;
+	}
+}
+
+extern "C" fn rom_avk_audio_write(
+	_avk: *mut AvkRaw,
+	channel: AudioChannel,
+	register: AudioRegister,
+	value: u16,
+) {
+	unsafe {
+		queue_audio(AudioCommand::Write {
+			channel,
+			register,
+			value,
+		})
+	}
+}
+
+extern "C" fn rom_avk_play_music(_avk: *mut AvkRaw, data: *const u8, len: usize) {
+	unsafe {
+		let Some(data) = rom_bytes(data, len) else {
+			return;
+		};
+		let shared = SHARED.get();
+		// songs that don't fit are cut short, so the runner rejects them
+		let len = data.len().min(MAX_SONG_SIZE);
+		let music = &mut (*shared).music;
+		music[..len].copy_from_slice(&data[..len]);
+		(*shared).music_len = len;
+		(*shared).music_request = MUSIC_PLAY;
+	}
+}
+
+extern "C" fn rom_avk_stop_music(_avk: *mut AvkRaw) {
+	unsafe { (*SHARED.get()).music_request = MUSIC_STOP };
+}
+
+extern "C" fn rom_avk_set_music_volume(_avk: *mut AvkRaw, volume: u8) {
+	unsafe {
+		let shared = SHARED.get();
+		(*shared).music_volume = volume;
+		(*shared).music_volume_dirty = 1;
+	}
+}
+
+extern "C" fn rom_avk_register_sample(_avk: *mut AvkRaw, id: u8, data: *const u8, len: usize) {
+	unsafe {
+		let Some(data) = rom_bytes(data, len) else {
+			return;
+		};
+		let shared = SHARED.get();
+		// samples that don't fit are cut short, so the runner rejects them
+		let len = data.len().min(MAX_SAMPLE_SIZE);
+		let sample = &mut (*shared).sample;
+		sample[..len].copy_from_slice(&data[..len]);
+		(*shared).sample_len = len;
+		(*shared).sample_id = id;
+		sync(REQUEST_REGISTER_SAMPLE);
+	}
+}
+
+extern "C" fn rom_avk_play_sample(_avk: *mut AvkRaw, id: u8, pitch: u16, volume: u8, pan: i8) {
+	unsafe {
+		queue_audio(AudioCommand::PlaySample {
+			id,
+			pitch,
+			volume,
+			pan,
+		})
+	}
+}
+
+extern "C" fn rom_avk_stop_samples(_avk: *mut AvkRaw) {
+	unsafe { queue_audio(AudioCommand::StopSamples) }
+}
+
+extern "C" fn rom_avk_play_effect(_avk: *mut AvkRaw, data: *const u8, len: usize, priority: u8) {
+	unsafe {
+		let Some(data) = rom_bytes(data, len) else {
+			return;
+		};
+		let shared = SHARED.get();
+		let offset = (*shared).effects_len;
+		// effects that don't fit before the next update are dropped
+		if len > MAX_SONG_SIZE - offset {
+			return;
+		}
+		let effects = &mut (*shared).effects;
+		effects[offset..offset + len].copy_from_slice(data);
+		(*shared).effects_len = offset + len;
+		queue_audio(AudioCommand::PlayEffect {
+			offset,
+			len,
+			priority,
+		})
+	}
+}
+
+extern "C" fn rom_avk_stop_effects(_avk: *mut AvkRaw) {
+	unsafe { queue_audio(AudioCommand::StopEffects) }
+}
+
+extern "C" fn rom_avk_set_sfx_volume(_avk: *mut AvkRaw, volume: u8) {
+	unsafe { queue_audio(AudioCommand::SetSfxVolume { volume }) }
+}
+
+extern "C" fn rom_avk_report_panic(message: *const u8, len: usize) {
+	// ends up in the crash dialog, through the tail of stderr
+	let message = unsafe { rom_bytes(message, len) }.unwrap_or_default();
+	error!("ROM panicked: {}", String::from_utf8_lossy(message));
+}
+
+/// Entry point of the ROM process, started by the runner with `--rom-process <fd>`.
+pub fn rom_process(fd: c_int, rom_path: &Path) -> ! {
+	let shared = SharedMapping::open(fd);
+	SHARED.set(shared.console);
+
+	let binds = AvkBinds {
+		init: rom_avk_init as *const c_void,
+		drop: rom_avk_drop as *const c_void,
+		update: rom_avk_update as *const c_void,
+		get_time: rom_avk_get_time as *const c_void,
+		get_input: rom_avk_get_input as *const c_void,
+		save_read: rom_avk_save_read as *const c_void,
+		save_write: rom_avk_save_write as *const c_void,
+		report_panic: rom_avk_report_panic as *const c_void,
+		audio_write: rom_avk_audio_write as *const c_void,
+		play_music: rom_avk_play_music as *const c_void,
+		stop_music: rom_avk_stop_music as *const c_void,
+		set_music_volume: rom_avk_set_music_volume as *const c_void,
+		register_sample: rom_avk_register_sample as *const c_void,
+		play_sample: rom_avk_play_sample as *const c_void,
+		stop_samples: rom_avk_stop_samples as *const c_void,
+		play_effect: rom_avk_play_effect as *const c_void,
+		stop_effects: rom_avk_stop_effects as *const c_void,
+		set_sfx_volume: rom_avk_set_sfx_volume as *const c_void,
+		held_frames: rom_avk_held_frames as *const c_void,
+		just_released: rom_avk_just_released as *const c_void,
+		get_axis: rom_avk_get_axis as *const c_void,
+		set_deadzone: rom_avk_set_deadzone as *const c_void,
+		is_connected: rom_avk_is_connected as *const c_void,
+		rumble: rom_avk_rumble as *const c_void,
+		get_pointer: rom_avk_get_pointer as *const c_void,
+		set_text_input: rom_avk_set_text_input as *const c_void,
+		read_text: rom_avk_read_text as *const c_void,
+	};
+	let lib = native::load(rom_path, &binds);
+	if !metadata::check_abi_version(metadata::read_native_abi_version(&lib)) {
+		process::exit(1);
+	}
+	if let Some(metadata) = metadata::read_native(&lib) {
+		unsafe {
+			(*shared.console).metadata = metadata;
+			(*shared.console).has_metadata = 1;
+		}
+	}
+
+	if let Some(mut rom) = NativeFrameRom::load(&lib) {
+		// the runner decides when to pause and fast-forward, all we do is wait on it
+		unsafe { (*shared.console).frame_driven = 1 };
+		rom.start();
+		loop {
+			rom.frame();
+			if !rom_avk_update(null_mut()) {
+				break;
+			}
+		}
+		rom.stop();
+	} else {
+		native::call_main(&lib);
+	}
+
+	process::exit(0)
+}
+
+// ---- runner side ----
+
+/// What the ROM process did while the runner was waiting on it.
+enum RomEvent {
+	Request(u32),
+	Exited,
+	Crashed(String),
+}
+
+/// How a ROM process's run ended.
+enum Outcome {
+	Quit,
+	Finished,
+	Crashed(String),
+}
+
+struct RomProcess {
+	child: Child,
+	shared: SharedMapping,
+	stderr_tail: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl RomProcess {
+	fn spawn(rom_path: &Path) -> Self {
+		let shared = SharedMapping::create();
+		let mut child = Command::new(current_exe().unwrap())
+			.arg("--rom-process")
+			.arg(shared.fd.to_string())
+			.arg(rom_path)
+			.stderr(Stdio::piped())
+			.spawn()
+			.unwrap();
+
+		// pass the ROM's stderr through, keeping the last few lines to explain crashes
+		let stderr_tail = Arc::new(Mutex::new(VecDeque::with_capacity(STDERR_TAIL_LINES)));
+		{
+			let stderr = child.stderr.take().unwrap();
+			let stderr_tail = stderr_tail.clone();
+			thread::spawn(move || {
+				for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+					eprintln!("{line}");
+					let mut tail = stderr_tail.lock().unwrap();
+					if tail.len() == STDERR_TAIL_LINES {
+						tail.pop_front();
+					}
+					tail.push_back(line);
+				}
+			});
+		}
+
+		Self {
+			child,
+			shared,
+			stderr_tail,
+		}
+	}
+
+	fn console(&mut self) -> &mut SharedConsole {
+		unsafe { &mut *self.shared.console }
+	}
+
+	/// Lets the ROM process continue past its synchronization point.
+	fn resume(&mut self) {
+		unsafe { libc::sem_post(&mut self.console().runner_ready) };
+	}
+
+	/// Waits for the ROM process to reach a synchronization point or exit.
+	fn wait(&mut self) -> RomEvent {
+		loop {
+			let deadline = unsafe {
+				let mut now = mem::zeroed::<libc::timespec>();
+				libc::clock_gettime(libc::CLOCK_REALTIME, &mut now);
+				let nanos = now.tv_nsec + POLL_INTERVAL.as_nanos() as libc::c_long;
+				libc::timespec {
+					tv_sec: now.tv_sec + nanos / 1_000_000_000,
+					tv_nsec: nanos % 1_000_000_000,
+				}
+			};
+			if unsafe { libc::sem_timedwait(&mut self.console().rom_ready, &deadline) } == 0 {
+				return RomEvent::Request(self.console().request);
+			}
+
+			match self.child.try_wait() {
+				Ok(Some(status)) if status.success() => return RomEvent::Exited,
+				Ok(Some(status)) => return RomEvent::Crashed(self.crash_reason(status)),
+				Ok(None) => {}
+				Err(err) => return RomEvent::Crashed(err.to_string()),
+			}
+		}
+	}
+
+	fn crash_reason(&self, status: ExitStatus) -> String {
+		let mut reason = format!("The ROM crashed ({status}).");
+		let tail = self.stderr_tail.lock().unwrap();
+		if !tail.is_empty() {
+			reason.push_str("\n\n");
+			reason.push_str(&Vec::from(tail.clone()).join("\n"));
+		}
+		reason
+	}
+
+	/// Writes anything the ROM saved to disk.
+	fn flush_save(&mut self, raw: *mut AvkRaw) {
+		let console = self.console();
+		if mem::take(&mut console.save_dirty) != 0 {
+			unsafe { backend(raw).save_write(0, &console.save) };
+		}
+	}
+
+	/// Hands the deadzones the ROM process set back to the backend.
+	fn flush_deadzones(&mut self, raw: *mut AvkRaw) {
+		let console = self.console();
+		let backend = unsafe { backend(raw) };
+		for player in Player::ALL {
+			for axis in AvkGamepadAxis::ALL {
+				let deadzone = console.deadzones[player.index()][axis as usize];
+				if deadzone != backend.deadzone(player, axis) {
+					backend.set_deadzone(player, axis, deadzone);
+				}
+			}
+		}
+	}
+
+	/// Rumbles the gamepads the ROM process asked to.
+	fn flush_rumble(&mut self, raw: *mut AvkRaw) {
+		let console = self.console();
+		let backend = unsafe { backend(raw) };
+		for player in Player::ALL {
+			if mem::take(&mut console.rumble_dirty[player.index()]) != 0 {
+				let rumble = console.rumble[player.index()];
+				backend.rumble(player, rumble.low, rumble.high, rumble.duration_ms);
+			}
+		}
+	}
+
+	/// Turns text input on or off if the ROM process asked to.
+	fn flush_text_input(&mut self, raw: *mut AvkRaw) {
+		let console = self.console();
+		if mem::take(&mut console.text_input_dirty) != 0 {
+			unsafe { backend(raw).set_text_input(console.text_input != 0) };
+		}
+	}
+
+	/// Waits for the ROM process like [`wait`](Self::wait), registering the samples it sends
+	/// on the way.
+	fn wait_registering(&mut self, raw: *mut AvkRaw) -> RomEvent {
+		loop {
+			match self.wait() {
+				RomEvent::Request(REQUEST_REGISTER_SAMPLE) => {
+					let console = self.console();
+					let sample = &console.sample[..console.sample_len.min(MAX_SAMPLE_SIZE)];
+					unsafe { backend(raw).register_sample(console.sample_id, sample) };
+					self.resume();
+				}
+				event => return event,
+			}
+		}
+	}
+
+	/// Makes the audio calls the ROM queued up.
+	fn flush_audio(&mut self, raw: *mut AvkRaw) {
+		let console = self.console();
+		let count = mem::take(&mut console.audio_command_count).min(MAX_AUDIO_COMMANDS);
+		let backend = unsafe { backend(raw) };
+		// anything that doesn't decode was scribbled over by the ROM process, not queued up
+		for command in &console.audio_commands[..count] {
+			let Ok(command) = AudioCommand::try_from(*command) else {
+				continue;
+			};
+			match command {
+				AudioCommand::Write {
+					channel,
+					register,
+					value,
+				} => backend.audio_write(channel, register, value),
+				AudioCommand::PlaySample {
+					id,
+					pitch,
+					volume,
+					pan,
+				} => backend.play_sample(id, pitch, volume, pan),
+				AudioCommand::StopSamples => backend.stop_samples(),
+				AudioCommand::PlayEffect {
+					offset,
+					len,
+					priority,
+				} => {
+					let end = offset.saturating_add(len);
+					let effect = console.effects.get(offset..end).unwrap_or(&[]);
+					backend.play_effect(effect, priority);
+				}
+				AudioCommand::StopEffects => backend.stop_effects(),
+				AudioCommand::SetSfxVolume { volume } => backend.set_sfx_volume(volume),
+			}
+		}
+		console.effects_len = 0;
+
+		match mem::take(&mut console.music_request) {
+			MUSIC_PLAY => {
+				backend.play_music(&console.music[..console.music_len.min(MAX_SONG_SIZE)])
+			}
+			MUSIC_STOP => backend.stop_music(),
+			_ => {}
+		}
+		if mem::take(&mut console.music_volume_dirty) != 0 {
+			backend.set_music_volume(console.music_volume);
+		}
+	}
+
+	/// Runs the ROM process until it quits or crashes.
+	/// `raw` is the runner-side console, created when the first ROM process initializes AVK.
+	fn run(&mut self, rom_path: &Path, raw: &mut *mut AvkRaw) -> Outcome {
+		match self.wait() {
+			RomEvent::Request(REQUEST_INIT) => {}
+			RomEvent::Request(_) => {
+				return Outcome::Crashed(String::from("The ROM updated before initializing AVK."))
+			}
+			RomEvent::Exited => return Outcome::Finished,
+			RomEvent::Crashed(reason) => return Outcome::Crashed(reason),
+		}
+
+		let console = self.console();
+		if raw.is_null() {
+			metadata::set(
+				rom_path,
+				(console.has_metadata != 0).then_some(console.metadata),
+			);
+			*raw = avk_init(console.images.as_ptr(), console.palettes.as_ptr());
+		} else {
+			unsafe { backend(*raw).reload_resources(&console.images, &console.palettes) };
+		}
+		let backend = unsafe { backend(*raw) };
+		backend.save_read(0, &mut console.save);
+		if console.frame_driven != 0 {
+			backend.use_frame_clock();
+		}
+		// even ROMs with their own main loop wait for the runner on every update here
+		backend.use_runner_controls();
+
+		let mut paused = false;
+		loop {
+			let controls = backend.take_controls();
+			if controls.quit {
+				return Outcome::Quit;
+			}
+			if controls.pause {
+				paused = !paused;
+				info!("{}", if paused { "Paused" } else { "Resumed" });
+			}
+
+			let frames = if paused {
+				controls.step as u32
+			} else if controls.fast_forward {
+				FAST_FORWARD_FRAMES
+			} else {
+				1
+			};
+			for _ in 0..frames {
+				let console = self.console();
+				console.running = 1;
+				console.time = backend.get_time();
+				console.text_len = backend.read_text(&mut console.text);
+				for player in Player::ALL {
+					console.connected[player.index()] = backend.is_connected(player) as u8;
+					console.pointers[player.index()] = backend.get_pointer(player);
+					for input in AvkGamepadInput::ALL {
+						let (p, i) = (player.index(), input as usize);
+						console.input[p][i] = backend.get_input(player, input) as u8;
+						console.held_frames[p][i] = backend.held_frames(player, input);
+						console.just_released[p][i] = backend.just_released(player, input) as u8;
+					}
+					for axis in AvkGamepadAxis::ALL {
+						let (p, a) = (player.index(), axis as usize);
+						console.axes[p][a] = backend.get_axis(player, axis);
+						console.deadzones[p][a] = backend.deadzone(player, axis);
+					}
+				}
+				self.resume();
+
+				let event = self.wait_registering(*raw);
+				self.flush_save(*raw);
+				self.flush_audio(*raw);
+				self.flush_deadzones(*raw);
+				self.flush_rumble(*raw);
+				self.flush_text_input(*raw);
+				match event {
+					RomEvent::Request(REQUEST_UPDATE) => {}
+					RomEvent::Request(_) => {
+						return Outcome::Crashed(String::from(
+							"The ROM initialized AVK more than once.",
+						))
+					}
+					RomEvent::Exited => return Outcome::Finished,
+					RomEvent::Crashed(reason) => return Outcome::Crashed(reason),
+				}
+
+				let frame = &self.console().raw;
+				unsafe {
+					(**raw).background = frame.background;
+					(**raw).foreground = frame.foreground;
+					(**raw).pan_x = frame.pan_x;
+					(**raw).pan_y = frame.pan_y;
+				}
+				backend.advance_frame();
+			}
+
+			if !avk_update(*raw) {
+				return Outcome::Quit;
+			}
+		}
+	}
+
+	/// Asks the ROM process to quit, killing it if it doesn't.
+	/// ROMs commonly save on their way out, so the save block is taken back until it's gone.
+	fn shutdown(mut self, raw: *mut AvkRaw) {
+		self.console().running = 0;
+		self.resume();
+
+		let start = Instant::now();
+		while start.elapsed() < SHUTDOWN_TIMEOUT {
+			if let Ok(Some(_)) = self.child.try_wait() {
+				// the shared mapping outlives the process, with whatever it wrote last
+				self.flush_save(raw);
+				return;
+			}
+			// keep answering ROMs that update a few more times on their way out
+			if unsafe { libc::sem_trywait(&mut self.console().rom_ready) } == 0 {
+				self.flush_save(raw);
+				self.resume();
+			}
+			thread::sleep(Duration::from_millis(10));
+		}
+
+		warn!("ROM did not quit in time, killing it");
+		let _ = self.child.kill();
+		let _ = self.child.wait();
+	}
+}
+
+/// Runs a native ROM in a separate process, offering to restart it whenever it crashes.
+pub fn run(rom_path: &Path) {
+	let mut raw: *mut AvkRaw = null_mut();
+	loop {
+		let mut rom = RomProcess::spawn(rom_path);
+		match rom.run(rom_path, &mut raw) {
+			Outcome::Quit => {
+				rom.shutdown(raw);
+				break;
+			}
+			Outcome::Finished => break,
+			Outcome::Crashed(reason) => {
+				error!("{reason}");
+				let _ = rom.child.wait();
+				// there's nobody to ask when running headless
+				if headless::is_enabled() {
+					break;
+				}
+				let window = unsafe { raw.as_mut().and_then(|raw| backend(raw).window()) };
+				if !sdl::ask_restart(window, &reason) {
+					break;
+				}
+				info!("Restarting ROM");
+			}
+		}
+	}
+
+	if !raw.is_null() {
+		avk_drop(raw);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn audio_commands_round_trip() {
+		let commands = [
+			AudioCommand::Write {
+				channel: AudioChannel::Noise,
+				register: AudioRegister::Trigger,
+				value: 0xbeef,
+			},
+			AudioCommand::PlaySample {
+				id: 255,
+				pitch: 0x0180,
+				volume: 15,
+				pan: -127,
+			},
+			AudioCommand::StopSamples,
+			AudioCommand::PlayEffect {
+				offset: 12,
+				len: MAX_SONG_SIZE,
+				priority: 3,
+			},
+			AudioCommand::StopEffects,
+			AudioCommand::SetSfxVolume { volume: 128 },
+		];
+		for command in commands {
+			assert_eq!(
+				AudioCommand::try_from(RawAudioCommand::from(command)),
+				Ok(command)
+			);
+		}
+	}
+
+	#[test]
+	fn invalid_audio_commands_are_rejected() {
+		let write = RawAudioCommand::from(AudioCommand::Write {
+			channel: AudioChannel::Pulse1,
+			register: AudioRegister::Frequency,
+			value: 440,
+		});
+		let mut channel = write;
+		channel.args[0] = AudioChannel::COUNT as u16;
+		assert_eq!(AudioCommand::try_from(channel), Err(()));
+		let mut register = write;
+		register.args[1] = u16::MAX;
+		assert_eq!(AudioCommand::try_from(register), Err(()));
+		for kind in [0, AUDIO_SET_SFX_VOLUME + 1, u8::MAX] {
+			assert_eq!(
+				AudioCommand::try_from(RawAudioCommand { kind, ..write }),
+				Err(())
+			);
+		}
+	}
+}
@@ -1,40 +1,150 @@
-use crate::c_binds::{avk_drop, avk_get_input, avk_get_time, avk_init, avk_update};
-use dlopen2::raw::Library;
+use crate::native::{AvkBinds, NativeFrameRom};
+use log::{info, warn};
 use std::env::args;
-use std::ffi::c_void;
-use std::mem;
 use std::path::Path;
 
 mod backend;
 mod c_binds;
+mod frame;
+mod headless;
+mod isolate;
 mod logchamp;
+mod metadata;
+mod movie;
+mod native;
+mod reload;
 mod render;
+mod save;
+mod script;
 mod sdl;
+mod wasm;
 
 fn main() {
 	logchamp::init().unwrap();
-	let args = args().collect::<Vec<String>>();
-	let so_path_arg = &args[1];
-	let so_path = Path::new(so_path_arg).canonicalize().unwrap();
-	let lib = Library::open(so_path).unwrap();
-	unsafe {
-		// load the external function pointers
-		let ext_avk_init = lib.symbol::<*const c_void>("AVK_INIT").unwrap();
-		let ext_avk_drop = lib.symbol::<*const c_void>("AVK_DROP").unwrap();
-		let ext_avk_update = lib.symbol::<*const c_void>("AVK_UPDATE").unwrap();
-		let ext_avk_get_time = lib.symbol::<*const c_void>("AVK_GET_TIME").unwrap();
-		let ext_avk_get_input = lib.symbol::<*const c_void>("AVK_GET_INPUT").unwrap();
-		let ext_avk_main = lib.symbol::<*const c_void>("avk_main").unwrap();
-
-		// write the actual in-memory function pointers to the destinations
-		*(ext_avk_init as *mut *const c_void) = avk_init as *const c_void;
-		*(ext_avk_drop as *mut *const c_void) = avk_drop as *const c_void;
-		*(ext_avk_update as *mut *const c_void) = avk_update as *const c_void;
-		*(ext_avk_get_time as *mut *const c_void) = avk_get_time as *const c_void;
-		*(ext_avk_get_input as *mut *const c_void) = avk_get_input as *const c_void;
-
-		// call the external main function
-		let main = mem::transmute::<*const c_void, fn()>(ext_avk_main);
-		main()
+
+	let mut watch = false;
+	let mut isolate = false;
+	let mut rom_process_fd = None;
+	let mut headless = None;
+	let mut movie = None;
+	let mut script = None;
+	let mut rom_path_arg = None;
+	let mut args = args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--watch" => watch = true,
+			"--isolate" => isolate = true,
+			// headless runs have no window, and are run on the frame clock
+			"--headless" => {
+				headless.get_or_insert_with(headless::Config::default);
+			}
+			"--frames" => {
+				let frames = args.next().expect("--frames needs a frame count");
+				headless
+					.get_or_insert_with(headless::Config::default)
+					.frames = Some(frames.parse().expect("Invalid --frames count"));
+			}
+			"--record-audio" => {
+				let path = args.next().expect("--record-audio needs a WAV file path");
+				headless
+					.get_or_insert_with(headless::Config::default)
+					.record_audio = Some(path.into());
+			}
+			"--record-movie" => {
+				let path = args.next().expect("--record-movie needs a movie file path");
+				movie = Some(movie::Config::Record(path.into()));
+			}
+			"--play-movie" => {
+				let path = args.next().expect("--play-movie needs a movie file path");
+				movie = Some(movie::Config::Play(path.into()));
+			}
+			"--input-script" => {
+				let path = args.next().expect("--input-script needs a file path");
+				script = Some(script::Config::File(path.into()));
+			}
+			"--input-socket" => {
+				let path = args.next().expect("--input-socket needs a socket path");
+				script = Some(script::Config::Socket(path.into()));
+			}
+			"--pointer" => {
+				let player = args.next().expect("--pointer needs a player");
+				let player = sdl::bindings::player_by_name(&player)
+					.expect("Invalid --pointer player, expected alpha, bravo, charlie or delta");
+				sdl::pointer::set_player(player);
+			}
+			"--no-rumble" => sdl::gamepads::disable_rumble(),
+			"--bindings" => {
+				let path = args.next().expect("--bindings needs a file path");
+				sdl::bindings::set_path(path);
+			}
+			"--rom-process" => {
+				let fd = args.next().expect("--rom-process needs a file descriptor");
+				rom_process_fd = Some(fd.parse().expect("Invalid --rom-process file descriptor"));
+			}
+			_ => rom_path_arg = Some(arg),
+		}
+	}
+	let rom_path = Path::new(&rom_path_arg.expect("No ROM path provided!"))
+		.canonicalize()
+		.unwrap();
+
+	// we are the child half of an isolated runner, and only run the ROM itself
+	if let Some(fd) = rom_process_fd {
+		isolate::rom_process(fd, &rom_path);
+	}
+
+	if let Some(config) = headless {
+		headless::enable(config);
+	}
+	if let Some(config) = movie {
+		movie::enable(config);
+	}
+	if let Some(config) = script {
+		script::enable(config);
+	}
+
+	// WebAssembly ROMs are sandboxed in an interpreter, everything else is dlopen'd
+	if rom_path.extension().is_some_and(|ext| ext == "wasm") {
+		wasm::run(&rom_path);
+		return;
+	}
+
+	if isolate {
+		if watch {
+			warn!("--watch is not supported together with --isolate, ignoring it");
+		}
+		isolate::run(&rom_path);
+		return;
+	}
+
+	if watch {
+		reload::enable(&rom_path);
+	}
+
+	let mut generation = 0;
+	loop {
+		let binds = AvkBinds::host();
+		let lib = if watch {
+			native::load_copy(&rom_path, &binds, generation)
+		} else {
+			native::load(&rom_path, &binds)
+		};
+		if !metadata::check_abi_version(metadata::read_native_abi_version(&lib)) {
+			break;
+		}
+		metadata::set(&rom_path, metadata::read_native(&lib));
+
+		if let Some(mut rom) = NativeFrameRom::load(&lib) {
+			frame::run(&mut rom);
+		} else {
+			native::call_main(&lib);
+		}
+		drop(lib);
+
+		if !reload::finish() {
+			break;
+		}
+		generation += 1;
+		info!("Reloading ROM ({generation})");
 	}
 }
@@ -0,0 +1,91 @@
+//! Metadata declared by the running ROM through its `AVK_METADATA` and `AVK_ABI_VERSION` exports.
+//! It names the window, SDL app and save file, so it has to be known before AVK is initialized.
+
+use crate::save;
+use avk_types::prelude::*;
+use avk_types::ABI_VERSION;
+use dlopen2::raw::Library;
+use log::{error, info};
+use std::cell::Cell;
+use std::path::Path;
+
+/// Used for ROMs that don't declare any metadata.
+const DEFAULT: Metadata = Metadata::new("AVK Virtual Console", "", "1.0.0", "computer.living.avk");
+
+thread_local! {
+	static METADATA: Cell<Metadata> = const { Cell::new(DEFAULT) };
+}
+
+/// Reads the metadata exported by a native ROM, if it has any.
+pub fn read_native(lib: &Library) -> Option<Metadata> {
+	unsafe {
+		lib.symbol::<*const Metadata>("AVK_METADATA")
+			.ok()
+			.map(|metadata| *metadata)
+	}
+}
+
+/// Reads the ABI version a native ROM was built against.
+/// ROMs built before the version existed don't export one.
+pub fn read_native_abi_version(lib: &Library) -> Option<u32> {
+	unsafe {
+		lib.symbol::<*const u32>("AVK_ABI_VERSION")
+			.ok()
+			.map(|version| *version)
+	}
+}
+
+/// Returns false, after logging why, if the ROM was built against an incompatible ABI.
+pub fn check_abi_version(version: Option<u32>) -> bool {
+	match version {
+		Some(version) if version != ABI_VERSION => {
+			error!(
+				"The ROM was built for AVK ABI version {version}, but this runner supports version {ABI_VERSION}!"
+			);
+			false
+		}
+		_ => true,
+	}
+}
+
+/// Sets the metadata of the ROM about to run.
+/// Save data is keyed by the ROM's id, falling back to its file name.
+pub fn set(rom_path: &Path, metadata: Option<Metadata>) {
+	let file_stem = rom_path.file_stem().unwrap().to_string_lossy();
+
+	let Some(metadata) = metadata else {
+		save::set_identity(file_stem);
+		METADATA.set(DEFAULT);
+		return;
+	};
+
+	info!(
+		"Running {} {} by {}",
+		metadata.title(),
+		metadata.version(),
+		metadata.author()
+	);
+
+	// the id ends up in a file name, so keep it to a safe set of characters
+	let id: String = metadata
+		.id()
+		.chars()
+		.map(|c| match c {
+			'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+			_ => '_',
+		})
+		.collect();
+	let id = id.trim_matches('.');
+	if id.is_empty() {
+		save::set_identity(file_stem);
+	} else {
+		save::set_identity(id);
+	}
+
+	METADATA.set(metadata);
+}
+
+/// Returns the metadata of the running ROM.
+pub fn get() -> Metadata {
+	METADATA.get()
+}
@@ -0,0 +1,307 @@
+//! Input movies, for `--record-movie` and `--play-movie`.
+//! A movie holds what the ROM saw of the inputs and the clock on every frame it ran. Playing it
+//! back feeds the ROM the very same frames, so a bug caught on camera can be reproduced at will,
+//! and played back headless as a regression test. The save block the ROM started with is part
+//! of the movie too, and is played back from a scratch copy, leaving the ROM's real save alone.
+
+use crate::save::SaveData;
+use avk_types::prelude::*;
+use avk_types::SAVE_SIZE;
+use log::{error, info, warn};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+
+// The header is the magic, the version and the save block the ROM started with.
+const MAGIC: &[u8; 8] = b"AVKMOVIE";
+const VERSION: u32 = 1;
+
+/// Where the parts of a frame start: the time, the inputs, the connected gamepads, the axes, the
+/// pointers and the length of the text, which follows them.
+const AXES: usize = 8 + 8 + 1;
+const POINTERS: usize = AXES + 4 * AvkGamepadAxis::COUNT * 4;
+const TEXT_LEN: usize = POINTERS + 4 * 5;
+const FRAME_SIZE: usize = TEXT_LEN + 1;
+
+#[derive(Clone)]
+pub enum Config {
+	Record(PathBuf),
+	Play(PathBuf),
+}
+
+thread_local! {
+	static CONFIG: RefCell<Option<Config>> = const { RefCell::new(None) };
+}
+
+/// Makes consoles created from now on record or play a movie.
+pub fn enable(config: Config) {
+	CONFIG.set(Some(config));
+}
+
+/// Everything the ROM sees of the inputs and the clock during a frame.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Frame {
+	pub time: u64,
+	pub inputs: [[bool; AvkGamepadInput::COUNT]; 4],
+	pub connected: [bool; 4],
+	pub axes: [[f32; AvkGamepadAxis::COUNT]; 4],
+	pub pointers: [AvkPointer; 4],
+	/// At most `MAX_TEXT_INPUT` bytes, which fits the byte its length is stored in.
+	pub text: String,
+}
+
+impl Frame {
+	fn encode(&self) -> Vec<u8> {
+		let mut inputs = 0u64;
+		let mut connected = 0u8;
+		for player in Player::ALL {
+			for input in AvkGamepadInput::ALL {
+				if self.inputs[player.index()][input as usize] {
+					inputs |= 1 << input_bit(player, input);
+				}
+			}
+			if self.connected[player.index()] {
+				connected |= 1 << player.index();
+			}
+		}
+
+		let mut bytes = vec![0; FRAME_SIZE];
+		bytes[..8].copy_from_slice(&self.time.to_le_bytes());
+		bytes[8..16].copy_from_slice(&inputs.to_le_bytes());
+		bytes[16] = connected;
+		let axes = self.axes.iter().flatten();
+		for (chunk, axis) in bytes[AXES..POINTERS].chunks_exact_mut(4).zip(axes) {
+			chunk.copy_from_slice(&axis.to_le_bytes());
+		}
+		for (chunk, pointer) in bytes[POINTERS..TEXT_LEN]
+			.chunks_exact_mut(5)
+			.zip(&self.pointers)
+		{
+			chunk[..2].copy_from_slice(&pointer.x.to_le_bytes());
+			chunk[2..4].copy_from_slice(&pointer.y.to_le_bytes());
+			chunk[4] = pointer.trigger as u8 | (pointer.on_screen as u8) << 1;
+		}
+		bytes[TEXT_LEN] = self.text.len() as u8;
+		bytes.extend_from_slice(self.text.as_bytes());
+		bytes
+	}
+
+	/// Decodes a frame, except for its text, which is read separately.
+	fn decode(bytes: &[u8; FRAME_SIZE]) -> Self {
+		let inputs = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+		let mut frame = Self {
+			time: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+			..Default::default()
+		};
+		for player in Player::ALL {
+			for input in AvkGamepadInput::ALL {
+				frame.inputs[player.index()][input as usize] =
+					inputs & (1 << input_bit(player, input)) != 0;
+			}
+			frame.connected[player.index()] = bytes[16] & (1 << player.index()) != 0;
+		}
+		let chunks = bytes[AXES..POINTERS].chunks_exact(4);
+		for (axis, chunk) in frame.axes.iter_mut().flatten().zip(chunks) {
+			*axis = f32::from_le_bytes(chunk.try_into().unwrap());
+		}
+		for (pointer, chunk) in frame
+			.pointers
+			.iter_mut()
+			.zip(bytes[POINTERS..TEXT_LEN].chunks_exact(5))
+		{
+			*pointer = AvkPointer {
+				x: i16::from_le_bytes([chunk[0], chunk[1]]),
+				y: i16::from_le_bytes([chunk[2], chunk[3]]),
+				trigger: chunk[4] & 1 != 0,
+				on_screen: chunk[4] & 2 != 0,
+			};
+		}
+		frame
+	}
+}
+
+pub enum Movie {
+	Recording { file: BufWriter<File>, frames: u64 },
+	Playing { file: BufReader<File>, frames: u64 },
+}
+
+impl Movie {
+	/// Returns None if no movie is being recorded or played.
+	/// A recording stores `save` in the movie, and playing one replaces it with the stored one.
+	pub fn new(save: &mut SaveData) -> Option<Self> {
+		let config = CONFIG.with_borrow(Clone::clone)?;
+		Some(match config {
+			Config::Record(path) => {
+				let mut file = File::create(&path)
+					.map(BufWriter::new)
+					.unwrap_or_else(|err| panic!("Failed to create {}: {err}", path.display()));
+				let mut block = [0; SAVE_SIZE];
+				save.read(0, &mut block);
+				file.write_all(MAGIC)
+					.and_then(|()| file.write_all(&VERSION.to_le_bytes()))
+					.and_then(|()| file.write_all(&block))
+					.unwrap_or_else(|err| panic!("Failed to write {}: {err}", path.display()));
+				info!("Recording a movie to {}", path.display());
+				Self::Recording { file, frames: 0 }
+			}
+			Config::Play(path) => {
+				let mut file = File::open(&path)
+					.map(BufReader::new)
+					.unwrap_or_else(|err| panic!("Failed to open {}: {err}", path.display()));
+				let mut header = [0; MAGIC.len() + 4];
+				file.read_exact(&mut header)
+					.unwrap_or_else(|err| panic!("Failed to read {}: {err}", path.display()));
+				if &header[..MAGIC.len()] != MAGIC {
+					panic!("{} is not a movie!", path.display());
+				}
+				let version = u32::from_le_bytes(header[MAGIC.len()..].try_into().unwrap());
+				if version != VERSION {
+					panic!(
+						"{} is a version {version} movie, expected {VERSION}",
+						path.display()
+					);
+				}
+				let mut block = [0; SAVE_SIZE];
+				file.read_exact(&mut block)
+					.unwrap_or_else(|err| panic!("Failed to read {}: {err}", path.display()));
+				*save = SaveData::scratch(block);
+				info!("Playing the movie {}", path.display());
+				Self::Playing { file, frames: 0 }
+			}
+		})
+	}
+
+	pub fn is_playing(&self) -> bool {
+		matches!(self, Self::Playing { .. })
+	}
+
+	/// Appends a frame to the movie being recorded.
+	/// Returns false if the recording failed, after which nothing more is recorded.
+	pub fn record(&mut self, frame: &Frame) -> bool {
+		let Self::Recording { file, frames } = self else {
+			return true;
+		};
+		// flushed every frame, so a crashing ROM leaves a complete movie behind
+		let written = file.write_all(&frame.encode()).and_then(|()| file.flush());
+		if let Err(err) = written {
+			error!("Failed to record the movie: {err}");
+			return false;
+		}
+		*frames += 1;
+		true
+	}
+
+	/// Reads the next frame of the movie being played.
+	/// Returns None once it's over, or if it's cut short.
+	pub fn next_frame(&mut self) -> Option<Frame> {
+		let Self::Playing { file, frames } = self else {
+			return None;
+		};
+		let mut bytes = [0; FRAME_SIZE];
+		let mut text = Vec::new();
+		let read = file.read_exact(&mut bytes).and_then(|()| {
+			text.resize(bytes[TEXT_LEN] as usize, 0);
+			file.read_exact(&mut text)
+		});
+		match read {
+			Ok(()) => {
+				*frames += 1;
+				Some(Frame {
+					text: String::from_utf8_lossy(&text).into_owned(),
+					..Frame::decode(&bytes)
+				})
+			}
+			// a frame cut short by a crash while recording is as good as the end
+			Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+				info!("The movie is over after {frames} frames");
+				None
+			}
+			Err(err) => {
+				warn!("Failed to read the movie: {err}");
+				None
+			}
+		}
+	}
+}
+
+impl Drop for Movie {
+	fn drop(&mut self) {
+		if let Self::Recording { frames, .. } = self {
+			info!("Recorded a movie of {frames} frames");
+		}
+	}
+}
+
+/// Returns which bit of a frame's inputs an input is stored in.
+fn input_bit(player: Player, input: AvkGamepadInput) -> usize {
+	player.index() * AvkGamepadInput::COUNT + input as usize
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use std::fs;
+
+	fn frame(time: u64) -> Frame {
+		let mut frame = Frame {
+			time,
+			text: String::from("héllo"),
+			..Default::default()
+		};
+		frame.inputs[0][AvkGamepadInput::FaceDown as usize] = true;
+		frame.inputs[3][AvkGamepadInput::ALL[AvkGamepadInput::COUNT - 1] as usize] = true;
+		frame.connected = [true, false, false, true];
+		frame.axes[1][0] = -0.5;
+		frame.axes[3][AvkGamepadAxis::COUNT - 1] = 1.0;
+		frame.pointers[2] = AvkPointer {
+			x: -3,
+			y: 191,
+			trigger: true,
+			on_screen: false,
+		};
+		frame
+	}
+
+	#[test]
+	fn frames_round_trip() {
+		let frame = frame(u64::MAX - 1);
+		let bytes = frame.encode();
+		assert_eq!(bytes.len(), FRAME_SIZE + frame.text.len());
+		let (fixed, text) = bytes.split_at(FRAME_SIZE);
+		let decoded = Frame {
+			text: String::from_utf8(text.to_vec()).unwrap(),
+			..Frame::decode(fixed.try_into().unwrap())
+		};
+		assert_eq!(decoded, frame);
+	}
+
+	#[test]
+	fn movies_keep_the_save_block() {
+		let path = env::temp_dir().join(format!("avk-movie-test-{}", std::process::id()));
+		let frames = [frame(0), Frame::default(), frame(2)];
+
+		enable(Config::Record(path.clone()));
+		let mut save = SaveData::scratch([7; SAVE_SIZE]);
+		let mut movie = Movie::new(&mut save).unwrap();
+		for frame in &frames {
+			assert!(movie.record(frame));
+		}
+		drop(movie);
+
+		enable(Config::Play(path.clone()));
+		let mut save = SaveData::scratch([0; SAVE_SIZE]);
+		let mut movie = Movie::new(&mut save).unwrap();
+		let mut block = [0; SAVE_SIZE];
+		save.read(0, &mut block);
+		assert_eq!(block, [7; SAVE_SIZE]);
+		for frame in &frames {
+			assert_eq!(movie.next_frame().as_ref(), Some(frame));
+		}
+		assert_eq!(movie.next_frame(), None);
+
+		CONFIG.set(None);
+		fs::remove_file(path).unwrap();
+	}
+}
@@ -0,0 +1,219 @@
+//! Loading of native (shared object) ROMs.
+
+use crate::c_binds;
+use crate::frame::FrameRom;
+use dlopen2::raw::Library;
+use std::env::temp_dir;
+use std::ffi::c_void;
+use std::path::Path;
+use std::{fs, process};
+
+/// The functions written into a native ROM's AVK_* pointers.
+pub struct AvkBinds {
+	pub init: *const c_void,
+	pub drop: *const c_void,
+	pub update: *const c_void,
+	pub get_time: *const c_void,
+	pub get_input: *const c_void,
+	pub save_read: *const c_void,
+	pub save_write: *const c_void,
+	pub report_panic: *const c_void,
+	pub audio_write: *const c_void,
+	pub play_music: *const c_void,
+	pub stop_music: *const c_void,
+	pub set_music_volume: *const c_void,
+	pub register_sample: *const c_void,
+	pub play_sample: *const c_void,
+	pub stop_samples: *const c_void,
+	pub play_effect: *const c_void,
+	pub stop_effects: *const c_void,
+	pub set_sfx_volume: *const c_void,
+	pub held_frames: *const c_void,
+	pub just_released: *const c_void,
+	pub get_axis: *const c_void,
+	pub set_deadzone: *const c_void,
+	pub is_connected: *const c_void,
+	pub rumble: *const c_void,
+	pub get_pointer: *const c_void,
+	pub set_text_input: *const c_void,
+	pub read_text: *const c_void,
+}
+
+impl AvkBinds {
+	/// The functions running the console in this process.
+	pub fn host() -> Self {
+		Self {
+			init: c_binds::avk_init as *const c_void,
+			drop: c_binds::avk_drop as *const c_void,
+			update: c_binds::avk_update as *const c_void,
+			get_time: c_binds::avk_get_time as *const c_void,
+			get_input: c_binds::avk_get_input as *const c_void,
+			save_read: c_binds::avk_save_read as *const c_void,
+			save_write: c_binds::avk_save_write as *const c_void,
+			report_panic: c_binds::avk_report_panic as *const c_void,
+			audio_write: c_binds::avk_audio_write as *const c_void,
+			play_music: c_binds::avk_play_music as *const c_void,
+			stop_music: c_binds::avk_stop_music as *const c_void,
+			set_music_volume: c_binds::avk_set_music_volume as *const c_void,
+			register_sample: c_binds::avk_register_sample as *const c_void,
+			play_sample: c_binds::avk_play_sample as *const c_void,
+			stop_samples: c_binds::avk_stop_samples as *const c_void,
+			play_effect: c_binds::avk_play_effect as *const c_void,
+			stop_effects: c_binds::avk_stop_effects as *const c_void,
+			set_sfx_volume: c_binds::avk_set_sfx_volume as *const c_void,
+			held_frames: c_binds::avk_held_frames as *const c_void,
+			just_released: c_binds::avk_just_released as *const c_void,
+			get_axis: c_binds::avk_get_axis as *const c_void,
+			set_deadzone: c_binds::avk_set_deadzone as *const c_void,
+			is_connected: c_binds::avk_is_connected as *const c_void,
+			rumble: c_binds::avk_rumble as *const c_void,
+			get_pointer: c_binds::avk_get_pointer as *const c_void,
+			set_text_input: c_binds::avk_set_text_input as *const c_void,
+			read_text: c_binds::avk_read_text as *const c_void,
+		}
+	}
+}
+
+/// Opens a native ROM and writes the AVK function pointers into it.
+pub fn load(path: &Path, binds: &AvkBinds) -> Library {
+	let lib = Library::open(path).unwrap();
+	unsafe {
+		// load the external function pointers
+		let ext_avk_init = lib.symbol::<*const c_void>("AVK_INIT").unwrap();
+		let ext_avk_drop = lib.symbol::<*const c_void>("AVK_DROP").unwrap();
+		let ext_avk_update = lib.symbol::<*const c_void>("AVK_UPDATE").unwrap();
+		let ext_avk_get_time = lib.symbol::<*const c_void>("AVK_GET_TIME").unwrap();
+		let ext_avk_get_input = lib.symbol::<*const c_void>("AVK_GET_INPUT").unwrap();
+
+		// write the actual in-memory function pointers to the destinations
+		*(ext_avk_init as *mut *const c_void) = binds.init;
+		*(ext_avk_drop as *mut *const c_void) = binds.drop;
+		*(ext_avk_update as *mut *const c_void) = binds.update;
+		*(ext_avk_get_time as *mut *const c_void) = binds.get_time;
+		*(ext_avk_get_input as *mut *const c_void) = binds.get_input;
+
+		// ROMs built before save data existed don't have these
+		if let Ok(ext_avk_save_read) = lib.symbol::<*const c_void>("AVK_SAVE_READ") {
+			*(ext_avk_save_read as *mut *const c_void) = binds.save_read;
+		}
+		if let Ok(ext_avk_save_write) = lib.symbol::<*const c_void>("AVK_SAVE_WRITE") {
+			*(ext_avk_save_write as *mut *const c_void) = binds.save_write;
+		}
+		if let Ok(ext_avk_report_panic) = lib.symbol::<*const c_void>("AVK_REPORT_PANIC") {
+			*(ext_avk_report_panic as *mut *const c_void) = binds.report_panic;
+		}
+		if let Ok(ext_avk_audio_write) = lib.symbol::<*const c_void>("AVK_AUDIO_WRITE") {
+			*(ext_avk_audio_write as *mut *const c_void) = binds.audio_write;
+		}
+		if let Ok(ext_avk_play_music) = lib.symbol::<*const c_void>("AVK_PLAY_MUSIC") {
+			*(ext_avk_play_music as *mut *const c_void) = binds.play_music;
+		}
+		if let Ok(ext_avk_stop_music) = lib.symbol::<*const c_void>("AVK_STOP_MUSIC") {
+			*(ext_avk_stop_music as *mut *const c_void) = binds.stop_music;
+		}
+		if let Ok(ext_avk_set_music_volume) = lib.symbol::<*const c_void>("AVK_SET_MUSIC_VOLUME") {
+			*(ext_avk_set_music_volume as *mut *const c_void) = binds.set_music_volume;
+		}
+		if let Ok(ext_avk_register_sample) = lib.symbol::<*const c_void>("AVK_REGISTER_SAMPLE") {
+			*(ext_avk_register_sample as *mut *const c_void) = binds.register_sample;
+		}
+		if let Ok(ext_avk_play_sample) = lib.symbol::<*const c_void>("AVK_PLAY_SAMPLE") {
+			*(ext_avk_play_sample as *mut *const c_void) = binds.play_sample;
+		}
+		if let Ok(ext_avk_stop_samples) = lib.symbol::<*const c_void>("AVK_STOP_SAMPLES") {
+			*(ext_avk_stop_samples as *mut *const c_void) = binds.stop_samples;
+		}
+		if let Ok(ext_avk_play_effect) = lib.symbol::<*const c_void>("AVK_PLAY_EFFECT") {
+			*(ext_avk_play_effect as *mut *const c_void) = binds.play_effect;
+		}
+		if let Ok(ext_avk_stop_effects) = lib.symbol::<*const c_void>("AVK_STOP_EFFECTS") {
+			*(ext_avk_stop_effects as *mut *const c_void) = binds.stop_effects;
+		}
+		if let Ok(ext_avk_set_sfx_volume) = lib.symbol::<*const c_void>("AVK_SET_SFX_VOLUME") {
+			*(ext_avk_set_sfx_volume as *mut *const c_void) = binds.set_sfx_volume;
+		}
+		if let Ok(ext_avk_held_frames) = lib.symbol::<*const c_void>("AVK_HELD_FRAMES") {
+			*(ext_avk_held_frames as *mut *const c_void) = binds.held_frames;
+		}
+		if let Ok(ext_avk_just_released) = lib.symbol::<*const c_void>("AVK_JUST_RELEASED") {
+			*(ext_avk_just_released as *mut *const c_void) = binds.just_released;
+		}
+		if let Ok(ext_avk_get_axis) = lib.symbol::<*const c_void>("AVK_GET_AXIS") {
+			*(ext_avk_get_axis as *mut *const c_void) = binds.get_axis;
+		}
+		if let Ok(ext_avk_set_deadzone) = lib.symbol::<*const c_void>("AVK_SET_DEADZONE") {
+			*(ext_avk_set_deadzone as *mut *const c_void) = binds.set_deadzone;
+		}
+		if let Ok(ext_avk_is_connected) = lib.symbol::<*const c_void>("AVK_IS_CONNECTED") {
+			*(ext_avk_is_connected as *mut *const c_void) = binds.is_connected;
+		}
+		if let Ok(ext_avk_rumble) = lib.symbol::<*const c_void>("AVK_RUMBLE") {
+			*(ext_avk_rumble as *mut *const c_void) = binds.rumble;
+		}
+		if let Ok(ext_avk_get_pointer) = lib.symbol::<*const c_void>("AVK_GET_POINTER") {
+			*(ext_avk_get_pointer as *mut *const c_void) = binds.get_pointer;
+		}
+		if let Ok(ext_avk_set_text_input) = lib.symbol::<*const c_void>("AVK_SET_TEXT_INPUT") {
+			*(ext_avk_set_text_input as *mut *const c_void) = binds.set_text_input;
+		}
+		if let Ok(ext_avk_read_text) = lib.symbol::<*const c_void>("AVK_READ_TEXT") {
+			*(ext_avk_read_text as *mut *const c_void) = binds.read_text;
+		}
+	}
+	lib
+}
+
+/// Loads a private copy of the ROM.
+/// The dynamic loader won't load a path it already has open again, so every reload needs a new one.
+pub fn load_copy(path: &Path, binds: &AvkBinds, generation: u32) -> Library {
+	let copy = temp_dir().join(format!("avk-rom-{}-{generation}.so", process::id()));
+	fs::copy(path, &copy).unwrap();
+	let lib = load(&copy, binds);
+	// the mapping outlives the file, so there's nothing to clean up later
+	let _ = fs::remove_file(&copy);
+	lib
+}
+
+/// Calls the ROM's `avk_main`, which runs its own loop.
+pub fn call_main(lib: &Library) {
+	unsafe {
+		let main = lib.symbol::<fn()>("avk_main").unwrap();
+		main()
+	}
+}
+
+/// The per-frame entry points of a native ROM.
+pub struct NativeFrameRom {
+	start: extern "C" fn(),
+	frame: extern "C" fn(),
+	stop: extern "C" fn(),
+}
+
+impl NativeFrameRom {
+	/// Returns None if the ROM runs its own loop in `avk_main` instead.
+	pub fn load(lib: &Library) -> Option<Self> {
+		unsafe {
+			Some(Self {
+				start: lib.symbol("avk_start").ok()?,
+				frame: lib.symbol("avk_frame").ok()?,
+				stop: lib.symbol("avk_stop").ok()?,
+			})
+		}
+	}
+}
+
+impl FrameRom for NativeFrameRom {
+	fn start(&mut self) -> bool {
+		(self.start)();
+		true
+	}
+
+	fn frame(&mut self) -> bool {
+		(self.frame)();
+		true
+	}
+
+	fn stop(&mut self) {
+		(self.stop)()
+	}
+}
@@ -0,0 +1,111 @@
+//! Hot-reloading of native ROMs for `--watch` mode.
+//! The ROM is asked to quit when its shared object changes on disk, but the console it was
+//! using (window, GL state, AvkBackend and AvkRaw) is kept around for the next ROM to pick up.
+
+use avk_types::AvkRaw;
+use log::debug;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr::null_mut;
+use std::time::SystemTime;
+
+/// How often the ROM's modification time is checked, in milliseconds.
+/// The file also has to stay unchanged for this long, so we don't load a half-written ROM.
+const POLL_INTERVAL_MS: u64 = 250;
+
+struct Watch {
+	path: PathBuf,
+	/// Modification time of the ROM that is currently loaded.
+	loaded: Option<SystemTime>,
+	/// Modification time seen on the last poll.
+	seen: Option<SystemTime>,
+	next_poll_ms: u64,
+	pending: bool,
+	/// The console kept alive across reloads.
+	live: *mut AvkRaw,
+}
+
+thread_local! {
+	static WATCH: RefCell<Option<Watch>> = const { RefCell::new(None) };
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+	fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Starts watching the ROM at `path` for changes.
+pub fn enable(path: &Path) {
+	let loaded = modified(path);
+	WATCH.with_borrow_mut(|watch| {
+		*watch = Some(Watch {
+			path: path.to_path_buf(),
+			loaded,
+			seen: loaded,
+			next_poll_ms: 0,
+			pending: false,
+			live: null_mut(),
+		})
+	});
+}
+
+/// Returns true if the ROM has been rebuilt and should quit so it can be reloaded.
+pub fn poll(now_ms: u64) -> bool {
+	WATCH.with_borrow_mut(|watch| {
+		let Some(watch) = watch else {
+			return false;
+		};
+		if watch.pending || now_ms < watch.next_poll_ms {
+			return watch.pending;
+		}
+		watch.next_poll_ms = now_ms + POLL_INTERVAL_MS;
+
+		let current = modified(&watch.path);
+		if current.is_some() && current != watch.loaded && current == watch.seen {
+			debug!("ROM changed on disk, reloading");
+			watch.pending = true;
+		}
+		watch.seen = current;
+		watch.pending
+	})
+}
+
+/// Returns the console left behind by the previous ROM, if there is one.
+pub fn reuse() -> Option<*mut AvkRaw> {
+	WATCH.with_borrow(|watch| watch.as_ref().map(|w| w.live).filter(|raw| !raw.is_null()))
+}
+
+/// Remembers the console so it can be handed to the next ROM.
+pub fn register(raw: *mut AvkRaw) {
+	WATCH.with_borrow_mut(|watch| {
+		if let Some(watch) = watch {
+			watch.live = raw;
+		}
+	});
+}
+
+/// Returns true if the console should outlive the ROM dropping it, because a reload is pending.
+pub fn keep_alive(raw: *mut AvkRaw) -> bool {
+	WATCH.with_borrow_mut(|watch| match watch {
+		Some(watch) if watch.live == raw => {
+			if !watch.pending {
+				watch.live = null_mut();
+			}
+			watch.pending
+		}
+		_ => false,
+	})
+}
+
+/// Called once the ROM has returned.
+/// Returns true if it quit for a reload, in which case the new ROM should be loaded.
+pub fn finish() -> bool {
+	WATCH.with_borrow_mut(|watch| match watch {
+		Some(watch) if watch.pending => {
+			watch.pending = false;
+			watch.loaded = watch.seen;
+			true
+		}
+		_ => false,
+	})
+}
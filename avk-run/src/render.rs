@@ -38,6 +38,18 @@ const QUAD_FRAG_SOURCE: &str = concat!(include_str!("shaders/quad_frag.glsl"), "
 const VIEW_VERT_SOURCE: &str = concat!(include_str!("shaders/view_vert.glsl"), "\0");
 const VIEW_FRAG_SOURCE: &str = concat!(include_str!("shaders/view_frag.glsl"), "\0");
 
+/// Returns the pixel of the screen under a position in the window, counting from the bottom left
+/// like sprite positions do. The screen is stretched over the whole window when it's drawn.
+/// The position and the window's size must be in the same units, those of mouse events.
+pub fn window_to_screen(x: f32, y: f32, window_width: u32, window_height: u32) -> (i16, i16) {
+	let column = (x / window_width as f32 * RESOLUTION_WIDTH as f32) as i16;
+	let row = (y / window_height as f32 * RESOLUTION_HEIGHT as f32) as i16;
+	(
+		column.clamp(0, RESOLUTION_WIDTH - 1),
+		(RESOLUTION_HEIGHT - 1 - row).clamp(0, RESOLUTION_HEIGHT - 1),
+	)
+}
+
 #[inline(always)]
 pub fn gl_err_check() {
 	unsafe {
@@ -57,6 +69,8 @@ pub(crate) struct AvkRenderManager {
 	viewport_prog: Material,
 	fbo: GLuint,
 	fbt: GLuint,
+	/// Drawn over the screen by draw_overlay, for the runner's own UI.
+	overlay: GLuint,
 }
 
 /// Called by OpenGL whenever an error occurs.
@@ -151,9 +165,27 @@ impl AvkRenderManager {
 				gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
 			}
 
+			let mut overlay = 0;
+			gl::GenTextures(1, &mut overlay);
+			gl::BindTexture(gl::TEXTURE_2D, overlay);
+			gl::TexImage2D(
+				gl::TEXTURE_2D,
+				0,
+				gl::RGBA as GLint,
+				RESOLUTION_WIDTH as GLsizei,
+				RESOLUTION_HEIGHT as GLsizei,
+				0,
+				gl::RGBA,
+				gl::UNSIGNED_BYTE,
+				null(),
+			);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+
 			Self {
 				fbo,
 				fbt,
+				overlay,
 				// create ALL THE TEXTURES!!!
 				textures: Texture::new_bulk(images),
 
@@ -166,7 +198,14 @@ impl AvkRenderManager {
 		}
 	}
 
-	/// Updates the OpenGL rendering backend.
+	/// Replaces every image texture, e.g. after the ROM has been reloaded.
+	pub fn reload_images(&mut self, images: &mut [Image; MAX_IMAGES]) {
+		Texture::delete_bulk(&self.textures);
+		self.textures = Texture::new_bulk(images);
+	}
+
+	/// Updates the OpenGL rendering backend. The window's size is in pixels, which on high DPI
+	/// displays isn't the size mouse positions are relative to.
 	pub fn update(&mut self, avk: *mut AvkBackend, window_width: u32, window_height: u32) {
 		unsafe {
 			gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
@@ -241,4 +280,28 @@ impl AvkRenderManager {
 			gl_err_check();
 		}
 	}
+	/// Blends RGBA pixels the size of the screen, from the bottom row up, over what update drew.
+	pub fn draw_overlay(&mut self, pixels: &[u8]) {
+		unsafe {
+			gl::BindTexture(gl::TEXTURE_2D, self.overlay);
+			gl::TexSubImage2D(
+				gl::TEXTURE_2D,
+				0,
+				0,
+				0,
+				RESOLUTION_WIDTH as GLsizei,
+				RESOLUTION_HEIGHT as GLsizei,
+				gl::RGBA,
+				gl::UNSIGNED_BYTE,
+				pixels.as_ptr() as *const c_void,
+			);
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+			self.viewport_prog.bind();
+			self.viewport_quad.draw();
+
+			gl::Finish();
+			gl_err_check();
+		}
+	}
 }
@@ -52,6 +52,16 @@ impl Texture {
 
 		textures
 	}
+
+	/// Frees all the textures created by [`Texture::new_bulk`].
+	pub fn delete_bulk(textures: &[Self; MAX_IMAGES]) {
+		let texture_ids = textures.map(|t| t.texture_handle);
+		unsafe {
+			gl::DeleteTextures(MAX_IMAGES as GLsizei, texture_ids.as_ptr());
+			gl_err_check();
+		}
+	}
+
 	pub fn bind(&self) {
 		unsafe {
 			gl::BindTexture(gl::TEXTURE_2D, self.texture_handle);
@@ -0,0 +1,132 @@
+//! Persistent save data (SRAM).
+//! Every ROM gets a fixed-size block, stored under the XDG data directory and keyed by the ROM's
+//! identity. The block is written to disk atomically whenever the ROM changes it.
+
+use avk_types::SAVE_SIZE;
+use log::{debug, error};
+use std::cell::RefCell;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+thread_local! {
+	/// Identifies the running ROM, so each one gets its own save file.
+	static IDENTITY: RefCell<String> = RefCell::new(String::from("unknown"));
+}
+
+/// Sets the identity used to find the save file of the ROM about to run.
+pub fn set_identity(identity: impl Into<String>) {
+	IDENTITY.set(identity.into());
+}
+
+/// Returns `$XDG_DATA_HOME/avk/saves`, falling back to `~/.local/share/avk/saves`.
+fn save_dir() -> PathBuf {
+	let data_home = env::var_os("XDG_DATA_HOME")
+		.filter(|dir| !dir.is_empty())
+		.map(PathBuf::from)
+		.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+		.unwrap_or_default();
+	data_home.join("avk").join("saves")
+}
+
+pub struct SaveData {
+	/// None for a scratch block, which is never written to disk.
+	path: Option<PathBuf>,
+	data: Box<[u8; SAVE_SIZE]>,
+	dirty: bool,
+}
+
+impl SaveData {
+	/// Loads the save block of the current ROM, or an empty one if it has never saved.
+	pub fn load() -> Self {
+		let identity = IDENTITY.with_borrow(|identity| identity.clone());
+		let path = save_dir().join(identity + ".sav");
+		let mut data = Box::new([0; SAVE_SIZE]);
+
+		match fs::read(&path) {
+			Ok(bytes) => {
+				let len = bytes.len().min(SAVE_SIZE);
+				data[..len].copy_from_slice(&bytes[..len]);
+				debug!("Loaded save data from {}", path.display());
+			}
+			Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+			Err(err) => error!("Failed to read save data from {}: {err}", path.display()),
+		}
+
+		Self {
+			path: Some(path),
+			data,
+			dirty: false,
+		}
+	}
+
+	/// Returns a save block that starts out as `data` and is never written to disk, e.g. for
+	/// playing a movie back.
+	pub fn scratch(data: [u8; SAVE_SIZE]) -> Self {
+		Self {
+			path: None,
+			data: Box::new(data),
+			dirty: false,
+		}
+	}
+
+	/// Copies from the save block into `buf`, starting `offset` bytes in.
+	/// Returns the number of bytes read.
+	pub fn read(&self, offset: usize, buf: &mut [u8]) -> usize {
+		let Some(src) = self.data.get(offset..) else {
+			return 0;
+		};
+		let len = buf.len().min(src.len());
+		buf[..len].copy_from_slice(&src[..len]);
+		len
+	}
+
+	/// Copies `data` into the save block, starting `offset` bytes in, and flushes it to disk.
+	/// Returns the number of bytes written.
+	pub fn write(&mut self, offset: usize, data: &[u8]) -> usize {
+		let Some(dst) = self.data.get_mut(offset..) else {
+			return 0;
+		};
+		let len = data.len().min(dst.len());
+		if dst[..len] != data[..len] {
+			dst[..len].copy_from_slice(&data[..len]);
+			self.dirty = true;
+			self.flush();
+		}
+		len
+	}
+
+	/// Writes the save block to disk if it has changed.
+	/// A temporary file is renamed over the old one, so a crash never leaves a torn save behind.
+	pub fn flush(&mut self) {
+		let Some(path) = &self.path else {
+			return;
+		};
+		if !self.dirty {
+			return;
+		}
+
+		let tmp_path = path.with_extension("sav.tmp");
+		let result = (|| {
+			if let Some(dir) = path.parent() {
+				fs::create_dir_all(dir)?;
+			}
+			let mut file = File::create(&tmp_path)?;
+			file.write_all(self.data.as_slice())?;
+			file.sync_all()?;
+			fs::rename(&tmp_path, path)
+		})();
+
+		match result {
+			Ok(()) => self.dirty = false,
+			Err(err) => error!("Failed to write save data to {}: {err}", path.display()),
+		}
+	}
+}
+
+impl Drop for SaveData {
+	fn drop(&mut self) {
+		self.flush();
+	}
+}
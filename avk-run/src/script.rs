@@ -0,0 +1,311 @@
+//! Virtual controllers driven by a script, for `--input-script` and `--input-socket`.
+//! Every line presses or releases an input of a player, on a frame counted from the ROM's first
+//! one, or as soon as it's read if the frame is left out:
+//! ```text
+//! # [frame <n>:] <player> <input> press|release
+//! frame 120: alpha face_down press
+//! frame 130: Alpha FaceDown release
+//! ```
+//! Players and inputs are named like in the bindings file, the underscores and case don't
+//! matter. The scripted inputs are held alongside the keyboard's and the gamepads', headless too.
+//! A socket takes lines from any number of programs connecting to it, so tests can react to what
+//! the ROM does instead of following a timeline.
+
+use crate::sdl::bindings;
+use avk_types::prelude::*;
+use log::{info, warn};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub enum Config {
+	File(PathBuf),
+	Socket(PathBuf),
+}
+
+thread_local! {
+	static CONFIG: RefCell<Option<Config>> = const { RefCell::new(None) };
+}
+
+/// Makes consoles created from now on follow a script.
+pub fn enable(config: Config) {
+	CONFIG.set(Some(config));
+}
+
+/// A line of a script.
+struct Event {
+	/// None to happen right away.
+	frame: Option<u64>,
+	player: Player,
+	input: AvkGamepadInput,
+	down: bool,
+}
+
+/// Returns None for blank and comment lines.
+fn parse_line(line: &str) -> Result<Option<Event>, String> {
+	let line = line.split('#').next().unwrap().trim();
+	if line.is_empty() {
+		return Ok(None);
+	}
+	let (frame, action) = match line.split_once(':') {
+		Some((frame, action)) => {
+			let frame = frame
+				.trim()
+				.strip_prefix("frame")
+				.and_then(|frame| frame.trim().parse().ok())
+				.ok_or_else(|| format!("expected `frame <n>:`, found `{frame}:`"))?;
+			(Some(frame), action)
+		}
+		None => (None, line),
+	};
+	let [player, input, down] = action.split_whitespace().collect::<Vec<_>>()[..] else {
+		return Err(String::from("expected `<player> <input> press|release`"));
+	};
+	let player = Player::ALL
+		.into_iter()
+		.find(|&p| same_name(bindings::player_name(p), player))
+		.ok_or_else(|| format!("unknown player `{player}`"))?;
+	let input = AvkGamepadInput::ALL
+		.into_iter()
+		.find(|&i| same_name(bindings::input_name(i), input))
+		.ok_or_else(|| format!("unknown input `{input}`"))?;
+	let down = match down {
+		"press" => true,
+		"release" => false,
+		_ => return Err(format!("expected press or release, found `{down}`")),
+	};
+	Ok(Some(Event {
+		frame,
+		player,
+		input,
+		down,
+	}))
+}
+
+/// Compares a name from the bindings file with one from a script, so `face_down` can also be
+/// written `FaceDown`.
+fn same_name(name: &str, script_name: &str) -> bool {
+	let script_name = script_name.chars().filter(|&c| c != '_');
+	name.chars()
+		.filter(|&c| c != '_')
+		.eq(script_name.map(|c| c.to_ascii_lowercase()))
+}
+
+/// A program sending lines through the socket.
+struct Client {
+	stream: UnixStream,
+	/// What it sent of the line it's in the middle of.
+	line: Vec<u8>,
+}
+
+struct Socket {
+	listener: UnixListener,
+	path: PathBuf,
+	clients: Vec<Client>,
+}
+
+impl Socket {
+	fn bind(path: PathBuf) -> Self {
+		// a socket left behind by a previous run would keep the bind from succeeding
+		let stale = fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket());
+		if stale {
+			let _ = fs::remove_file(&path);
+		}
+		let listener = UnixListener::bind(&path)
+			.and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+			.unwrap_or_else(|err| panic!("Failed to listen on {}: {err}", path.display()));
+		info!("Listening for input scripts on {}", path.display());
+		Self {
+			listener,
+			path,
+			clients: Vec::new(),
+		}
+	}
+
+	/// Returns the lines sent since the last call, without waiting for more.
+	fn read_lines(&mut self) -> Vec<String> {
+		loop {
+			match self.listener.accept() {
+				Ok((stream, _)) => {
+					if let Err(err) = stream.set_nonblocking(true) {
+						warn!("Failed to accept an input script: {err}");
+						continue;
+					}
+					self.clients.push(Client {
+						stream,
+						line: Vec::new(),
+					});
+				}
+				Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+				Err(err) => {
+					warn!("Failed to accept an input script: {err}");
+					break;
+				}
+			}
+		}
+
+		let mut lines = Vec::new();
+		self.clients.retain_mut(|client| {
+			let mut buf = [0; 1024];
+			let connected = loop {
+				match client.stream.read(&mut buf) {
+					Ok(0) => break false,
+					Ok(len) => client.line.extend_from_slice(&buf[..len]),
+					Err(err) if err.kind() == ErrorKind::WouldBlock => break true,
+					Err(err) if err.kind() == ErrorKind::Interrupted => {}
+					Err(err) => {
+						warn!("Failed to read an input script: {err}");
+						break false;
+					}
+				}
+			};
+			while let Some(end) = client.line.iter().position(|&b| b == b'\n') {
+				let line = client.line.drain(..=end).collect::<Vec<_>>();
+				lines.push(String::from_utf8_lossy(&line).into_owned());
+			}
+			// a last line without a newline still counts once the program hangs up
+			if !connected && !client.line.is_empty() {
+				lines.push(String::from_utf8_lossy(&client.line).into_owned());
+			}
+			connected
+		});
+		lines
+	}
+}
+
+impl Drop for Socket {
+	fn drop(&mut self) {
+		let _ = fs::remove_file(&self.path);
+	}
+}
+
+pub struct Script {
+	/// The inputs the script holds down.
+	pub state: [HashMap<AvkGamepadInput, bool>; 4],
+	/// Lines waiting for their frame, in the order they were read.
+	pending: Vec<Event>,
+	socket: Option<Socket>,
+}
+
+impl Script {
+	/// Returns None if no script is being followed.
+	pub fn new() -> Option<Self> {
+		let config = CONFIG.with_borrow(Clone::clone)?;
+		let mut script = Self {
+			state: Default::default(),
+			pending: Vec::new(),
+			socket: None,
+		};
+		match config {
+			Config::File(path) => {
+				let text = fs::read_to_string(&path)
+					.unwrap_or_else(|err| panic!("Failed to read {}: {err}", path.display()));
+				// a test script with a typo would test something else, so it isn't run at all
+				for (number, line) in text.lines().enumerate() {
+					match parse_line(line) {
+						Ok(event) => script.pending.extend(event),
+						Err(message) => panic!("{}:{}: {message}", path.display(), number + 1),
+					}
+				}
+				info!("Following the input script {}", path.display());
+			}
+			Config::Socket(path) => script.socket = Some(Socket::bind(path)),
+		}
+		Some(script)
+	}
+
+	/// Moves the inputs on to `frame`, pressing and releasing what the script says should be by
+	/// then.
+	pub fn update(&mut self, frame: u64) {
+		if let Some(socket) = &mut self.socket {
+			for line in socket.read_lines() {
+				match parse_line(&line) {
+					Ok(event) => self.pending.extend(event),
+					Err(message) => warn!("Ignored input script line `{}`: {message}", line.trim()),
+				}
+			}
+		}
+		self.pending.retain(|event| {
+			if event.frame.is_some_and(|at| at > frame) {
+				return true;
+			}
+			self.state[event.player.index()].insert(event.input, event.down);
+			false
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(line: &str) -> Event {
+		parse_line(line).unwrap().unwrap()
+	}
+
+	#[test]
+	fn parses_lines() {
+		let event = parse("frame 120: alpha face_down press");
+		assert_eq!(event.frame, Some(120));
+		assert_eq!(event.player.index(), Player::Alpha.index());
+		assert_eq!(event.input, AvkGamepadInput::FaceDown);
+		assert!(event.down);
+
+		let event = parse("  Delta   TriggerRight release  # let go");
+		assert_eq!(event.frame, None);
+		assert_eq!(event.player.index(), Player::Delta.index());
+		assert_eq!(event.input, AvkGamepadInput::TriggerRight);
+		assert!(!event.down);
+
+		assert_eq!(parse("frame0:bravo MENU press").frame, Some(0));
+	}
+
+	#[test]
+	fn skips_blank_and_comment_lines() {
+		for line in ["", "   ", "# frame 1: alpha menu press", "  # indented"] {
+			assert!(parse_line(line).unwrap().is_none(), "{line:?}");
+		}
+	}
+
+	#[test]
+	fn rejects_bad_lines() {
+		for (line, error) in [
+			(
+				"frame x: alpha menu press",
+				"expected `frame <n>:`, found `frame x:`",
+			),
+			(
+				"tick 3: alpha menu press",
+				"expected `frame <n>:`, found `tick 3:`",
+			),
+			(
+				"frame -1: alpha menu press",
+				"expected `frame <n>:`, found `frame -1:`",
+			),
+			("alpha menu", "expected `<player> <input> press|release`"),
+			(
+				"alpha menu press now",
+				"expected `<player> <input> press|release`",
+			),
+			("echo menu press", "unknown player `echo`"),
+			("alpha start press", "unknown input `start`"),
+			("alpha menu hold", "expected press or release, found `hold`"),
+		] {
+			assert_eq!(parse_line(line).err().as_deref(), Some(error), "{line}");
+		}
+	}
+
+	#[test]
+	fn matches_names_loosely() {
+		assert!(same_name("face_down", "FaceDown"));
+		assert!(same_name("face_down", "face_down"));
+		assert!(same_name("face_down", "FACE_DOWN"));
+		assert!(!same_name("face_down", "face_up"));
+		assert!(!same_name("face_down", "face_downs"));
+	}
+}
@@ -2,15 +2,26 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
+pub mod audio;
+pub mod bindings;
+pub mod gamepads;
+pub mod music;
+pub mod pointer;
+pub mod text_input;
+pub mod voices;
 pub mod window;
 
-mod audio;
 mod sys;
 
+use crate::sdl::audio::AudioOutput;
+use crate::sdl::bindings::{Bindings, Button};
+use crate::sdl::gamepads::Gamepads;
+use crate::sdl::pointer::Pointer;
 use crate::sdl::sys::*;
+use crate::sdl::text_input::TextInput;
 use crate::sdl::window::Window;
-use avk_types::{AvkGamepadInput, Player};
-use log::{debug, warn};
+use avk_types::{AvkGamepadAxis, AvkGamepadInput, Player};
+use log::info;
 use std::array::from_fn;
 use std::collections::HashMap;
 use std::ffi::*;
@@ -28,6 +39,9 @@ pub(super) enum Keycode {
 	Space,
 	Comma,
 	Period,
+	Minus,
+	Slash,
+	Semicolon,
 	Num0,
 	Num1,
 	Num2,
@@ -85,6 +99,30 @@ pub(super) enum Keycode {
 	PageUp,
 	End,
 	PageDown,
+	Up,
+	Right,
+	Down,
+	Left,
+	Kp0,
+	Kp1,
+	Kp2,
+	Kp3,
+	Kp4,
+	Kp5,
+	Kp6,
+	Kp7,
+	Kp8,
+	Kp9,
+	KpEnter,
+	KpPlus,
+	KpMinus,
+	KpPeriod,
+	LeftShift,
+	RightShift,
+	LeftCtrl,
+	RightCtrl,
+	LeftAlt,
+	RightAlt,
 }
 
 /// Converts an SDL Keycode to the binding enumerated Keycode type.
@@ -97,6 +135,9 @@ pub(super) fn sdl_keycode_to_keycode(value: SDL_Keycode) -> Keycode {
 		SDLK_SPACE => Keycode::Space,
 		SDLK_COMMA => Keycode::Comma,
 		SDLK_PERIOD => Keycode::Period,
+		SDLK_MINUS => Keycode::Minus,
+		SDLK_SLASH => Keycode::Slash,
+		SDLK_SEMICOLON => Keycode::Semicolon,
 		SDLK_0 => Keycode::Num0,
 		SDLK_1 => Keycode::Num1,
 		SDLK_2 => Keycode::Num2,
@@ -133,24 +174,70 @@ pub(super) fn sdl_keycode_to_keycode(value: SDL_Keycode) -> Keycode {
 		SDLK_X => Keycode::X,
 		SDLK_Y => Keycode::Y,
 		SDLK_Z => Keycode::Z,
+		SDLK_DELETE => Keycode::Delete,
+		SDLK_F1 => Keycode::F1,
+		SDLK_F2 => Keycode::F2,
+		SDLK_F3 => Keycode::F3,
+		SDLK_F4 => Keycode::F4,
+		SDLK_F5 => Keycode::F5,
+		SDLK_F6 => Keycode::F6,
+		SDLK_F7 => Keycode::F7,
+		SDLK_F8 => Keycode::F8,
+		SDLK_F9 => Keycode::F9,
+		SDLK_F10 => Keycode::F10,
+		SDLK_F11 => Keycode::F11,
+		SDLK_F12 => Keycode::F12,
 		SDLK_PRINTSCREEN => Keycode::PrintScreen,
+		SDLK_SCROLLLOCK => Keycode::ScrollLock,
 		SDLK_PAUSE => Keycode::PauseBreak,
+		SDLK_INSERT => Keycode::Insert,
+		SDLK_HOME => Keycode::Home,
+		SDLK_PAGEUP => Keycode::PageUp,
+		SDLK_END => Keycode::End,
+		SDLK_PAGEDOWN => Keycode::PageDown,
+		SDLK_UP => Keycode::Up,
+		SDLK_RIGHT => Keycode::Right,
+		SDLK_DOWN => Keycode::Down,
+		SDLK_LEFT => Keycode::Left,
+		SDLK_KP_0 => Keycode::Kp0,
+		SDLK_KP_1 => Keycode::Kp1,
+		SDLK_KP_2 => Keycode::Kp2,
+		SDLK_KP_3 => Keycode::Kp3,
+		SDLK_KP_4 => Keycode::Kp4,
+		SDLK_KP_5 => Keycode::Kp5,
+		SDLK_KP_6 => Keycode::Kp6,
+		SDLK_KP_7 => Keycode::Kp7,
+		SDLK_KP_8 => Keycode::Kp8,
+		SDLK_KP_9 => Keycode::Kp9,
+		SDLK_KP_ENTER => Keycode::KpEnter,
+		SDLK_KP_PLUS => Keycode::KpPlus,
+		SDLK_KP_MINUS => Keycode::KpMinus,
+		SDLK_KP_PERIOD => Keycode::KpPeriod,
+		SDLK_LSHIFT => Keycode::LeftShift,
+		SDLK_RSHIFT => Keycode::RightShift,
+		SDLK_LCTRL => Keycode::LeftCtrl,
+		SDLK_RCTRL => Keycode::RightCtrl,
+		SDLK_LALT => Keycode::LeftAlt,
+		SDLK_RALT => Keycode::RightAlt,
 		_ => Keycode::Unknown,
 	}
 }
 
 /// Panics and prints the contents of SDL_GetError to the console.
 unsafe fn panic_sdl_error(format_string: &str) -> ! {
-	let err = SDL_GetError();
-	panic!(
-		"{} {}",
-		format_string,
-		if err == null() {
-			"No further information."
-		} else {
-			CStr::from_ptr(err as *mut c_char).to_str().unwrap()
-		}
-	);
+	panic!("{} {}", format_string, sdl_error());
+}
+
+/// Returns the contents of SDL_GetError.
+fn sdl_error() -> String {
+	let err = unsafe { SDL_GetError() };
+	if err == null() {
+		"No further information.".into()
+	} else {
+		unsafe { CStr::from_ptr(err as *mut c_char) }
+			.to_string_lossy()
+			.into_owned()
+	}
 }
 
 enum SdlProperty<'a> {
@@ -185,39 +272,87 @@ unsafe fn set_sdl_prop(props: SDL_PropertiesID, key: &[u8], value: SdlProperty)
 	}
 }
 
-unsafe fn process_gamepad_button(event: &SDL_Event) -> Option<AvkGamepadInput> {
-	match event.gbutton.button as c_int {
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_NORTH => Some(AvkGamepadInput::FaceUp),
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_EAST => Some(AvkGamepadInput::FaceRight),
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_SOUTH => Some(AvkGamepadInput::FaceDown),
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_WEST => Some(AvkGamepadInput::FaceLeft),
-
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_DPAD_UP => Some(AvkGamepadInput::DirUp),
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_DPAD_RIGHT => Some(AvkGamepadInput::DirRight),
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_DPAD_DOWN => Some(AvkGamepadInput::DirDown),
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_DPAD_LEFT => Some(AvkGamepadInput::DirLeft),
-
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_LEFT_SHOULDER => Some(AvkGamepadInput::TriggerLeft),
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_RIGHT_SHOULDER => Some(AvkGamepadInput::TriggerRight),
-
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_BACK => Some(AvkGamepadInput::Menu),
-		SDL_GamepadButton_SDL_GAMEPAD_BUTTON_START => Some(AvkGamepadInput::Menu),
+unsafe fn process_gamepad_axis(event: &SDL_Event) -> Option<AvkGamepadAxis> {
+	match event.gaxis.axis as c_int {
+		SDL_GamepadAxis_SDL_GAMEPAD_AXIS_LEFTX => Some(AvkGamepadAxis::LeftX),
+		SDL_GamepadAxis_SDL_GAMEPAD_AXIS_LEFTY => Some(AvkGamepadAxis::LeftY),
+		SDL_GamepadAxis_SDL_GAMEPAD_AXIS_RIGHTX => Some(AvkGamepadAxis::RightX),
+		SDL_GamepadAxis_SDL_GAMEPAD_AXIS_RIGHTY => Some(AvkGamepadAxis::RightY),
+		SDL_GamepadAxis_SDL_GAMEPAD_AXIS_LEFT_TRIGGER => Some(AvkGamepadAxis::LeftTrigger),
+		SDL_GamepadAxis_SDL_GAMEPAD_AXIS_RIGHT_TRIGGER => Some(AvkGamepadAxis::RightTrigger),
 
 		_ => None,
 	}
 }
 
+/// Shows a dialog explaining that the ROM crashed.
+/// Returns true if the user chose to restart it.
+pub fn ask_restart(window: Option<&Window>, message: &str) -> bool {
+	let message = CString::new(message.replace('\0', "")).unwrap();
+	let buttons = [
+		SDL_MessageBoxButtonData {
+			flags: SDL_MESSAGEBOX_BUTTON_ESCAPEKEY_DEFAULT,
+			buttonID: 0,
+			text: c"Quit".as_ptr(),
+		},
+		SDL_MessageBoxButtonData {
+			flags: SDL_MESSAGEBOX_BUTTON_RETURNKEY_DEFAULT,
+			buttonID: 1,
+			text: c"Restart".as_ptr(),
+		},
+	];
+	let data = SDL_MessageBoxData {
+		flags: SDL_MESSAGEBOX_ERROR,
+		window: window.map_or(null_mut(), |window| window.sdl_window),
+		title: c"AVK".as_ptr(),
+		message: message.as_ptr(),
+		numbuttons: buttons.len() as c_int,
+		buttons: buttons.as_ptr(),
+		colorScheme: null(),
+	};
+
+	let mut button_id = 0;
+	unsafe { SDL_ShowMessageBox(&data, &mut button_id) && button_id == 1 }
+}
+
+/// Hotkeys handled by the runner itself instead of being passed on to the ROM.
+#[derive(Default, Copy, Clone)]
+pub struct RunnerControls {
+	/// F1 was pressed, toggling pause.
+	pub pause: bool,
+	/// F2 was pressed, running a single frame while paused.
+	pub step: bool,
+	/// F3 is held down.
+	pub fast_forward: bool,
+	/// Escape was pressed.
+	pub quit: bool,
+}
+
 pub struct SdlManager {
 	pub window: Window,
-	gamepads: Vec<(SDL_JoystickID, *mut SDL_Gamepad)>,
+	/// None if there's no audio device, in which case the ROM runs silently.
+	pub audio: Option<AudioOutput>,
+	controls: RunnerControls,
+	/// Set while the runner drives the main loop and can pause it. Otherwise F1 to F3 are left
+	/// to the ROM's bindings, and only Escape is a hotkey.
+	runner_controls: bool,
+	gamepads: Gamepads,
 	// TODO: fix joystick support by tracking previous state... grumble grumble
 	pub action_state_gp: [HashMap<AvkGamepadInput, bool>; 4],
 	pub action_state_kb: [HashMap<AvkGamepadInput, bool>; 4],
+	/// Raw positions of the analog controls, the backend takes the deadzones out.
+	pub axis_state_gp: [[i16; AvkGamepadAxis::COUNT]; 4],
+	pub pointer: Pointer,
+	pub text_input: TextInput,
+	bindings: Bindings,
+	/// The player whose inputs are being rebound, and the index of the next one.
+	rebinding: Option<(Player, usize)>,
 }
 
 impl<'a> SdlManager {
 	pub fn new(
 		app_name: impl Into<String>,
+		app_creator: impl Into<String>,
 		app_version: impl Into<String>,
 		app_identifier: impl Into<String>,
 	) -> Self {
@@ -238,6 +373,15 @@ impl<'a> SdlManager {
 					app_version.as_ptr() as *const c_char,
 					app_identifier.as_ptr() as *const c_char,
 				);
+
+				let app_creator = app_creator.into();
+				if !app_creator.is_empty() {
+					let app_creator = app_creator + "\0";
+					SDL_SetAppMetadataProperty(
+						SDL_PROP_APP_METADATA_CREATOR_STRING.as_ptr() as *const c_char,
+						app_creator.as_ptr() as *const c_char,
+					);
+				}
 			}
 
 			if !SDL_Init(SDL_INIT_VIDEO | SDL_INIT_AUDIO | SDL_INIT_GAMEPAD) {
@@ -254,12 +398,21 @@ impl<'a> SdlManager {
 			SDL_GL_SetAttribute(SDL_GLAttr_SDL_GL_DOUBLEBUFFER, 0);
 
 			let window = Window::init(width, height);
+			let audio = AudioOutput::open();
 
 			Self {
 				window,
-				gamepads: Vec::new(),
+				audio,
+				controls: RunnerControls::default(),
+				runner_controls: false,
+				gamepads: Gamepads::new(),
 				action_state_gp: from_fn(|_| HashMap::with_capacity(4)),
 				action_state_kb: from_fn(|_| HashMap::with_capacity(4)),
+				axis_state_gp: [[0; AvkGamepadAxis::COUNT]; 4],
+				pointer: Pointer::default(),
+				text_input: TextInput::new(),
+				bindings: Bindings::load(),
+				rebinding: None,
 			}
 		}
 	}
@@ -276,98 +429,168 @@ impl<'a> SdlManager {
 		res
 	}
 
-	/// Updates the
+	/// Returns the runner hotkeys pressed since the last call.
+	pub fn take_controls(&mut self) -> RunnerControls {
+		let controls = self.controls;
+		self.controls.pause = false;
+		self.controls.step = false;
+		self.controls.quit = false;
+		controls
+	}
+
+	/// Makes F1 to F3 pause, step and fast-forward the ROM, for main loops that can.
+	pub fn set_runner_controls(&mut self, enabled: bool) {
+		self.runner_controls = enabled;
+		self.controls = RunnerControls::default();
+	}
+
+	pub fn has_runner_controls(&self) -> bool {
+		self.runner_controls
+	}
+
+	/// Updates the runner hotkeys and the keyboard's inputs.
 	fn keyboard_update(&mut self, event: SDL_KeyboardEvent) {
-		let player: Player;
-		let button: AvkGamepadInput;
-		match sdl_keycode_to_keycode(event.key) {
-			Keycode::W => {
-				player = Player::Alpha;
-				button = AvkGamepadInput::DirUp
-			}
-			Keycode::A => {
-				player = Player::Alpha;
-				button = AvkGamepadInput::DirLeft
-			}
-			Keycode::S => {
-				player = Player::Alpha;
-				button = AvkGamepadInput::DirDown
+		let key = sdl_keycode_to_keycode(event.key);
+		let rebind_player = match key {
+			Keycode::F1 if self.runner_controls => {
+				self.controls.pause |= event.down && !event.repeat;
+				return;
 			}
-			Keycode::D => {
-				player = Player::Alpha;
-				button = AvkGamepadInput::DirRight
+			Keycode::F2 if self.runner_controls => {
+				self.controls.step |= event.down;
+				return;
 			}
-			Keycode::Q => {
-				player = Player::Alpha;
-				button = AvkGamepadInput::TriggerLeft
+			Keycode::F3 if self.runner_controls => {
+				self.controls.fast_forward = event.down;
+				return;
 			}
-			Keycode::E => {
-				player = Player::Alpha;
-				button = AvkGamepadInput::TriggerRight
-			}
-			Keycode::Z => {
-				player = Player::Alpha;
-				button = AvkGamepadInput::FaceLeft
-			}
-			Keycode::C => {
-				player = Player::Alpha;
-				button = AvkGamepadInput::FaceRight
+			Keycode::Escape if self.rebinding.is_some() => {
+				if event.down && !event.repeat {
+					self.rebinding = None;
+					info!("Cancelled rebinding");
+				}
+				return;
 			}
-			Keycode::X => {
-				player = Player::Alpha;
-				button = AvkGamepadInput::FaceUp
+			Keycode::Escape => {
+				self.controls.quit |= event.down && !event.repeat;
+				return;
 			}
-			Keycode::V => {
-				player = Player::Alpha;
-				button = AvkGamepadInput::FaceDown
+			Keycode::F5 => Player::Alpha,
+			Keycode::F6 => Player::Bravo,
+			Keycode::F7 => Player::Charlie,
+			Keycode::F8 => Player::Delta,
+
+			_ => {
+				if self.text_input.is_enabled() && self.rebinding.is_none() {
+					// the keys type instead, repeating like they do in text fields
+					if event.down {
+						self.text_input.push_key(key);
+					}
+					return;
+				}
+				if event.repeat {
+					return;
+				}
+				if let Some((player, input)) = self.rebinding_input() {
+					if event.down && self.bindings.bind_key(key, player, input) {
+						self.next_rebinding();
+					}
+				} else if let Some((player, input)) = self.bindings.key(key) {
+					self.action_state_kb[player.index()].insert(input, event.down);
+				}
+				return;
 			}
+		};
+		if event.down && !event.repeat {
+			// the inputs held now would never see their keys and buttons go up
+			self.action_state_kb.iter_mut().for_each(HashMap::clear);
+			self.action_state_gp.iter_mut().for_each(HashMap::clear);
+			self.rebinding = Some((rebind_player, 0));
+			self.prompt_rebinding();
+		}
+	}
 
-			Keycode::I => {
-				player = Player::Bravo;
-				button = AvkGamepadInput::DirUp
-			}
-			Keycode::J => {
-				player = Player::Bravo;
-				button = AvkGamepadInput::DirLeft
-			}
-			Keycode::K => {
-				player = Player::Bravo;
-				button = AvkGamepadInput::DirDown
-			}
-			Keycode::L => {
-				player = Player::Bravo;
-				button = AvkGamepadInput::DirRight
-			}
-			Keycode::U => {
-				player = Player::Bravo;
-				button = AvkGamepadInput::TriggerLeft
-			}
-			Keycode::O => {
-				player = Player::Bravo;
-				button = AvkGamepadInput::TriggerRight
-			}
-			Keycode::N => {
-				player = Player::Bravo;
-				button = AvkGamepadInput::FaceLeft
+	/// Handles a button of a gamepad, which joins as a player on its first press.
+	fn gamepad_button_update(&mut self, id: SDL_JoystickID, button: c_int, down: bool) {
+		let Some(button) = Button::from_sdl(button) else {
+			return;
+		};
+		if let Some((rebound, input)) = self.rebinding_input() {
+			// any gamepad will do, the buttons are bound for the player being rebound
+			if down {
+				self.bindings.bind_button(rebound, button, input);
+				self.next_rebinding();
 			}
-			Keycode::M => {
-				player = Player::Bravo;
-				button = AvkGamepadInput::FaceRight
+			return;
+		}
+		// the on-screen keyboard takes every gamepad over while it's needed
+		if self.text_input.is_enabled() {
+			if down {
+				self.text_input.press_button(button);
 			}
-			Keycode::Comma => {
-				player = Player::Bravo;
-				button = AvkGamepadInput::FaceUp
+			return;
+		}
+		match self.gamepads.player(id) {
+			Some(player) => {
+				if let Some(input) = self.bindings.button(player, button) {
+					self.action_state_gp[player.index()].insert(input, down);
+				}
 			}
-			Keycode::Period => {
-				player = Player::Bravo;
-				button = AvkGamepadInput::FaceDown
+			// the press that joins doesn't count as an input
+			None if down => {
+				self.gamepads.join(id);
 			}
-			_ => return,
+			None => {}
+		}
+	}
+
+	/// Returns true if a player has a gamepad plugged in.
+	pub fn is_connected(&self, player: Player) -> bool {
+		self.gamepads.is_connected(player)
+	}
+
+	pub fn rumble(&mut self, player: Player, low: u16, high: u16, duration_ms: u32) {
+		self.gamepads.rumble(player, low, high, duration_ms);
+	}
+
+	/// Turns text input on or off for the ROM.
+	pub fn set_text_input(&mut self, enabled: bool) {
+		if enabled && !self.text_input.is_enabled() {
+			// the inputs held now would never see their keys and buttons go up
+			self.action_state_kb.iter_mut().for_each(HashMap::clear);
+			self.action_state_gp.iter_mut().for_each(HashMap::clear);
+		}
+		self.text_input.set_enabled(&self.window, enabled);
+	}
+
+	/// Returns the input waiting for a new binding, if the player is rebinding their inputs.
+	fn rebinding_input(&self) -> Option<(Player, AvkGamepadInput)> {
+		let (player, next) = self.rebinding?;
+		Some((player, AvkGamepadInput::ALL[next]))
+	}
+
+	/// Moves on to the next input to rebind, or saves the bindings after the last one.
+	fn next_rebinding(&mut self) {
+		let Some((player, next)) = &mut self.rebinding else {
+			return;
+		};
+		*next += 1;
+		if *next == AvkGamepadInput::COUNT {
+			info!("Rebound {}", bindings::player_name(*player));
+			self.rebinding = None;
+			self.bindings.save();
+		} else {
+			self.prompt_rebinding();
 		}
+	}
 
-		if !event.repeat {
-			// println!("player {player:?} button {button:?} state {}", event.down);
-			self.action_state_kb[player.index()].insert(button, event.down);
+	fn prompt_rebinding(&self) {
+		if let Some((player, input)) = self.rebinding_input() {
+			info!(
+				"Press a key or gamepad button for {} {}, or Escape to cancel",
+				bindings::player_name(player),
+				bindings::input_name(input),
+			);
 		}
 	}
 
@@ -400,144 +623,62 @@ impl<'a> SdlManager {
 							self.keyboard_update(event.key)
 						}
 
+						SDL_EventType_SDL_EVENT_TEXT_INPUT => {
+							let text = CStr::from_ptr(event.text.text);
+							self.text_input.push_text(&text.to_string_lossy());
+						}
+
 						SDL_EventType_SDL_EVENT_GAMEPAD_REMOVED => {
-							let gamepad = self
-								.gamepads
-								.iter()
-								.position(|(j_id, _gamepad)| *j_id == event.gdevice.which);
-							if let Some(index) = gamepad {
-								let (_joy_id, gamepad) = self.gamepads[index];
-
-								{
-									let name = SDL_GetGamepadName(gamepad) as *mut c_char;
-									if name.is_null() {
-										debug!(
-											"Gamepad disconnected (@ {:?})",
-											SDL_GetGamepadPlayerIndex(gamepad),
-										);
-									} else {
-										debug!(
-											"Gamepad disconnected ({:?} @ {:?})",
-											CString::from_raw(name),
-											SDL_GetGamepadPlayerIndex(gamepad),
-										);
-									};
-								}
-
-								SDL_CloseGamepad(gamepad);
-								self.gamepads.remove(index);
+							if let Some(player) = self.gamepads.remove(event.gdevice.which) {
+								// nothing would release what it was holding
+								self.action_state_gp[player.index()].clear();
+								self.axis_state_gp[player.index()] = [0; AvkGamepadAxis::COUNT];
 							}
 						}
 
 						SDL_EventType_SDL_EVENT_GAMEPAD_ADDED => {
-							let gamepad = SDL_OpenGamepad(event.gdevice.which);
-							if gamepad.is_null() {
-								panic!("Failed to open gamepad!");
-							}
-							self.gamepads.push((event.gdevice.which, gamepad));
-
-							{
-								let name = SDL_GetGamepadName(gamepad) as *mut c_char;
-								if name.is_null() {
-									debug!(
-										"Gamepad connected (@ {:?})",
-										SDL_GetGamepadPlayerIndex(gamepad),
-									);
-								} else {
-									debug!(
-										"Gamepad connected ({:?} @ {:?})",
-										CString::from_raw(name),
-										SDL_GetGamepadPlayerIndex(gamepad),
-									);
-								};
-							}
+							self.gamepads.add(event.gdevice.which);
 						}
 
 						SDL_EventType_SDL_EVENT_GAMEPAD_AXIS_MOTION => {
 							let g_axis = event.gaxis;
-							let pos = self
-								.gamepads
-								.iter()
-								.position(|e| g_axis.which == e.0)
-								.expect("Received a gamepad event from an invalid gamepad!");
-							if pos > 3 {
-								warn!("Input from extra gamepad discarded!");
-							} else {
-								let hm = self
-									.action_state_gp
-									.get_mut(pos)
-									.expect("Failed to get hashmap!");
-								match g_axis.axis as c_int {
-									SDL_GamepadAxis_SDL_GAMEPAD_AXIS_LEFTX => {
-										if g_axis.value > i16::MAX / 4 {
-											hm.insert(AvkGamepadInput::DirRight, true);
-											hm.insert(AvkGamepadInput::DirLeft, false);
-										} else if g_axis.value < i16::MIN / 4 {
-											hm.insert(AvkGamepadInput::DirRight, false);
-											hm.insert(AvkGamepadInput::DirLeft, true);
-										} else {
-											hm.insert(AvkGamepadInput::DirRight, false);
-											hm.insert(AvkGamepadInput::DirLeft, false);
-										}
-									}
-									SDL_GamepadAxis_SDL_GAMEPAD_AXIS_LEFTY => {
-										if g_axis.value > i16::MAX / 4 {
-											hm.insert(AvkGamepadInput::DirUp, false);
-											hm.insert(AvkGamepadInput::DirDown, true);
-										} else if g_axis.value < i16::MIN / 4 {
-											hm.insert(AvkGamepadInput::DirUp, true);
-											hm.insert(AvkGamepadInput::DirDown, false);
-										} else {
-											hm.insert(AvkGamepadInput::DirUp, false);
-											hm.insert(AvkGamepadInput::DirDown, false);
-										}
-									}
-									_ => (),
-								};
+							if let (Some(player), Some(axis)) = (
+								self.gamepads.player(g_axis.which),
+								process_gamepad_axis(&event),
+							) {
+								self.axis_state_gp[player.index()][axis as usize] = g_axis.value;
 							}
 						}
 
-						SDL_EventType_SDL_EVENT_GAMEPAD_BUTTON_DOWN => {
+						SDL_EventType_SDL_EVENT_GAMEPAD_BUTTON_DOWN
+						| SDL_EventType_SDL_EVENT_GAMEPAD_BUTTON_UP => {
 							let g_button = event.gbutton;
-							let pos = self
-								.gamepads
-								.iter()
-								.position(|e| g_button.which == e.0)
-								.expect("Received a gamepad event from an invalid gamepad!");
-
-							if pos > 3 {
-								warn!("Input from extra gamepad discarded!");
-							} else if let Some(input) = process_gamepad_button(&event) {
-								let hm = self
-									.action_state_gp
-									.get_mut(pos)
-									.expect("Failed to get hashmap!");
-								hm.insert(input, true);
-							}
+							self.gamepad_button_update(
+								g_button.which,
+								g_button.button as c_int,
+								g_button.down,
+							);
 						}
-						SDL_EventType_SDL_EVENT_GAMEPAD_BUTTON_UP => {
-							let g_button = event.gbutton;
-							let pos = self
-								.gamepads
-								.iter()
-								.position(|e| g_button.which == e.0)
-								.expect("Received a gamepad event from an invalid gamepad!");
-
-							if pos > 3 {
-								warn!("Input from extra gamepad discarded!");
-							} else if let Some(input) = process_gamepad_button(&event) {
-								let hm = self
-									.action_state_gp
-									.get_mut(pos)
-									.expect("Failed to get hashmap!");
-								hm.insert(input, false);
-							}
+
+						SDL_EventType_SDL_EVENT_MOUSE_MOTION
+						| SDL_EventType_SDL_EVENT_MOUSE_BUTTON_DOWN
+						| SDL_EventType_SDL_EVENT_MOUSE_BUTTON_UP
+						| SDL_EventType_SDL_EVENT_WINDOW_MOUSE_LEAVE
+						| SDL_EventType_SDL_EVENT_FINGER_DOWN
+						| SDL_EventType_SDL_EVENT_FINGER_MOTION
+						| SDL_EventType_SDL_EVENT_FINGER_UP => {
+							self.pointer.process_event(&event, &self.window);
 						}
 
-						// SDL_EventType_SDL_EVENT_WINDOW_RESIZED => Some(BackendEvent::WindowResized {
-						// 	w: event.window.data1,
-						// 	h: event.window.data2,
-						// }),
+						// e.g. going fullscreen, or the window manager tiling it
+						SDL_EventType_SDL_EVENT_WINDOW_RESIZED => {
+							self.window.width = event.window.data1 as u32;
+							self.window.height = event.window.data2 as u32;
+						}
+						SDL_EventType_SDL_EVENT_WINDOW_PIXEL_SIZE_CHANGED => {
+							self.window.pixel_width = event.window.data1 as u32;
+							self.window.pixel_height = event.window.data2 as u32;
+						}
 						_ => {}
 					};
 				}
@@ -563,6 +704,10 @@ impl<'a> Drop for SdlManager {
 			}
 		}
 
+		// close the audio device and gamepads while SDL is still around
+		self.audio = None;
+		self.gamepads = Gamepads::new();
+
 		// De-init SDL
 		unsafe {
 			SDL_Quit();
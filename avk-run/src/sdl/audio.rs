@@ -1 +1,488 @@
+use crate::sdl::sdl_error;
+use crate::sdl::sys::*;
+use crate::sdl::voices::Voices;
+use avk_types::prelude::*;
+use avk_types::{ENVELOPE_UP, MAX_SAMPLES, SAMPLE_BASE_PITCH, SWEEP_DOWN, WAVETABLE_SIZE};
+use log::warn;
+use std::array;
+use std::ffi::{c_int, c_void};
+use std::mem::size_of_val;
+use std::sync::{Arc, Mutex, MutexGuard};
 
+/// Output sample rate, in Hz. Output is stereo, but only samples can be panned.
+pub const SAMPLE_RATE: u32 = 48_000;
+
+/// Samples that can play at the same time.
+const SAMPLE_VOICES: usize = 4;
+
+/// Samples between envelope steps (64 Hz).
+const ENVELOPE_STEP: u32 = SAMPLE_RATE / 64;
+/// Samples between sweep steps (128 Hz).
+const SWEEP_STEP: u32 = SAMPLE_RATE / 128;
+
+/// Loudness of a single channel or sample voice at full volume.
+const CHANNEL_GAIN: f32 = 0.25;
+
+/// The four-channel programmable sound generator.
+/// It only knows about samples, so it can be rendered without an audio device.
+pub struct Psg {
+	channels: [Channel; AudioChannel::COUNT],
+	wavetable: [u8; WAVETABLE_SIZE],
+	/// Samples rendered since the last envelope step.
+	envelope_clock: u32,
+	/// Samples rendered since the last sweep step.
+	sweep_clock: u32,
+}
+
+#[derive(Copy, Clone, Default)]
+struct Channel {
+	// registers
+	frequency: u16,
+	volume: u8,
+	duty: u8,
+	envelope: u16,
+	sweep: u16,
+
+	// state of the note being played
+	playing: bool,
+	/// The frequency, after sweeping.
+	pitch: u32,
+	level: u8,
+	/// Position within the current period, from 0 to 1.
+	phase: f32,
+	envelope_timer: u8,
+	sweep_timer: u8,
+	/// Noise generator state.
+	lfsr: u16,
+}
+
+impl Psg {
+	pub fn new() -> Self {
+		Self {
+			channels: [Channel::default(); AudioChannel::COUNT],
+			// a triangle wave, until the ROM writes its own
+			wavetable: array::from_fn(|i| {
+				if i < WAVETABLE_SIZE / 2 {
+					i as u8
+				} else {
+					(WAVETABLE_SIZE - 1 - i) as u8
+				}
+			}),
+			envelope_clock: 0,
+			sweep_clock: 0,
+		}
+	}
+
+	/// Writes a register, as the ROM asked to with `Avk::audio_write`.
+	pub fn write(&mut self, channel: AudioChannel, register: AudioRegister, value: u16) {
+		let index = channel as usize;
+		let ch = &mut self.channels[index];
+		match register {
+			AudioRegister::Frequency => {
+				ch.frequency = value;
+				ch.pitch = value as u32;
+			}
+			AudioRegister::Volume => ch.volume = value.min(15) as u8,
+			AudioRegister::Duty => ch.duty = (value & 0b11) as u8,
+			AudioRegister::Envelope => ch.envelope = value,
+			AudioRegister::Sweep => ch.sweep = value,
+			AudioRegister::WaveTable => {
+				let index = (value >> 8) as usize % WAVETABLE_SIZE;
+				self.wavetable[index] = (value & 0b1111) as u8;
+			}
+			AudioRegister::Trigger if value == 0 => ch.playing = false,
+			AudioRegister::Trigger => {
+				ch.playing = true;
+				ch.pitch = ch.frequency as u32;
+				ch.level = ch.volume;
+				ch.phase = 0.0;
+				ch.envelope_timer = 0;
+				ch.sweep_timer = 0;
+				ch.lfsr = 0x7FFF;
+			}
+		}
+	}
+
+	/// Fills `out` with the next mono samples.
+	pub fn render(&mut self, out: &mut [f32]) {
+		for sample in out {
+			self.envelope_clock += 1;
+			if self.envelope_clock >= ENVELOPE_STEP {
+				self.envelope_clock = 0;
+				for ch in &mut self.channels {
+					ch.step_envelope();
+				}
+			}
+			self.sweep_clock += 1;
+			if self.sweep_clock >= SWEEP_STEP {
+				self.sweep_clock = 0;
+				// only the pulse channels sweep
+				for ch in &mut self.channels[..2] {
+					ch.step_sweep();
+				}
+			}
+
+			let [pulse1, pulse2, wave, noise] = &mut self.channels;
+			let mix = pulse1.next_pulse()
+				+ pulse2.next_pulse()
+				+ wave.next_wave(&self.wavetable)
+				+ noise.next_noise();
+			*sample = mix * CHANNEL_GAIN;
+		}
+	}
+}
+
+impl Channel {
+	fn step_envelope(&mut self) {
+		let period = (self.envelope & 0b1111) as u8;
+		if !self.playing || period == 0 {
+			return;
+		}
+		self.envelope_timer += 1;
+		if self.envelope_timer < period {
+			return;
+		}
+		self.envelope_timer = 0;
+		if self.envelope & ENVELOPE_UP != 0 {
+			self.level = (self.level + 1).min(15);
+		} else {
+			self.level = self.level.saturating_sub(1);
+		}
+	}
+
+	fn step_sweep(&mut self) {
+		let shift = self.sweep & 0b111;
+		let period = ((self.sweep >> 4) & 0b111) as u8;
+		if !self.playing || period == 0 {
+			return;
+		}
+		self.sweep_timer += 1;
+		if self.sweep_timer < period {
+			return;
+		}
+		self.sweep_timer = 0;
+		let delta = self.pitch >> shift;
+		if self.sweep & SWEEP_DOWN != 0 {
+			self.pitch = self.pitch.saturating_sub(delta);
+		} else {
+			self.pitch += delta;
+			// sweeping past the top of the register ends the note
+			if self.pitch > u16::MAX as u32 {
+				self.playing = false;
+			}
+		}
+	}
+
+	/// Advances the phase by a sample.
+	fn advance(&mut self) {
+		self.phase = (self.phase + self.pitch as f32 / SAMPLE_RATE as f32).fract();
+	}
+
+	/// Scales a sample from -1 to 1 by the channel's volume.
+	fn amplify(&self, sample: f32) -> f32 {
+		sample * self.level as f32 / 15.0
+	}
+
+	fn next_pulse(&mut self) -> f32 {
+		if !self.playing {
+			return 0.0;
+		}
+		self.advance();
+		let duty = match self.duty {
+			0 => 0.125,
+			1 => 0.25,
+			2 => 0.5,
+			_ => 0.75,
+		};
+		self.amplify(if self.phase < duty { 1.0 } else { -1.0 })
+	}
+
+	fn next_wave(&mut self, wavetable: &[u8; WAVETABLE_SIZE]) -> f32 {
+		if !self.playing {
+			return 0.0;
+		}
+		self.advance();
+		let sample = wavetable[(self.phase * WAVETABLE_SIZE as f32) as usize % WAVETABLE_SIZE];
+		self.amplify(sample as f32 / 7.5 - 1.0)
+	}
+
+	fn next_noise(&mut self) -> f32 {
+		if !self.playing {
+			return 0.0;
+		}
+		// the generator is clocked at the channel's frequency, which can be above the sample rate
+		let steps = self.phase + self.pitch as f32 / SAMPLE_RATE as f32;
+		for _ in 0..steps as u32 {
+			let bit = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+			self.lfsr = (self.lfsr >> 1) | (bit << 14);
+			if self.duty & 1 != 0 {
+				// short mode repeats every 127 steps
+				self.lfsr = (self.lfsr & !(1 << 6)) | (bit << 6);
+			}
+		}
+		self.phase = steps.fract();
+		self.amplify(if self.lfsr & 1 == 0 { 1.0 } else { -1.0 })
+	}
+}
+
+/// A registered sample, decoded.
+struct PcmSample {
+	sample_rate: u16,
+	pcm: Vec<i16>,
+}
+
+/// A sample being played.
+struct Voice {
+	sample: Arc<PcmSample>,
+	/// Position in the sample, 32.32 fixed point.
+	position: u64,
+	/// How far the position moves every output sample, 32.32 fixed point.
+	step: u64,
+	left: f32,
+	right: f32,
+	/// When the voice was started, to find the oldest one.
+	started: u64,
+}
+
+/// Plays the samples registered by the ROM, on top of the PSG.
+pub struct Sampler {
+	bank: Vec<Option<Arc<PcmSample>>>,
+	voices: [Option<Voice>; SAMPLE_VOICES],
+	/// Samples started so far.
+	plays: u64,
+	/// From 0 to 255, scales the volume of every voice.
+	volume: u8,
+}
+
+impl Sampler {
+	pub fn new() -> Self {
+		Self {
+			bank: (0..MAX_SAMPLES).map(|_| None).collect(),
+			voices: array::from_fn(|_| None),
+			plays: 0,
+			volume: u8::MAX,
+		}
+	}
+
+	/// Decodes a sample and registers it under `id`. Returns false if it isn't a valid sample.
+	/// Voices already playing the sample it replaces keep playing the old one.
+	pub fn register(&mut self, id: u8, data: &[u8]) -> bool {
+		let Some(sample) = Sample::parse(data) else {
+			return false;
+		};
+		self.bank[id as usize] = Some(Arc::new(PcmSample {
+			sample_rate: sample.sample_rate(),
+			pcm: sample.decode().collect(),
+		}));
+		true
+	}
+
+	/// Starts a registered sample on a free voice, or the oldest one.
+	/// Returns false if there's no sample registered under `id`.
+	pub fn play(&mut self, id: u8, pitch: u16, volume: u8, pan: i8) -> bool {
+		let Some(sample) = &self.bank[id as usize] else {
+			return false;
+		};
+		let voice = match self.voices.iter().position(Option::is_none) {
+			Some(free) => free,
+			None => (0..SAMPLE_VOICES)
+				.min_by_key(|&i| self.voices[i].as_ref().map_or(0, |voice| voice.started))
+				.unwrap(),
+		};
+
+		let pan = pan.max(-127) as f32 / 127.0;
+		let gain = volume.min(15) as f32 / 15.0 * CHANNEL_GAIN;
+		self.voices[voice] = Some(Voice {
+			sample: sample.clone(),
+			position: 0,
+			step: ((sample.sample_rate as u64) << 32) * pitch as u64
+				/ SAMPLE_BASE_PITCH as u64
+				/ SAMPLE_RATE as u64,
+			left: (1.0 - pan).min(1.0) * gain,
+			right: (1.0 + pan).min(1.0) * gain,
+			started: self.plays,
+		});
+		self.plays += 1;
+		true
+	}
+
+	pub fn stop(&mut self) {
+		self.voices = array::from_fn(|_| None);
+	}
+
+	pub fn set_volume(&mut self, volume: u8) {
+		self.volume = volume;
+	}
+
+	/// Adds the voices to `out`, interleaved stereo.
+	pub fn mix(&mut self, out: &mut [f32]) {
+		let volume = self.volume as f32 / u8::MAX as f32;
+		for slot in &mut self.voices {
+			let Some(voice) = slot else {
+				continue;
+			};
+			for frame in out.chunks_exact_mut(2) {
+				let Some(&sample) = voice.sample.pcm.get((voice.position >> 32) as usize) else {
+					*slot = None;
+					break;
+				};
+				let sample = sample as f32 / -(i16::MIN as f32) * volume;
+				frame[0] += sample * voice.left;
+				frame[1] += sample * voice.right;
+				voice.position += voice.step;
+			}
+		}
+	}
+}
+
+/// Everything making sound: the PSG with the music and effects playing on it, and the sampler.
+pub struct Synth {
+	voices: Voices,
+	sampler: Sampler,
+}
+
+impl Synth {
+	pub fn new() -> Self {
+		Self {
+			voices: Voices::new(),
+			sampler: Sampler::new(),
+		}
+	}
+
+	/// Fills `out` with the next stereo samples, left and right interleaved.
+	pub fn render(&mut self, out: &mut [f32]) {
+		let mut mono = [0.0; 256];
+		for frames in out.chunks_mut(mono.len() * 2) {
+			let mono = &mut mono[..frames.len() / 2];
+			self.voices.render(mono);
+			for (frame, &sample) in frames.chunks_exact_mut(2).zip(mono.iter()) {
+				frame[0] = sample;
+				frame[1] = sample;
+			}
+			self.sampler.mix(frames);
+		}
+	}
+
+	pub fn write(&mut self, channel: AudioChannel, register: AudioRegister, value: u16) {
+		self.voices.write(channel, register, value);
+	}
+
+	/// Starts playing an encoded song. Returns false if it isn't a valid song.
+	pub fn play_music(&mut self, data: &[u8]) -> bool {
+		self.voices.play_music(data)
+	}
+
+	pub fn stop_music(&mut self) {
+		self.voices.stop_music();
+	}
+
+	pub fn set_music_volume(&mut self, volume: u8) {
+		self.voices.set_music_volume(volume);
+	}
+
+	/// Starts playing an encoded song as a sound effect. Returns false if it isn't a valid song.
+	pub fn play_effect(&mut self, data: &[u8], priority: u8) -> bool {
+		self.voices.play_effect(data, priority)
+	}
+
+	pub fn stop_effects(&mut self) {
+		self.voices.stop_effects();
+	}
+
+	/// Sets the volume of the sound effects and samples.
+	pub fn set_sfx_volume(&mut self, volume: u8) {
+		self.voices.set_sfx_volume(volume);
+		self.sampler.set_volume(volume);
+	}
+
+	/// Decodes a sample and registers it under `id`. Returns false if it isn't a valid sample.
+	pub fn register_sample(&mut self, id: u8, data: &[u8]) -> bool {
+		self.sampler.register(id, data)
+	}
+
+	/// Returns false if there's no sample registered under `id`.
+	pub fn play_sample(&mut self, id: u8, pitch: u16, volume: u8, pan: i8) -> bool {
+		self.sampler.play(id, pitch, volume, pan)
+	}
+
+	pub fn stop_samples(&mut self) {
+		self.sampler.stop();
+	}
+}
+
+/// Plays a [`Synth`] on the default audio device.
+pub struct AudioOutput {
+	stream: *mut SDL_AudioStream,
+	/// Shared with the audio thread, which renders it on demand.
+	synth: Box<Mutex<Synth>>,
+}
+
+impl AudioOutput {
+	/// Opens the default playback device, returning None if there isn't one.
+	pub fn open() -> Option<Self> {
+		let synth = Box::new(Mutex::new(Synth::new()));
+		let spec = SDL_AudioSpec {
+			format: SDL_AudioFormat_SDL_AUDIO_F32LE,
+			channels: 2,
+			freq: SAMPLE_RATE as c_int,
+		};
+		let stream = unsafe {
+			SDL_OpenAudioDeviceStream(
+				SDL_AUDIO_DEVICE_DEFAULT_PLAYBACK,
+				&spec,
+				Some(audio_callback),
+				synth.as_ref() as *const Mutex<Synth> as *mut c_void,
+			)
+		};
+		if stream.is_null() {
+			warn!(
+				"Failed to open the audio device, sound is disabled: {}",
+				sdl_error()
+			);
+			return None;
+		}
+		// devices opened with a stream start paused
+		unsafe {
+			SDL_ResumeAudioStreamDevice(stream);
+		}
+		Some(Self { stream, synth })
+	}
+
+	/// Locks the synth, pausing the audio thread until the guard is dropped.
+	pub fn synth(&self) -> MutexGuard<'_, Synth> {
+		self.synth.lock().unwrap()
+	}
+}
+
+impl Drop for AudioOutput {
+	fn drop(&mut self) {
+		// stops the audio thread before the synth is freed
+		unsafe {
+			SDL_DestroyAudioStream(self.stream);
+		}
+	}
+}
+
+/// Called on SDL's audio thread whenever the stream needs more data.
+unsafe extern "C" fn audio_callback(
+	userdata: *mut c_void,
+	stream: *mut SDL_AudioStream,
+	additional_amount: c_int,
+	_total_amount: c_int,
+) {
+	let synth = &*(userdata as *const Mutex<Synth>);
+	let mut synth = synth.lock().unwrap();
+	// stereo, so always an even number of samples
+	let mut buf = [0.0f32; 512];
+	let mut remaining = additional_amount.max(0) as usize / size_of::<[f32; 2]>() * 2;
+	while remaining > 0 {
+		let len = remaining.min(buf.len());
+		synth.render(&mut buf[..len]);
+		SDL_PutAudioStreamData(
+			stream,
+			buf.as_ptr() as *const c_void,
+			size_of_val(&buf[..len]) as c_int,
+		);
+		remaining -= len;
+	}
+}
@@ -0,0 +1,421 @@
+//! Keyboard and gamepad bindings of the four players.
+//! They're read from `$XDG_CONFIG_HOME/avk/bindings.cfg` (or the file passed with `--bindings`),
+//! where every line replaces the defaults of one input:
+//! ```text
+//! # <player>.<input> = <keys and pad:<button>s>
+//! alpha.face_down = Space, V, pad:south
+//! ```
+//! F5 to F8 rebind the inputs of Alpha to Delta one after the other, and save the result there.
+
+use crate::sdl::sys::*;
+use crate::sdl::Keycode;
+use avk_types::{AvkGamepadInput, Player};
+use log::{debug, error, info, warn};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::c_int;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::{array, env};
+
+thread_local! {
+	/// Set by `--bindings`, None for the default path.
+	static PATH: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Uses the bindings file at `path` instead of the default one.
+pub fn set_path(path: impl Into<PathBuf>) {
+	PATH.set(Some(path.into()));
+}
+
+/// Returns the `--bindings` path, or `$XDG_CONFIG_HOME/avk/bindings.cfg`, falling back to
+/// `~/.config/avk/bindings.cfg`.
+fn path() -> PathBuf {
+	PATH.with_borrow(Clone::clone).unwrap_or_else(|| {
+		let config_home = env::var_os("XDG_CONFIG_HOME")
+			.filter(|dir| !dir.is_empty())
+			.map(PathBuf::from)
+			.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+			.unwrap_or_default();
+		config_home.join("avk").join("bindings.cfg")
+	})
+}
+
+/// A gamepad button, in SDL's layout.
+#[derive(Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Button {
+	South,
+	East,
+	West,
+	North,
+	Back,
+	Start,
+	LeftStick,
+	RightStick,
+	LeftShoulder,
+	RightShoulder,
+	DpadUp,
+	DpadDown,
+	DpadLeft,
+	DpadRight,
+}
+
+impl Button {
+	pub fn from_sdl(button: c_int) -> Option<Self> {
+		match button {
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_SOUTH => Some(Button::South),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_EAST => Some(Button::East),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_WEST => Some(Button::West),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_NORTH => Some(Button::North),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_BACK => Some(Button::Back),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_START => Some(Button::Start),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_LEFT_STICK => Some(Button::LeftStick),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_RIGHT_STICK => Some(Button::RightStick),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_LEFT_SHOULDER => Some(Button::LeftShoulder),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_RIGHT_SHOULDER => Some(Button::RightShoulder),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_DPAD_UP => Some(Button::DpadUp),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_DPAD_DOWN => Some(Button::DpadDown),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_DPAD_LEFT => Some(Button::DpadLeft),
+			SDL_GamepadButton_SDL_GAMEPAD_BUTTON_DPAD_RIGHT => Some(Button::DpadRight),
+			_ => None,
+		}
+	}
+}
+
+/// The names of the players in the bindings file.
+const PLAYERS: [(&str, Player); 4] = [
+	("alpha", Player::Alpha),
+	("bravo", Player::Bravo),
+	("charlie", Player::Charlie),
+	("delta", Player::Delta),
+];
+
+/// The names of the inputs in the bindings file.
+const INPUTS: [(&str, AvkGamepadInput); AvkGamepadInput::COUNT] = [
+	("dir_up", AvkGamepadInput::DirUp),
+	("dir_right", AvkGamepadInput::DirRight),
+	("dir_down", AvkGamepadInput::DirDown),
+	("dir_left", AvkGamepadInput::DirLeft),
+	("face_up", AvkGamepadInput::FaceUp),
+	("face_right", AvkGamepadInput::FaceRight),
+	("face_down", AvkGamepadInput::FaceDown),
+	("face_left", AvkGamepadInput::FaceLeft),
+	("trigger_left", AvkGamepadInput::TriggerLeft),
+	("trigger_right", AvkGamepadInput::TriggerRight),
+	("menu", AvkGamepadInput::Menu),
+];
+
+/// The names of the gamepad buttons in the bindings file, after `pad:`.
+const BUTTONS: [(&str, Button); 14] = [
+	("south", Button::South),
+	("east", Button::East),
+	("west", Button::West),
+	("north", Button::North),
+	("back", Button::Back),
+	("start", Button::Start),
+	("left_stick", Button::LeftStick),
+	("right_stick", Button::RightStick),
+	("left_shoulder", Button::LeftShoulder),
+	("right_shoulder", Button::RightShoulder),
+	("dpad_up", Button::DpadUp),
+	("dpad_down", Button::DpadDown),
+	("dpad_left", Button::DpadLeft),
+	("dpad_right", Button::DpadRight),
+];
+
+/// The names of the keys in the bindings file.
+const KEYS: &[(&str, Keycode)] = &[
+	("Return", Keycode::Return),
+	("Backspace", Keycode::Backspace),
+	("Tab", Keycode::Tab),
+	("Space", Keycode::Space),
+	("Comma", Keycode::Comma),
+	("Period", Keycode::Period),
+	("Minus", Keycode::Minus),
+	("Slash", Keycode::Slash),
+	("Semicolon", Keycode::Semicolon),
+	("0", Keycode::Num0),
+	("1", Keycode::Num1),
+	("2", Keycode::Num2),
+	("3", Keycode::Num3),
+	("4", Keycode::Num4),
+	("5", Keycode::Num5),
+	("6", Keycode::Num6),
+	("7", Keycode::Num7),
+	("8", Keycode::Num8),
+	("9", Keycode::Num9),
+	("A", Keycode::A),
+	("B", Keycode::B),
+	("C", Keycode::C),
+	("D", Keycode::D),
+	("E", Keycode::E),
+	("F", Keycode::F),
+	("G", Keycode::G),
+	("H", Keycode::H),
+	("I", Keycode::I),
+	("J", Keycode::J),
+	("K", Keycode::K),
+	("L", Keycode::L),
+	("M", Keycode::M),
+	("N", Keycode::N),
+	("O", Keycode::O),
+	("P", Keycode::P),
+	("Q", Keycode::Q),
+	("R", Keycode::R),
+	("S", Keycode::S),
+	("T", Keycode::T),
+	("U", Keycode::U),
+	("V", Keycode::V),
+	("W", Keycode::W),
+	("X", Keycode::X),
+	("Y", Keycode::Y),
+	("Z", Keycode::Z),
+	("Delete", Keycode::Delete),
+	("F9", Keycode::F9),
+	("F10", Keycode::F10),
+	("F11", Keycode::F11),
+	("F12", Keycode::F12),
+	("PrintScreen", Keycode::PrintScreen),
+	("ScrollLock", Keycode::ScrollLock),
+	("PauseBreak", Keycode::PauseBreak),
+	("Insert", Keycode::Insert),
+	("Home", Keycode::Home),
+	("PageUp", Keycode::PageUp),
+	("End", Keycode::End),
+	("PageDown", Keycode::PageDown),
+	("Up", Keycode::Up),
+	("Right", Keycode::Right),
+	("Down", Keycode::Down),
+	("Left", Keycode::Left),
+	("Kp0", Keycode::Kp0),
+	("Kp1", Keycode::Kp1),
+	("Kp2", Keycode::Kp2),
+	("Kp3", Keycode::Kp3),
+	("Kp4", Keycode::Kp4),
+	("Kp5", Keycode::Kp5),
+	("Kp6", Keycode::Kp6),
+	("Kp7", Keycode::Kp7),
+	("Kp8", Keycode::Kp8),
+	("Kp9", Keycode::Kp9),
+	("KpEnter", Keycode::KpEnter),
+	("KpPlus", Keycode::KpPlus),
+	("KpMinus", Keycode::KpMinus),
+	("KpPeriod", Keycode::KpPeriod),
+	("LeftShift", Keycode::LeftShift),
+	("RightShift", Keycode::RightShift),
+	("LeftCtrl", Keycode::LeftCtrl),
+	("RightCtrl", Keycode::RightCtrl),
+	("LeftAlt", Keycode::LeftAlt),
+	("RightAlt", Keycode::RightAlt),
+];
+
+/// Default keys of each player, in the order of [`AvkGamepadInput::ALL`].
+const DEFAULT_KEYS: [[Keycode; AvkGamepadInput::COUNT]; 4] = {
+	use Keycode::*;
+	[
+		[W, D, S, A, X, C, V, Z, Q, E, Tab],
+		[I, L, K, J, Comma, M, Period, N, U, O, P],
+		[
+			Up, Right, Down, Left, Home, PageDown, End, Delete, Insert, PageUp, Return,
+		],
+		[Kp8, Kp6, Kp5, Kp4, Kp0, Kp3, Kp2, Kp1, Kp7, Kp9, KpEnter],
+	]
+};
+
+/// Default gamepad buttons, the same for every player.
+const DEFAULT_BUTTONS: [(Button, AvkGamepadInput); 12] = [
+	(Button::DpadUp, AvkGamepadInput::DirUp),
+	(Button::DpadRight, AvkGamepadInput::DirRight),
+	(Button::DpadDown, AvkGamepadInput::DirDown),
+	(Button::DpadLeft, AvkGamepadInput::DirLeft),
+	(Button::North, AvkGamepadInput::FaceUp),
+	(Button::East, AvkGamepadInput::FaceRight),
+	(Button::South, AvkGamepadInput::FaceDown),
+	(Button::West, AvkGamepadInput::FaceLeft),
+	(Button::LeftShoulder, AvkGamepadInput::TriggerLeft),
+	(Button::RightShoulder, AvkGamepadInput::TriggerRight),
+	(Button::Back, AvkGamepadInput::Menu),
+	(Button::Start, AvkGamepadInput::Menu),
+];
+
+pub fn player_name(player: Player) -> &'static str {
+	PLAYERS[player.index()].0
+}
+
+/// Returns the player with a name, as written in the bindings file.
+pub fn player_by_name(name: &str) -> Option<Player> {
+	let (_, player) = PLAYERS.iter().find(|(n, _)| *n == name)?;
+	Some(*player)
+}
+
+pub fn input_name(input: AvkGamepadInput) -> &'static str {
+	INPUTS[input as usize].0
+}
+
+fn key_name(key: Keycode) -> Option<&'static str> {
+	KEYS.iter().find(|(_, k)| *k == key).map(|(name, _)| *name)
+}
+
+pub struct Bindings {
+	keys: HashMap<Keycode, (Player, AvkGamepadInput)>,
+	/// The buttons of the gamepad of each player.
+	buttons: [HashMap<Button, AvkGamepadInput>; 4],
+}
+
+impl Bindings {
+	pub fn defaults() -> Self {
+		let mut keys = HashMap::new();
+		for player in Player::ALL {
+			for input in AvkGamepadInput::ALL {
+				keys.insert(
+					DEFAULT_KEYS[player.index()][input as usize],
+					(player, input),
+				);
+			}
+		}
+		Self {
+			keys,
+			buttons: array::from_fn(|_| HashMap::from(DEFAULT_BUTTONS)),
+		}
+	}
+
+	/// Loads the bindings file on top of the defaults.
+	pub fn load() -> Self {
+		let mut bindings = Self::defaults();
+		let path = path();
+		match fs::read_to_string(&path) {
+			Ok(text) => {
+				for (number, line) in text.lines().enumerate() {
+					if let Err(message) = bindings.parse_line(line) {
+						warn!("{}:{}: {message}", path.display(), number + 1);
+					}
+				}
+				debug!("Loaded bindings from {}", path.display());
+			}
+			Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+			Err(err) => error!("Failed to read bindings from {}: {err}", path.display()),
+		}
+		bindings
+	}
+
+	fn parse_line(&mut self, line: &str) -> Result<(), String> {
+		let line = line.split('#').next().unwrap().trim();
+		if line.is_empty() {
+			return Ok(());
+		}
+		let Some((target, sources)) = line.split_once('=') else {
+			return Err(String::from(
+				"expected `<player>.<input> = <keys and buttons>`",
+			));
+		};
+		let target = target.trim();
+		let (player, input) = target
+			.split_once('.')
+			.and_then(|(player, input)| {
+				let player = player_by_name(player)?;
+				let input = INPUTS.iter().find(|(name, _)| *name == input)?.1;
+				Some((player, input))
+			})
+			.ok_or_else(|| format!("unknown input `{target}`"))?;
+
+		let mut keys = Vec::new();
+		let mut buttons = Vec::new();
+		for source in sources.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+			if let Some(button) = source.strip_prefix("pad:") {
+				let Some(&(_, button)) = BUTTONS.iter().find(|(name, _)| *name == button) else {
+					return Err(format!("unknown gamepad button `{button}`"));
+				};
+				buttons.push(button);
+			} else {
+				let Some(&(_, key)) = KEYS.iter().find(|(name, _)| *name == source) else {
+					return Err(format!("unknown key `{source}`"));
+				};
+				keys.push(key);
+			}
+		}
+
+		// a line replaces every default of its input, so it can also unbind it
+		self.keys
+			.retain(|_, (p, i)| p.index() != player.index() || *i != input);
+		self.buttons[player.index()].retain(|_, i| *i != input);
+		for key in keys {
+			self.keys.insert(key, (player, input));
+		}
+		for button in buttons {
+			self.buttons[player.index()].insert(button, input);
+		}
+		Ok(())
+	}
+
+	/// Returns the input a key is bound to.
+	pub fn key(&self, key: Keycode) -> Option<(Player, AvkGamepadInput)> {
+		self.keys.get(&key).copied()
+	}
+
+	/// Returns the input a button of a player's gamepad is bound to.
+	pub fn button(&self, player: Player, button: Button) -> Option<AvkGamepadInput> {
+		self.buttons[player.index()].get(&button).copied()
+	}
+
+	/// Binds a key to an input instead of the keys it had, taking it away from its old input.
+	/// Returns false if the key can't be bound, which is the case of the runner's hotkeys.
+	pub fn bind_key(&mut self, key: Keycode, player: Player, input: AvkGamepadInput) -> bool {
+		if key_name(key).is_none() {
+			return false;
+		}
+		self.keys
+			.retain(|_, (p, i)| p.index() != player.index() || *i != input);
+		self.keys.insert(key, (player, input));
+		true
+	}
+
+	/// Binds a button of a player's gamepad to an input instead of the buttons it had.
+	pub fn bind_button(&mut self, player: Player, button: Button, input: AvkGamepadInput) {
+		let buttons = &mut self.buttons[player.index()];
+		buttons.retain(|_, i| *i != input);
+		buttons.insert(button, input);
+	}
+
+	/// Writes every binding to the bindings file.
+	pub fn save(&self) {
+		let mut text = String::from(
+			"# AVK controls. Each line binds keys and gamepad buttons (pad:<button>) to an input.\n",
+		);
+		for player in Player::ALL {
+			for input in AvkGamepadInput::ALL {
+				let keys = KEYS
+					.iter()
+					.filter(|(_, key)| {
+						self.key(*key)
+							.is_some_and(|(p, i)| p.index() == player.index() && i == input)
+					})
+					.map(|(name, _)| String::from(*name));
+				let buttons = BUTTONS
+					.iter()
+					.filter(|(_, button)| self.button(player, *button) == Some(input))
+					.map(|(name, _)| format!("pad:{name}"));
+				let sources: Vec<String> = keys.chain(buttons).collect();
+				let line = format!(
+					"{}.{} = {}",
+					player_name(player),
+					input_name(input),
+					sources.join(", ")
+				);
+				// unbound inputs are left with nothing after the `=`
+				text.push_str(line.trim_end());
+				text.push('\n');
+			}
+		}
+
+		let path = path();
+		let result = path
+			.parent()
+			.map_or(Ok(()), fs::create_dir_all)
+			.and_then(|()| fs::write(&path, text));
+		match result {
+			Ok(()) => info!("Saved bindings to {}", path.display()),
+			Err(err) => error!("Failed to write bindings to {}: {err}", path.display()),
+		}
+	}
+}
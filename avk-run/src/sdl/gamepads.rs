@@ -0,0 +1,191 @@
+//! Assigns gamepads to players.
+//! A gamepad joins as the first free player once a button is pressed on it. Its player stays
+//! reserved for it after it's unplugged, so it gets the same one back when it reconnects.
+
+use crate::sdl::bindings;
+use crate::sdl::sdl_error;
+use crate::sdl::sys::*;
+use avk_types::Player;
+use log::{debug, info, warn};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ffi::{c_int, CStr};
+
+thread_local! {
+	static RUMBLE: Cell<bool> = const { Cell::new(true) };
+}
+
+/// Keeps every gamepad still, whatever the ROM asks for.
+pub fn disable_rumble() {
+	RUMBLE.set(false);
+}
+
+/// Tells a gamepad apart from the others, and from itself across reconnections.
+/// Gamepads without a serial number can only be told apart by model, so a reconnecting one may
+/// get the player of another gamepad of the same model if that one is unplugged too.
+#[derive(Clone, Eq, PartialEq)]
+struct Identity {
+	guid: [u8; 16],
+	serial: Option<String>,
+}
+
+struct Pad {
+	gamepad: *mut SDL_Gamepad,
+	identity: Identity,
+}
+
+/// The gamepad a player belongs to.
+struct Slot {
+	identity: Identity,
+	/// None while the gamepad is unplugged.
+	id: Option<SDL_JoystickID>,
+}
+
+pub struct Gamepads {
+	/// Every open gamepad, whether it has joined or not.
+	pads: HashMap<SDL_JoystickID, Pad>,
+	slots: [Option<Slot>; 4],
+}
+
+impl Gamepads {
+	pub fn new() -> Self {
+		Self {
+			pads: HashMap::new(),
+			slots: [const { None }; 4],
+		}
+	}
+
+	/// Opens a gamepad that was plugged in, giving it its player back if it had one.
+	pub fn add(&mut self, id: SDL_JoystickID) {
+		let gamepad = unsafe { SDL_OpenGamepad(id) };
+		if gamepad.is_null() {
+			warn!("Failed to open gamepad: {}", sdl_error());
+			return;
+		}
+		let identity = unsafe {
+			let serial = SDL_GetGamepadSerial(gamepad);
+			Identity {
+				guid: SDL_GetGamepadGUIDForID(id).data,
+				serial: (!serial.is_null())
+					.then(|| CStr::from_ptr(serial).to_string_lossy().into_owned()),
+			}
+		};
+		self.pads.insert(id, Pad { gamepad, identity });
+
+		let rejoined = self.slots.iter().position(|slot| {
+			slot.as_ref()
+				.is_some_and(|slot| slot.id.is_none() && slot.identity == self.pads[&id].identity)
+		});
+		match rejoined {
+			Some(index) => {
+				self.assign(index, id);
+				info!("{} reconnected as {}", self.name(id), player_name(index));
+			}
+			None => info!("{} connected, press a button to join", self.name(id)),
+		}
+	}
+
+	/// Closes a gamepad that was unplugged. Returns the player it belonged to.
+	pub fn remove(&mut self, id: SDL_JoystickID) -> Option<Player> {
+		let name = self.name(id);
+		let pad = self.pads.remove(&id)?;
+		unsafe { SDL_CloseGamepad(pad.gamepad) };
+
+		let player = self.player(id);
+		match player {
+			Some(player) => {
+				// the slot stays reserved, for when it comes back
+				self.slots[player.index()].as_mut().unwrap().id = None;
+				info!("{name} disconnected from {}", bindings::player_name(player));
+			}
+			None => debug!("{name} disconnected"),
+		}
+		player
+	}
+
+	/// Returns the player a gamepad has joined as.
+	pub fn player(&self, id: SDL_JoystickID) -> Option<Player> {
+		let index = self
+			.slots
+			.iter()
+			.position(|slot| slot.as_ref().is_some_and(|slot| slot.id == Some(id)))?;
+		Some(Player::ALL[index])
+	}
+
+	/// Makes a gamepad join as the first player without a gamepad, preferring players that
+	/// never had one to those whose gamepad is unplugged. Returns None if they're all taken.
+	pub fn join(&mut self, id: SDL_JoystickID) -> Option<Player> {
+		if !self.pads.contains_key(&id) {
+			return None;
+		}
+		let index = self.slots.iter().position(Option::is_none).or_else(|| {
+			self.slots
+				.iter()
+				.position(|slot| slot.as_ref().is_some_and(|slot| slot.id.is_none()))
+		});
+		let Some(index) = index else {
+			warn!("{} can't join, every player has a gamepad", self.name(id));
+			return None;
+		};
+		self.assign(index, id);
+		info!("{} joined as {}", self.name(id), player_name(index));
+		Some(Player::ALL[index])
+	}
+
+	/// Returns true if a player has a gamepad plugged in.
+	pub fn is_connected(&self, player: Player) -> bool {
+		self.slots[player.index()]
+			.as_ref()
+			.is_some_and(|slot| slot.id.is_some())
+	}
+
+	/// Rumbles a player's gamepad, if it has one that can.
+	pub fn rumble(&self, player: Player, low: u16, high: u16, duration_ms: u32) {
+		if !RUMBLE.get() {
+			return;
+		}
+		let Some(id) = self.slots[player.index()].as_ref().and_then(|slot| slot.id) else {
+			return;
+		};
+		// fails for gamepads without motors, which ROMs can't be expected to know about
+		if !unsafe { SDL_RumbleGamepad(self.pads[&id].gamepad, low, high, duration_ms) } {
+			debug!("{} can't rumble: {}", self.name(id), sdl_error());
+		}
+	}
+
+	fn assign(&mut self, index: usize, id: SDL_JoystickID) {
+		let pad = &self.pads[&id];
+		self.slots[index] = Some(Slot {
+			identity: pad.identity.clone(),
+			id: Some(id),
+		});
+		// lights up the player number on gamepads that have one
+		unsafe { SDL_SetGamepadPlayerIndex(pad.gamepad, index as c_int) };
+	}
+
+	fn name(&self, id: SDL_JoystickID) -> String {
+		let name = self
+			.pads
+			.get(&id)
+			.map(|pad| unsafe { SDL_GetGamepadName(pad.gamepad) })
+			.filter(|name| !name.is_null());
+		match name {
+			Some(name) => format!("Gamepad \"{}\"", unsafe {
+				CStr::from_ptr(name).to_string_lossy()
+			}),
+			None => format!("Gamepad {id}"),
+		}
+	}
+}
+
+impl Drop for Gamepads {
+	fn drop(&mut self) {
+		for pad in self.pads.values() {
+			unsafe { SDL_CloseGamepad(pad.gamepad) };
+		}
+	}
+}
+
+fn player_name(index: usize) -> &'static str {
+	bindings::player_name(Player::ALL[index])
+}
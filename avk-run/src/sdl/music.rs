@@ -0,0 +1,175 @@
+use crate::sdl::audio::SAMPLE_RATE;
+use avk_types::prelude::*;
+use avk_types::{Instrument, PatternCell, INSTRUMENT_VOLUME, NOTE_OFF};
+
+type Row = [PatternCell; AudioChannel::COUNT];
+
+/// A song copied out of the ROM, parsed once so playing it doesn't have to.
+pub struct LoadedSong {
+	rows_per_pattern: usize,
+	/// Samples per row.
+	row_len: usize,
+	loop_order: Option<usize>,
+	instruments: Vec<Instrument>,
+	/// The rows of every pattern, one pattern after the other.
+	rows: Vec<Row>,
+	/// The pattern played at each order.
+	orders: Vec<usize>,
+}
+
+impl LoadedSong {
+	/// Returns None if it isn't a valid song.
+	pub fn parse(data: &[u8]) -> Option<Self> {
+		let song = Song::parse(data)?;
+		let rows_per_pattern = song.rows_per_pattern();
+		Some(Self {
+			rows_per_pattern,
+			row_len: SAMPLE_RATE as usize * 60 / song.tempo() as usize,
+			loop_order: song.loop_order(),
+			instruments: (0..song.instrument_count())
+				.map(|index| song.instrument(index).unwrap())
+				.collect(),
+			rows: (0..song.pattern_count())
+				.flat_map(|pattern| {
+					(0..rows_per_pattern).map(move |row| {
+						AudioChannel::ALL.map(|channel| song.cell(pattern, row, channel))
+					})
+				})
+				.collect(),
+			orders: (0..song.order_count())
+				.map(|order| song.order(order))
+				.collect(),
+		})
+	}
+
+	/// Returns the channels the song has notes on.
+	pub fn used_channels(&self) -> Vec<AudioChannel> {
+		AudioChannel::ALL
+			.into_iter()
+			.filter(|&channel| self.rows.iter().any(|row| row[channel as usize].note != 0))
+			.collect()
+	}
+
+	fn row(&self, order: usize, row: usize) -> &Row {
+		&self.rows[self.orders[order] * self.rows_per_pattern + row]
+	}
+}
+
+/// Plays songs a row at a time, for the music or a sound effect.
+pub struct Sequencer {
+	song: Option<LoadedSong>,
+	order: usize,
+	row: usize,
+	/// Samples left until the next row.
+	countdown: usize,
+	/// From 0 to 255, scales the volume of every note.
+	volume: u8,
+}
+
+impl Sequencer {
+	pub fn new() -> Self {
+		Self {
+			song: None,
+			order: 0,
+			row: 0,
+			countdown: 0,
+			volume: u8::MAX,
+		}
+	}
+
+	/// Starts playing a song from the beginning, once the current one has been stopped.
+	pub fn play(&mut self, song: LoadedSong) {
+		self.song = Some(song);
+		self.order = 0;
+		self.row = 0;
+		self.countdown = 0;
+	}
+
+	/// Stops the song, silencing every channel.
+	pub fn stop(&mut self, write: &mut impl FnMut(AudioChannel, AudioRegister, u16)) {
+		if self.song.take().is_some() {
+			for channel in AudioChannel::ALL {
+				write(channel, AudioRegister::Trigger, 0);
+			}
+		}
+		self.order = 0;
+		self.row = 0;
+		self.countdown = 0;
+	}
+
+	pub fn is_playing(&self) -> bool {
+		self.song.is_some()
+	}
+
+	pub fn set_volume(&mut self, volume: u8) {
+		self.volume = volume;
+	}
+
+	/// Returns the samples left until the next row, or None if there's no song playing.
+	pub fn countdown(&self) -> Option<usize> {
+		self.song.as_ref().map(|_| self.countdown)
+	}
+
+	/// Moves the song on by `samples`, which must not go past the next row.
+	pub fn advance(&mut self, samples: usize) {
+		if self.song.is_some() {
+			self.countdown -= samples;
+		}
+	}
+
+	/// Plays the current row once its time has come, with the register writes going to `write`,
+	/// and moves on to the next one. Stops at the end of the song.
+	pub fn play_row(&mut self, write: &mut impl FnMut(AudioChannel, AudioRegister, u16)) {
+		let Some(song) = &self.song else {
+			return;
+		};
+		if self.order == song.orders.len() {
+			// the last row has rung out
+			self.stop(write);
+			return;
+		}
+
+		let row = song.row(self.order, self.row);
+		for (channel, cell) in AudioChannel::ALL.into_iter().zip(row) {
+			match cell.note {
+				0 => {}
+				NOTE_OFF => write(channel, AudioRegister::Trigger, 0),
+				note => {
+					// cells naming a missing instrument are skipped
+					let Some(instrument) = song.instruments.get(cell.instrument as usize) else {
+						continue;
+					};
+					let volume = match cell.volume {
+						INSTRUMENT_VOLUME => instrument.volume,
+						volume => volume,
+					};
+					let volume = volume.min(15) as u16 * self.volume as u16 / u8::MAX as u16;
+					write(channel, AudioRegister::Duty, instrument.duty as u16);
+					write(channel, AudioRegister::Envelope, instrument.envelope);
+					write(channel, AudioRegister::Sweep, instrument.sweep);
+					write(channel, AudioRegister::Volume, volume);
+					write(channel, AudioRegister::Frequency, note_frequency(note));
+					write(channel, AudioRegister::Trigger, 1);
+				}
+			}
+		}
+		self.countdown = song.row_len;
+
+		self.row += 1;
+		if self.row == song.rows_per_pattern {
+			self.row = 0;
+			self.order += 1;
+			if self.order == song.orders.len() {
+				// songs that don't loop stay past the end until the last row has rung out
+				if let Some(order) = song.loop_order {
+					self.order = order;
+				}
+			}
+		}
+	}
+}
+
+/// Returns the frequency of a MIDI note, in Hz.
+pub fn note_frequency(note: u8) -> u16 {
+	(440.0 * 2f32.powf((note as f32 - 69.0) / 12.0)).round() as u16
+}
@@ -0,0 +1,86 @@
+//! The mouse and the touch screen, which make up the light gun of one player.
+//! The left mouse button and touching the screen pull the trigger. Only the first finger down
+//! is followed, the others are ignored until it's lifted.
+
+use crate::sdl::sys::*;
+use crate::sdl::window::Window;
+use avk_types::Player;
+use std::cell::Cell;
+
+/// The mouse SDL reports the mouse events it makes up from touches as, which are handled as
+/// touches instead. SDL defines it as `(SDL_MouseID)-1`, which bindgen can't translate.
+const TOUCH_MOUSE_ID: SDL_MouseID = SDL_MouseID::MAX;
+
+thread_local! {
+	/// Set by `--pointer`.
+	static PLAYER: Cell<Player> = const { Cell::new(Player::Alpha) };
+}
+
+/// Makes the mouse and the touch screen the light gun of `player`, instead of Alpha.
+pub fn set_player(player: Player) {
+	PLAYER.set(player);
+}
+
+/// Returns the player the mouse and the touch screen belong to.
+pub fn player() -> Player {
+	PLAYER.get()
+}
+
+/// Where the mouse or the finger is, in window coordinates.
+#[derive(Default)]
+pub struct Pointer {
+	pub x: f32,
+	pub y: f32,
+	pub trigger: bool,
+	/// False while the mouse is outside of the window or no finger is down.
+	pub on_screen: bool,
+	/// The finger being followed.
+	finger: Option<SDL_FingerID>,
+}
+
+impl Pointer {
+	/// Follows a mouse or touch event.
+	pub fn process_event(&mut self, event: &SDL_Event, window: &Window) {
+		match unsafe { event.type_ } {
+			SDL_EventType_SDL_EVENT_MOUSE_MOTION => {
+				let motion = unsafe { event.motion };
+				if motion.which != TOUCH_MOUSE_ID {
+					self.x = motion.x;
+					self.y = motion.y;
+					self.on_screen = true;
+				}
+			}
+			SDL_EventType_SDL_EVENT_MOUSE_BUTTON_DOWN | SDL_EventType_SDL_EVENT_MOUSE_BUTTON_UP => {
+				let button = unsafe { event.button };
+				if button.which != TOUCH_MOUSE_ID && button.button as u32 == SDL_BUTTON_LEFT {
+					self.x = button.x;
+					self.y = button.y;
+					self.trigger = button.down;
+					self.on_screen = true;
+				}
+			}
+			SDL_EventType_SDL_EVENT_WINDOW_MOUSE_LEAVE if self.finger.is_none() => {
+				self.on_screen = false;
+			}
+			SDL_EventType_SDL_EVENT_FINGER_DOWN | SDL_EventType_SDL_EVENT_FINGER_MOTION => {
+				let finger = unsafe { event.tfinger };
+				if *self.finger.get_or_insert(finger.fingerID) == finger.fingerID {
+					// its position is a fraction of the window's size
+					self.x = finger.x * window.get_width() as f32;
+					self.y = finger.y * window.get_height() as f32;
+					self.trigger = true;
+					self.on_screen = true;
+				}
+			}
+			SDL_EventType_SDL_EVENT_FINGER_UP
+				if self.finger == Some(unsafe { event.tfinger }.fingerID) =>
+			{
+				// a lifted finger isn't pointing anywhere anymore
+				self.finger = None;
+				self.trigger = false;
+				self.on_screen = false;
+			}
+			_ => {}
+		}
+	}
+}
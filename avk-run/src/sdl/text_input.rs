@@ -0,0 +1,290 @@
+//! Text entry, for ROMs asking for names and the like.
+//! While the ROM has text input on, the keyboard types instead of pressing the players' inputs:
+//! Backspace comes through as `'\x08'` and Return as `'\n'`. Players without a keyboard get an
+//! on-screen keyboard as soon as they press a gamepad button, which then takes the gamepads
+//! over: the D-pad picks a key, South types it, East deletes and Start is Return.
+
+use crate::sdl::bindings::Button;
+use crate::sdl::sys::*;
+use crate::sdl::window::Window;
+use crate::sdl::Keycode;
+use avk_types::{MAX_TEXT_INPUT, RESOLUTION_HEIGHT, RESOLUTION_WIDTH};
+
+const COLUMNS: usize = 10;
+const ROWS: usize = 5;
+/// The character keys, from the top row down. The bottom row holds the wider keys.
+const LAYOUT: [&[u8; COLUMNS]; ROWS - 1] =
+	[b"1234567890", b"QWERTYUIOP", b"ASDFGHJKL-", b"ZXCVBNM.,!"];
+
+const KEY_WIDTH: usize = 20;
+const KEY_HEIGHT: usize = 14;
+/// The bottom left corner of the keyboard, in pixels from the bottom left of the screen.
+const KEYBOARD_X: usize = (RESOLUTION_WIDTH as usize - COLUMNS * KEY_WIDTH) / 2;
+const KEYBOARD_Y: usize = 8;
+const MARGIN: usize = 4;
+
+const PANEL_COLOR: [u8; 4] = [0, 0, 0, 192];
+const KEY_COLOR: [u8; 4] = [64, 64, 80, 255];
+const SELECTED_COLOR: [u8; 4] = [255, 208, 64, 255];
+const LABEL_COLOR: [u8; 4] = [255, 255, 255, 255];
+const SELECTED_LABEL_COLOR: [u8; 4] = [0, 0, 0, 255];
+
+/// The characters the font has glyphs for, in the order of [`FONT`].
+const GLYPHS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-.,!";
+/// 5x7 glyphs, one byte per row from the top down, with the leftmost pixel in bit 4.
+const FONT: [[u8; 7]; 40] = [
+	[0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+	[0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+	[0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+	[0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+	[0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+	[0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+	[0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+	[0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+	[0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+	[0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+	[0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+	[0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+	[0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+	[0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c],
+	[0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+	[0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+	[0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+	[0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+	[0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+	[0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+	[0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+	[0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+	[0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+	[0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+	[0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+	[0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+	[0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+	[0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+	[0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+	[0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+	[0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+	[0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+	[0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+	[0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+	[0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04],
+	[0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+	[0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+	[0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08],
+	[0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+];
+
+/// A key of the on-screen keyboard.
+#[derive(Copy, Clone)]
+enum Key {
+	Char(u8),
+	Space,
+	Delete,
+	Done,
+}
+
+impl Key {
+	fn at(row: usize, column: usize) -> Self {
+		match (LAYOUT.get(row), column) {
+			(Some(keys), _) => Key::Char(keys[column]),
+			(None, 0..6) => Key::Space,
+			(None, 6..8) => Key::Delete,
+			(None, _) => Key::Done,
+		}
+	}
+
+	/// Returns the first column the key at a position takes, and how many it takes.
+	fn span(row: usize, column: usize) -> (usize, usize) {
+		match Key::at(row, column) {
+			Key::Char(_) => (column, 1),
+			Key::Space => (0, 6),
+			Key::Delete => (6, 2),
+			Key::Done => (8, 2),
+		}
+	}
+
+	fn label(&self) -> &[u8] {
+		match self {
+			Key::Char(char) => std::slice::from_ref(char),
+			Key::Space => b"SPACE",
+			Key::Delete => b"DEL",
+			Key::Done => b"OK",
+		}
+	}
+
+	fn text(&self) -> char {
+		match self {
+			Key::Char(char) => *char as char,
+			Key::Space => ' ',
+			Key::Delete => '\x08',
+			Key::Done => '\n',
+		}
+	}
+}
+
+pub struct TextInput {
+	enabled: bool,
+	/// Typed since the text was last taken.
+	text: String,
+	/// The row and column picked on the on-screen keyboard, while it's shown.
+	keyboard: Option<(usize, usize)>,
+	/// The on-screen keyboard as RGBA pixels, from the bottom row up.
+	overlay: Vec<u8>,
+}
+
+impl TextInput {
+	pub fn new() -> Self {
+		Self {
+			enabled: false,
+			text: String::new(),
+			keyboard: None,
+			overlay: vec![0; RESOLUTION_WIDTH as usize * RESOLUTION_HEIGHT as usize * 4],
+		}
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		self.enabled
+	}
+
+	/// Turns text input on or off, making SDL send text events, and IMEs show up, only when on.
+	pub fn set_enabled(&mut self, window: &Window, enabled: bool) {
+		if enabled == self.enabled {
+			return;
+		}
+		self.enabled = enabled;
+		self.keyboard = None;
+		unsafe {
+			if enabled {
+				SDL_StartTextInput(window.sdl_window);
+			} else {
+				SDL_StopTextInput(window.sdl_window);
+				self.text.clear();
+			}
+		}
+	}
+
+	/// Adds text typed on the keyboard. Typing on the keyboard hides the on-screen one.
+	pub fn push_text(&mut self, text: &str) {
+		if self.enabled {
+			self.text.push_str(text);
+			self.keyboard = None;
+		}
+	}
+
+	/// Types the keys that don't make text events.
+	pub fn push_key(&mut self, key: Keycode) {
+		match key {
+			Keycode::Backspace => self.push_text("\x08"),
+			Keycode::Return | Keycode::KpEnter => self.push_text("\n"),
+			_ => {}
+		}
+	}
+
+	/// Takes what was typed since the last call, up to `MAX_TEXT_INPUT` bytes. Anything past
+	/// that is kept for the next call.
+	pub fn take_text(&mut self) -> String {
+		let mut len = self.text.len().min(MAX_TEXT_INPUT);
+		while !self.text.is_char_boundary(len) {
+			len -= 1;
+		}
+		let rest = self.text.split_off(len);
+		std::mem::replace(&mut self.text, rest)
+	}
+
+	/// Handles a gamepad button pressed while text input is on. The first press brings up the
+	/// on-screen keyboard, the next ones use it.
+	pub fn press_button(&mut self, button: Button) {
+		let Some((row, column)) = self.keyboard else {
+			self.keyboard = Some((0, 0));
+			self.draw_keyboard();
+			return;
+		};
+		let (first, width) = Key::span(row, column);
+		self.keyboard = Some(match button {
+			Button::DpadUp => ((row + ROWS - 1) % ROWS, column),
+			Button::DpadDown => ((row + 1) % ROWS, column),
+			Button::DpadLeft => (row, (first + COLUMNS - 1) % COLUMNS),
+			Button::DpadRight => (row, (first + width) % COLUMNS),
+			_ => {
+				let typed = match button {
+					Button::South => Key::at(row, column).text(),
+					Button::East => '\x08',
+					Button::Start => '\n',
+					_ => return,
+				};
+				self.text.push(typed);
+				return;
+			}
+		});
+		self.draw_keyboard();
+	}
+
+	/// Returns the on-screen keyboard as RGBA pixels covering the screen, from the bottom row
+	/// up, or None if it isn't shown.
+	pub fn keyboard_overlay(&self) -> Option<&[u8]> {
+		self.keyboard.map(|_| &self.overlay[..])
+	}
+
+	fn draw_keyboard(&mut self) {
+		let Some((selected_row, selected_column)) = self.keyboard else {
+			return;
+		};
+		let selected = Key::span(selected_row, selected_column);
+		self.overlay.fill(0);
+		self.fill(
+			KEYBOARD_X - MARGIN,
+			KEYBOARD_Y - MARGIN,
+			COLUMNS * KEY_WIDTH + MARGIN * 2,
+			ROWS * KEY_HEIGHT + MARGIN * 2,
+			PANEL_COLOR,
+		);
+		for row in 0..ROWS {
+			let mut column = 0;
+			while column < COLUMNS {
+				let (first, width) = Key::span(row, column);
+				let is_selected = row == selected_row && (first, width) == selected;
+				let (color, label_color) = match is_selected {
+					true => (SELECTED_COLOR, SELECTED_LABEL_COLOR),
+					false => (KEY_COLOR, LABEL_COLOR),
+				};
+				// the top row is drawn highest
+				let x = KEYBOARD_X + first * KEY_WIDTH;
+				let y = KEYBOARD_Y + (ROWS - 1 - row) * KEY_HEIGHT;
+				self.fill(x + 1, y + 1, width * KEY_WIDTH - 2, KEY_HEIGHT - 2, color);
+
+				let key = Key::at(row, column);
+				let label = key.label();
+				let label_x = x + (width * KEY_WIDTH - (label.len() * 6 - 1)) / 2;
+				for (i, &char) in label.iter().enumerate() {
+					self.draw_glyph(label_x + i * 6, y + (KEY_HEIGHT - 7) / 2, char, label_color);
+				}
+				column = first + width;
+			}
+		}
+	}
+
+	fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: [u8; 4]) {
+		for y in y..y + height {
+			let row = y * RESOLUTION_WIDTH as usize;
+			for pixel in self.overlay[(row + x) * 4..(row + x + width) * 4].chunks_exact_mut(4) {
+				pixel.copy_from_slice(&color);
+			}
+		}
+	}
+
+	/// Draws a glyph with its bottom left corner at `x`, `y`.
+	fn draw_glyph(&mut self, x: usize, y: usize, char: u8, color: [u8; 4]) {
+		let Some(index) = GLYPHS.iter().position(|&glyph| glyph == char) else {
+			return;
+		};
+		for (i, bits) in FONT[index].iter().enumerate() {
+			for column in 0..5 {
+				if bits & (0x10 >> column) != 0 {
+					// the glyph's rows go from the top down
+					self.fill(x + column, y + 6 - i, 1, 1, color);
+				}
+			}
+		}
+	}
+}
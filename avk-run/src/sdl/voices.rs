@@ -0,0 +1,243 @@
+//! Shares the PSG's channels between the music and sound effects.
+//!
+//! Sound effects are songs played over the music. An effect takes the channels it has notes on
+//! for as long as it plays, while the music carries on without being heard on them. Once the
+//! effect is over, the channels are handed back with the music's registers restored.
+
+use crate::sdl::audio::Psg;
+use crate::sdl::music::{LoadedSong, Sequencer};
+use avk_types::prelude::*;
+
+/// The registers restored when the music gets a channel back, in the order they're written.
+const RESTORED_REGISTERS: [AudioRegister; 6] = [
+	AudioRegister::Duty,
+	AudioRegister::Envelope,
+	AudioRegister::Sweep,
+	AudioRegister::Volume,
+	AudioRegister::Frequency,
+	AudioRegister::Trigger,
+];
+
+/// A channel's registers, indexed by register.
+type Registers = [u16; AudioRegister::Trigger as usize + 1];
+
+/// Who is heard on a PSG channel.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Owner {
+	Music,
+	/// The effect in that slot.
+	Effect(usize),
+}
+
+struct Effect {
+	sequencer: Sequencer,
+	priority: u8,
+}
+
+/// The PSG, the music and the sound effects playing over it.
+pub struct Voices {
+	psg: Psg,
+	music: Sequencer,
+	/// Every effect has a channel of its own, so there can't be more of them than channels.
+	effects: [Option<Effect>; AudioChannel::COUNT],
+	owners: [Owner; AudioChannel::COUNT],
+	/// The registers the music last wrote to each channel.
+	music_registers: [Registers; AudioChannel::COUNT],
+	sfx_volume: u8,
+}
+
+impl Voices {
+	pub fn new() -> Self {
+		Self {
+			psg: Psg::new(),
+			music: Sequencer::new(),
+			effects: [const { None }; AudioChannel::COUNT],
+			owners: [Owner::Music; AudioChannel::COUNT],
+			music_registers: [Registers::default(); AudioChannel::COUNT],
+			sfx_volume: u8::MAX,
+		}
+	}
+
+	/// Writes a register directly, as the ROM asked to with `Avk::audio_write`.
+	pub fn write(&mut self, channel: AudioChannel, register: AudioRegister, value: u16) {
+		self.psg.write(channel, register, value);
+	}
+
+	/// Starts playing an encoded song as the music. Returns false if it isn't a valid song.
+	pub fn play_music(&mut self, data: &[u8]) -> bool {
+		let Some(song) = LoadedSong::parse(data) else {
+			return false;
+		};
+		self.stop_music();
+		self.music.play(song);
+		true
+	}
+
+	pub fn stop_music(&mut self) {
+		let Self {
+			psg,
+			music,
+			owners,
+			music_registers,
+			..
+		} = self;
+		music.stop(&mut |channel, register, value| {
+			write_music(psg, owners, music_registers, channel, register, value)
+		});
+	}
+
+	pub fn set_music_volume(&mut self, volume: u8) {
+		self.music.set_volume(volume);
+	}
+
+	/// Starts playing an encoded song as a sound effect, on the channels it has notes on.
+	/// Effects already playing on them are stopped, unless one of them has a higher priority,
+	/// in which case the new effect isn't played.
+	/// Returns false if it isn't a valid song.
+	pub fn play_effect(&mut self, data: &[u8], priority: u8) -> bool {
+		let Some(song) = LoadedSong::parse(data) else {
+			return false;
+		};
+		let channels = song.used_channels();
+		let overlapping = || {
+			channels
+				.iter()
+				.filter_map(|&channel| match self.owners[channel as usize] {
+					Owner::Effect(slot) => Some(slot),
+					Owner::Music => None,
+				})
+		};
+		if overlapping().any(|slot| self.effects[slot].as_ref().unwrap().priority > priority) {
+			return true;
+		}
+		let overlapping: Vec<usize> = overlapping().collect();
+		for slot in overlapping {
+			self.stop_effect(slot);
+		}
+		if channels.is_empty() {
+			return true;
+		}
+
+		// every effect left has a channel the new one doesn't use, so there's a free slot
+		let slot = self.effects.iter().position(Option::is_none).unwrap();
+		let mut sequencer = Sequencer::new();
+		sequencer.play(song);
+		sequencer.set_volume(self.sfx_volume);
+		self.effects[slot] = Some(Effect {
+			sequencer,
+			priority,
+		});
+		for channel in channels {
+			self.owners[channel as usize] = Owner::Effect(slot);
+		}
+		true
+	}
+
+	pub fn stop_effects(&mut self) {
+		for slot in 0..self.effects.len() {
+			self.stop_effect(slot);
+		}
+	}
+
+	pub fn set_sfx_volume(&mut self, volume: u8) {
+		self.sfx_volume = volume;
+		for effect in self.effects.iter_mut().flatten() {
+			effect.sequencer.set_volume(volume);
+		}
+	}
+
+	/// Fills `out` with the next mono samples, playing the rows of the songs that fall within it.
+	pub fn render(&mut self, mut out: &mut [f32]) {
+		while !out.is_empty() {
+			if self.music.countdown() == Some(0) {
+				let Self {
+					psg,
+					music,
+					owners,
+					music_registers,
+					..
+				} = self;
+				music.play_row(&mut |channel, register, value| {
+					write_music(psg, owners, music_registers, channel, register, value)
+				});
+			}
+			for slot in 0..self.effects.len() {
+				let Some(effect) = &mut self.effects[slot] else {
+					continue;
+				};
+				if effect.sequencer.countdown() == Some(0) {
+					let (psg, owners) = (&mut self.psg, &self.owners);
+					effect.sequencer.play_row(&mut |channel, register, value| {
+						if owners[channel as usize] == Owner::Effect(slot) {
+							psg.write(channel, register, value);
+						}
+					});
+					if !effect.sequencer.is_playing() {
+						self.release(slot);
+					}
+				}
+			}
+
+			// every song playing is at least a sample away from its next row now
+			let len = self
+				.effects
+				.iter()
+				.flatten()
+				.filter_map(|effect| effect.sequencer.countdown())
+				.chain(self.music.countdown())
+				.fold(out.len(), usize::min);
+			let (now, rest) = out.split_at_mut(len);
+			self.psg.render(now);
+			self.music.advance(len);
+			for effect in self.effects.iter_mut().flatten() {
+				effect.sequencer.advance(len);
+			}
+			out = rest;
+		}
+	}
+
+	/// Stops the effect in a slot, if there's one, and hands its channels back to the music.
+	fn stop_effect(&mut self, slot: usize) {
+		let Some(effect) = &mut self.effects[slot] else {
+			return;
+		};
+		let (psg, owners) = (&mut self.psg, &self.owners);
+		effect.sequencer.stop(&mut |channel, register, value| {
+			if owners[channel as usize] == Owner::Effect(slot) {
+				psg.write(channel, register, value);
+			}
+		});
+		self.release(slot);
+	}
+
+	/// Frees the slot of an effect that's over, and hands its channels back to the music.
+	fn release(&mut self, slot: usize) {
+		self.effects[slot] = None;
+		for channel in AudioChannel::ALL {
+			if self.owners[channel as usize] != Owner::Effect(slot) {
+				continue;
+			}
+			self.owners[channel as usize] = Owner::Music;
+			// picks the music's note back up, or silences the channel if there isn't one
+			for register in RESTORED_REGISTERS {
+				let value = self.music_registers[channel as usize][register as usize];
+				self.psg.write(channel, register, value);
+			}
+		}
+	}
+}
+
+/// Makes a write of the music's, which is only heard if no effect has taken the channel.
+fn write_music(
+	psg: &mut Psg,
+	owners: &[Owner; AudioChannel::COUNT],
+	music_registers: &mut [Registers; AudioChannel::COUNT],
+	channel: AudioChannel,
+	register: AudioRegister,
+	value: u16,
+) {
+	music_registers[channel as usize][register as usize] = value;
+	if owners[channel as usize] == Owner::Music {
+		psg.write(channel, register, value);
+	}
+}
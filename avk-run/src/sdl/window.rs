@@ -1,13 +1,21 @@
 use crate::sdl::sys::*;
 use crate::sdl::{panic_sdl_error, set_sdl_prop, SdlProperty};
-use std::ffi::{c_char, c_int};
+use avk_types::prelude::*;
+use avk_types::u16_to_rgba;
+use log::warn;
+use std::ffi::{c_char, c_int, c_void};
 use std::ptr::null_mut;
 
 #[derive(Copy, Clone)]
 pub struct Window {
 	pub(super) sdl_window: *mut SDL_Window,
+	/// In the units of mouse positions, which are points rather than pixels on high DPI displays.
+	/// Kept up to date by the resize events.
 	pub(super) width: u32,
 	pub(super) height: u32,
+	/// The size of the drawable area, in pixels.
+	pub(super) pixel_width: u32,
+	pub(super) pixel_height: u32,
 	pub(super) _gl_context: SDL_GLContext,
 }
 
@@ -63,11 +71,18 @@ impl Window {
 			// -1 for adaptive vsync
 			SDL_GL_SetSwapInterval(1);
 
+			let (mut pixel_width, mut pixel_height) = (width, height);
+			if !SDL_GetWindowSizeInPixels(sdl_window, &mut pixel_width, &mut pixel_height) {
+				warn!("Failed to get the window's size in pixels!");
+			}
+
 			Window {
 				sdl_window,
 				_gl_context: gl_context,
 				width: width as u32,
 				height: height as u32,
+				pixel_width: pixel_width as u32,
+				pixel_height: pixel_height as u32,
 			}
 		}
 	}
@@ -105,10 +120,50 @@ impl Window {
 		self.height
 	}
 
+	/// Returns the size of the drawable area, in pixels, for the GL viewport.
+	#[inline]
+	pub fn get_pixel_size(&self) -> (u32, u32) {
+		(self.pixel_width, self.pixel_height)
+	}
+
 	#[inline]
 	pub fn set_title(&mut self, title: impl Into<String>) {
+		let title = title.into() + "\0";
+		unsafe {
+			SDL_SetWindowTitle(self.sdl_window, title.as_ptr() as *const c_char);
+		}
+	}
+
+	/// Sets the window icon. Does nothing if the icon is empty.
+	pub fn set_icon(&mut self, icon: &Icon) {
+		let size = icon.pixel_size();
+		if size == 0 {
+			return;
+		}
+
+		let mut pixels = Vec::with_capacity(size * size * 4);
+		for y in 0..size {
+			for x in 0..size {
+				// scale the 4 bit channels up to 8 bits
+				pixels.extend(u16_to_rgba(icon.get_pixel(x, y)).map(|channel| channel * 17));
+			}
+		}
+
 		unsafe {
-			SDL_SetWindowTitle(self.sdl_window, title.into().as_ptr() as *const c_char);
+			let surface = SDL_CreateSurfaceFrom(
+				size as c_int,
+				size as c_int,
+				SDL_PixelFormat_SDL_PIXELFORMAT_RGBA32,
+				pixels.as_mut_ptr() as *mut c_void,
+				size as c_int * 4,
+			);
+			if surface.is_null() {
+				warn!("Failed to create window icon!");
+				return;
+			}
+			// SDL copies the icon, so the surface can go right away
+			SDL_SetWindowIcon(self.sdl_window, surface);
+			SDL_DestroySurface(surface);
 		}
 	}
 }
@@ -0,0 +1,679 @@
+//! Runs `.wasm` ROMs in a wasmi interpreter instead of loading them into the process.
+//! The ROM imports the AVK calls from the "avk" module and keeps its AvkRaw in linear memory;
+//! the runner copies the frame out of it every update.
+
+use crate::c_binds::{
+	avk_audio_write, avk_drop, avk_get_axis, avk_get_input, avk_get_pointer, avk_get_time,
+	avk_held_frames, avk_init, avk_is_connected, avk_just_released, avk_play_effect,
+	avk_play_music, avk_play_sample, avk_read_text, avk_register_sample, avk_report_panic,
+	avk_rumble, avk_save_read, avk_save_write, avk_set_deadzone, avk_set_music_volume,
+	avk_set_sfx_volume, avk_set_text_input, avk_stop_effects, avk_stop_music, avk_stop_samples,
+	avk_update,
+};
+use crate::frame::{self, FrameRom};
+use crate::metadata;
+use avk_types::prelude::*;
+use avk_types::{
+	AvkRaw, MAX_IMAGES, MAX_PALETTES, WASM_RAW_BACKGROUND_OFFSET, WASM_RAW_FOREGROUND_OFFSET,
+	WASM_RAW_PAN_X_OFFSET, WASM_RAW_PAN_Y_OFFSET, WASM_RAW_SIZE,
+};
+use log::error;
+use std::mem::size_of;
+use std::path::Path;
+use std::ptr::{self, null_mut};
+use wasmi::{Caller, Engine, Error, Extern, Instance, Linker, Memory, Module, Store, TypedFunc};
+
+const PALETTE_SIZE: usize = 16 * size_of::<u16>();
+
+/// State owned by the wasmi store.
+struct WasmHost {
+	/// The runner-side AvkRaw, null until the ROM calls `init`.
+	raw: *mut AvkRaw,
+	/// Where the ROM keeps its AvkRaw in linear memory.
+	guest_raw: i32,
+}
+
+/// Fetches the ROM's exported linear memory.
+fn get_memory(caller: &Caller<'_, WasmHost>) -> Result<Memory, Error> {
+	match caller.get_export("memory") {
+		Some(Extern::Memory(memory)) => Ok(memory),
+		_ => Err(Error::new("ROM does not export its memory!")),
+	}
+}
+
+/// Returns `len` bytes of guest memory starting at `ptr`, or traps if they're out of bounds.
+fn guest_slice(memory: &[u8], ptr: i32, len: usize) -> Result<&[u8], Error> {
+	let start = ptr as u32 as usize;
+	start
+		.checked_add(len)
+		.and_then(|end| memory.get(start..end))
+		.ok_or_else(|| Error::new("ROM passed an out-of-bounds pointer!"))
+}
+
+/// Mutable version of [`guest_slice`].
+fn guest_slice_mut(memory: &mut [u8], ptr: i32, len: usize) -> Result<&mut [u8], Error> {
+	let start = ptr as u32 as usize;
+	start
+		.checked_add(len)
+		.and_then(|end| memory.get_mut(start..end))
+		.ok_or_else(|| Error::new("ROM passed an out-of-bounds pointer!"))
+}
+
+/// Returns the runner-side AvkRaw, or traps if the ROM hasn't called `init` yet.
+fn host_raw(caller: &Caller<'_, WasmHost>) -> Result<*mut AvkRaw, Error> {
+	let raw = caller.data().raw;
+	if raw.is_null() {
+		Err(Error::new("AVK has not been initialized!"))
+	} else {
+		Ok(raw)
+	}
+}
+
+fn wasm_init(
+	mut caller: Caller<'_, WasmHost>,
+	guest_raw: i32,
+	images: i32,
+	palettes: i32,
+) -> Result<(), Error> {
+	if !caller.data().raw.is_null() {
+		return Err(Error::new("AVK has already been initialized!"));
+	}
+
+	let memory = get_memory(&caller)?;
+	let data = memory.data(&caller);
+	guest_slice(data, guest_raw, WASM_RAW_SIZE)?;
+
+	let image_data = guest_slice(data, images, MAX_IMAGES * Image::PIXEL_COUNT)?;
+	let mut host_images = vec![Image::empty(); MAX_IMAGES];
+	for (image, pixels) in host_images
+		.iter_mut()
+		.zip(image_data.chunks_exact(Image::PIXEL_COUNT))
+	{
+		image.0.copy_from_slice(pixels);
+	}
+
+	let palette_data = guest_slice(data, palettes, MAX_PALETTES * PALETTE_SIZE)?;
+	let mut host_palettes = vec![Palette::empty(); MAX_PALETTES];
+	for (palette, colors) in host_palettes
+		.iter_mut()
+		.zip(palette_data.chunks_exact(PALETTE_SIZE))
+	{
+		for (color, bytes) in palette.0.iter_mut().zip(colors.chunks_exact(2)) {
+			*color = u16::from_le_bytes([bytes[0], bytes[1]]);
+		}
+	}
+
+	caller.data_mut().raw = avk_init(host_images.as_ptr(), host_palettes.as_ptr());
+	caller.data_mut().guest_raw = guest_raw;
+	Ok(())
+}
+
+fn wasm_drop(mut caller: Caller<'_, WasmHost>, _guest_raw: i32) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	avk_drop(raw);
+	caller.data_mut().raw = null_mut();
+	Ok(())
+}
+
+/// Copies the ROM's frame out of linear memory into the runner-side AvkRaw.
+fn copy_frame(memory: &[u8], guest_raw: i32, raw: *mut AvkRaw) -> Result<(), Error> {
+	let frame = guest_slice(memory, guest_raw, WASM_RAW_SIZE)?;
+	unsafe {
+		let raw = &mut *raw;
+		let background = &frame[WASM_RAW_BACKGROUND_OFFSET..WASM_RAW_FOREGROUND_OFFSET];
+		for (tile, bytes) in raw.background.iter_mut().zip(background.chunks_exact(2)) {
+			tile.image_id = bytes[0];
+			tile.palette_id = bytes[1];
+		}
+		let foreground = &frame[WASM_RAW_FOREGROUND_OFFSET..WASM_RAW_PAN_X_OFFSET];
+		for (sprite, bytes) in raw
+			.foreground
+			.iter_mut()
+			.zip(foreground.chunks_exact(size_of::<Sprite>()))
+		{
+			sprite.image_id = bytes[0];
+			sprite.palette_transform = bytes[1];
+			sprite.x = i16::from_le_bytes([bytes[2], bytes[3]]);
+			sprite.y = i16::from_le_bytes([bytes[4], bytes[5]]);
+		}
+		raw.pan_x = frame[WASM_RAW_PAN_X_OFFSET] as i8;
+		raw.pan_y = frame[WASM_RAW_PAN_Y_OFFSET] as i8;
+	}
+	Ok(())
+}
+
+fn wasm_update(caller: Caller<'_, WasmHost>, guest_raw: i32) -> Result<i32, Error> {
+	let raw = host_raw(&caller)?;
+	let memory = get_memory(&caller)?;
+	copy_frame(memory.data(&caller), guest_raw, raw)?;
+	Ok(avk_update(raw) as i32)
+}
+
+fn wasm_get_time(caller: Caller<'_, WasmHost>, _guest_raw: i32) -> Result<i64, Error> {
+	let raw = host_raw(&caller)?;
+	Ok(avk_get_time(raw) as i64)
+}
+
+/// Decodes one of the C enums passed by the guest, or returns None if it's invalid.
+fn guest_enum<T: TryFrom<u32>>(value: i32) -> Option<T> {
+	T::try_from(value as u32).ok()
+}
+
+/// Decodes a player and an input passed by the guest, or returns None if either is invalid.
+fn guest_input(player: i32, input: i32) -> Option<(Player, AvkGamepadInput)> {
+	Some((guest_enum(player)?, guest_enum(input)?))
+}
+
+/// Decodes a player and an axis passed by the guest, or returns None if either is invalid.
+fn guest_axis(player: i32, axis: i32) -> Option<(Player, AvkGamepadAxis)> {
+	Some((guest_enum(player)?, guest_enum(axis)?))
+}
+
+fn wasm_get_input(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	player: i32,
+	input: i32,
+) -> Result<i32, Error> {
+	let raw = host_raw(&caller)?;
+	let Some((player, input)) = guest_input(player, input) else {
+		return Ok(0);
+	};
+	Ok(avk_get_input(raw, player, input) as i32)
+}
+
+fn wasm_held_frames(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	player: i32,
+	input: i32,
+) -> Result<i32, Error> {
+	let raw = host_raw(&caller)?;
+	let Some((player, input)) = guest_input(player, input) else {
+		return Ok(0);
+	};
+	Ok(avk_held_frames(raw, player, input) as i32)
+}
+
+fn wasm_just_released(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	player: i32,
+	input: i32,
+) -> Result<i32, Error> {
+	let raw = host_raw(&caller)?;
+	let Some((player, input)) = guest_input(player, input) else {
+		return Ok(0);
+	};
+	Ok(avk_just_released(raw, player, input) as i32)
+}
+
+fn wasm_is_connected(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	player: i32,
+) -> Result<i32, Error> {
+	let raw = host_raw(&caller)?;
+	let Some(player) = guest_enum(player) else {
+		return Ok(0);
+	};
+	Ok(avk_is_connected(raw, player) as i32)
+}
+
+fn wasm_rumble(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	player: i32,
+	low: i32,
+	high: i32,
+	duration_ms: i32,
+) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	if let Some(player) = guest_enum(player) {
+		avk_rumble(raw, player, low as u16, high as u16, duration_ms as u32);
+	}
+	Ok(())
+}
+
+fn wasm_get_pointer(
+	mut caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	player: i32,
+	pointer: i32,
+) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	let mut host_pointer = AvkPointer::default();
+	if let Some(player) = guest_enum(player) {
+		avk_get_pointer(raw, player, &mut host_pointer);
+	}
+	let memory = get_memory(&caller)?;
+	let pointer = guest_slice_mut(
+		memory.data_mut(&mut caller),
+		pointer,
+		size_of::<AvkPointer>(),
+	)?;
+	// the guest's pointer may not be aligned
+	unsafe { ptr::write_unaligned(pointer.as_mut_ptr() as *mut AvkPointer, host_pointer) };
+	Ok(())
+}
+
+fn wasm_set_text_input(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	enabled: i32,
+) -> Result<(), Error> {
+	avk_set_text_input(host_raw(&caller)?, enabled != 0);
+	Ok(())
+}
+
+fn wasm_read_text(
+	mut caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	buf: i32,
+	len: i32,
+) -> Result<i32, Error> {
+	let raw = host_raw(&caller)?;
+	let memory = get_memory(&caller)?;
+	let buf = guest_slice_mut(memory.data_mut(&mut caller), buf, len as u32 as usize)?;
+	Ok(avk_read_text(raw, buf.as_mut_ptr(), buf.len()) as i32)
+}
+
+fn wasm_get_axis(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	player: i32,
+	axis: i32,
+) -> Result<f32, Error> {
+	let raw = host_raw(&caller)?;
+	let Some((player, axis)) = guest_axis(player, axis) else {
+		return Ok(0.0);
+	};
+	Ok(avk_get_axis(raw, player, axis))
+}
+
+fn wasm_set_deadzone(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	player: i32,
+	axis: i32,
+	deadzone: f32,
+) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	if let Some((player, axis)) = guest_axis(player, axis) {
+		avk_set_deadzone(raw, player, axis, deadzone);
+	}
+	Ok(())
+}
+
+fn wasm_save_read(
+	mut caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	offset: i32,
+	buf: i32,
+	len: i32,
+) -> Result<i32, Error> {
+	let raw = host_raw(&caller)?;
+	let memory = get_memory(&caller)?;
+	let buf = guest_slice_mut(memory.data_mut(&mut caller), buf, len as u32 as usize)?;
+	Ok(avk_save_read(raw, offset as u32 as usize, buf.as_mut_ptr(), buf.len()) as i32)
+}
+
+fn wasm_save_write(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	offset: i32,
+	data: i32,
+	len: i32,
+) -> Result<i32, Error> {
+	let raw = host_raw(&caller)?;
+	let memory = get_memory(&caller)?;
+	let data = guest_slice(memory.data(&caller), data, len as u32 as usize)?;
+	Ok(avk_save_write(raw, offset as u32 as usize, data.as_ptr(), data.len()) as i32)
+}
+
+fn wasm_audio_write(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	channel: i32,
+	register: i32,
+	value: i32,
+) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	let (Some(channel), Some(register)) = (guest_enum(channel), guest_enum(register)) else {
+		return Ok(());
+	};
+	avk_audio_write(raw, channel, register, value as u16);
+	Ok(())
+}
+
+fn wasm_play_music(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	data: i32,
+	len: i32,
+) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	let memory = get_memory(&caller)?;
+	let data = guest_slice(memory.data(&caller), data, len as u32 as usize)?;
+	avk_play_music(raw, data.as_ptr(), data.len());
+	Ok(())
+}
+
+fn wasm_stop_music(caller: Caller<'_, WasmHost>, _guest_raw: i32) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	avk_stop_music(raw);
+	Ok(())
+}
+
+fn wasm_set_music_volume(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	volume: i32,
+) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	avk_set_music_volume(raw, volume as u8);
+	Ok(())
+}
+
+fn wasm_register_sample(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	id: i32,
+	data: i32,
+	len: i32,
+) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	let memory = get_memory(&caller)?;
+	let data = guest_slice(memory.data(&caller), data, len as u32 as usize)?;
+	avk_register_sample(raw, id as u8, data.as_ptr(), data.len());
+	Ok(())
+}
+
+fn wasm_play_sample(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	id: i32,
+	pitch: i32,
+	volume: i32,
+	pan: i32,
+) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	avk_play_sample(raw, id as u8, pitch as u16, volume as u8, pan as i8);
+	Ok(())
+}
+
+fn wasm_stop_samples(caller: Caller<'_, WasmHost>, _guest_raw: i32) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	avk_stop_samples(raw);
+	Ok(())
+}
+
+fn wasm_play_effect(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	data: i32,
+	len: i32,
+	priority: i32,
+) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	let memory = get_memory(&caller)?;
+	let data = guest_slice(memory.data(&caller), data, len as u32 as usize)?;
+	avk_play_effect(raw, data.as_ptr(), data.len(), priority as u8);
+	Ok(())
+}
+
+fn wasm_stop_effects(caller: Caller<'_, WasmHost>, _guest_raw: i32) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	avk_stop_effects(raw);
+	Ok(())
+}
+
+fn wasm_set_sfx_volume(
+	caller: Caller<'_, WasmHost>,
+	_guest_raw: i32,
+	volume: i32,
+) -> Result<(), Error> {
+	let raw = host_raw(&caller)?;
+	avk_set_sfx_volume(raw, volume as u8);
+	Ok(())
+}
+
+fn wasm_report_panic(caller: Caller<'_, WasmHost>, message: i32, len: i32) -> Result<(), Error> {
+	let memory = get_memory(&caller)?;
+	let message = guest_slice(memory.data(&caller), message, len as u32 as usize)?;
+	avk_report_panic(message.as_ptr(), message.len());
+	Ok(())
+}
+
+/// The per-frame entry points of a WebAssembly ROM.
+struct WasmFrameRom<'a> {
+	store: &'a mut Store<WasmHost>,
+	memory: Memory,
+	start: TypedFunc<(), ()>,
+	frame: TypedFunc<(), ()>,
+	stop: TypedFunc<(), ()>,
+}
+
+impl FrameRom for WasmFrameRom<'_> {
+	fn start(&mut self) -> bool {
+		if let Err(err) = self.start.call(&mut *self.store, ()) {
+			error!("ROM trapped in avk_start: {err}");
+			return false;
+		}
+		true
+	}
+
+	fn frame(&mut self) -> bool {
+		let result = self.frame.call(&mut *self.store, ()).and_then(|()| {
+			let WasmHost { raw, guest_raw } = *self.store.data();
+			copy_frame(self.memory.data(&*self.store), guest_raw, raw)
+		});
+		if let Err(err) = result {
+			error!("ROM trapped in avk_frame: {err}");
+			return false;
+		}
+		true
+	}
+
+	fn stop(&mut self) {
+		if let Err(err) = self.stop.call(&mut *self.store, ()) {
+			error!("ROM trapped in avk_stop: {err}");
+		}
+	}
+}
+
+/// Reads a static exported by the ROM, if it has one.
+/// Data exports are globals holding the address of the data in linear memory.
+/// `T` must not contain pointers, so it's laid out the same on wasm32.
+fn read_data_export<T: Copy>(
+	instance: &Instance,
+	store: &Store<WasmHost>,
+	name: &str,
+) -> Option<T> {
+	let address = instance.get_global(store, name)?.get(store).i32()?;
+	let memory = instance.get_memory(store, "memory")?;
+	let bytes = guest_slice(memory.data(store), address, size_of::<T>()).ok()?;
+	Some(unsafe { ptr::read_unaligned(bytes.as_ptr() as *const T) })
+}
+
+/// Loads a WebAssembly ROM and runs it until it returns or traps.
+pub fn run(rom_path: &Path) {
+	let wasm = std::fs::read(rom_path).unwrap();
+
+	let engine = Engine::default();
+	let module = Module::new(&engine, &wasm).unwrap();
+	let mut store = Store::new(
+		&engine,
+		WasmHost {
+			raw: null_mut(),
+			guest_raw: 0,
+		},
+	);
+
+	let mut linker = <Linker<WasmHost>>::new(&engine);
+	linker.func_wrap("avk", "init", wasm_init).unwrap();
+	linker.func_wrap("avk", "drop", wasm_drop).unwrap();
+	linker.func_wrap("avk", "update", wasm_update).unwrap();
+	linker.func_wrap("avk", "get_time", wasm_get_time).unwrap();
+	linker
+		.func_wrap("avk", "get_input", wasm_get_input)
+		.unwrap();
+	linker
+		.func_wrap("avk", "save_read", wasm_save_read)
+		.unwrap();
+	linker
+		.func_wrap("avk", "save_write", wasm_save_write)
+		.unwrap();
+	linker
+		.func_wrap("avk", "report_panic", wasm_report_panic)
+		.unwrap();
+	linker
+		.func_wrap("avk", "audio_write", wasm_audio_write)
+		.unwrap();
+	linker
+		.func_wrap("avk", "play_music", wasm_play_music)
+		.unwrap();
+	linker
+		.func_wrap("avk", "stop_music", wasm_stop_music)
+		.unwrap();
+	linker
+		.func_wrap("avk", "set_music_volume", wasm_set_music_volume)
+		.unwrap();
+	linker
+		.func_wrap("avk", "register_sample", wasm_register_sample)
+		.unwrap();
+	linker
+		.func_wrap("avk", "play_sample", wasm_play_sample)
+		.unwrap();
+	linker
+		.func_wrap("avk", "stop_samples", wasm_stop_samples)
+		.unwrap();
+	linker
+		.func_wrap("avk", "play_effect", wasm_play_effect)
+		.unwrap();
+	linker
+		.func_wrap("avk", "stop_effects", wasm_stop_effects)
+		.unwrap();
+	linker
+		.func_wrap("avk", "set_sfx_volume", wasm_set_sfx_volume)
+		.unwrap();
+	linker
+		.func_wrap("avk", "held_frames", wasm_held_frames)
+		.unwrap();
+	linker
+		.func_wrap("avk", "just_released", wasm_just_released)
+		.unwrap();
+	linker.func_wrap("avk", "get_axis", wasm_get_axis).unwrap();
+	linker
+		.func_wrap("avk", "is_connected", wasm_is_connected)
+		.unwrap();
+	linker.func_wrap("avk", "rumble", wasm_rumble).unwrap();
+	linker
+		.func_wrap("avk", "get_pointer", wasm_get_pointer)
+		.unwrap();
+	linker
+		.func_wrap("avk", "set_text_input", wasm_set_text_input)
+		.unwrap();
+	linker
+		.func_wrap("avk", "read_text", wasm_read_text)
+		.unwrap();
+	linker
+		.func_wrap("avk", "set_deadzone", wasm_set_deadzone)
+		.unwrap();
+
+	let instance = linker
+		.instantiate(&mut store, &module)
+		.and_then(|pre| pre.start(&mut store))
+		.unwrap();
+	if !metadata::check_abi_version(read_data_export(&instance, &store, "AVK_ABI_VERSION")) {
+		return;
+	}
+	metadata::set(
+		rom_path,
+		read_data_export(&instance, &store, "AVK_METADATA"),
+	);
+
+	let frame_funcs = (
+		instance.get_typed_func::<(), ()>(&store, "avk_start"),
+		instance.get_typed_func::<(), ()>(&store, "avk_frame"),
+		instance.get_typed_func::<(), ()>(&store, "avk_stop"),
+		instance.get_memory(&store, "memory"),
+	);
+	if let (Ok(start), Ok(frame), Ok(stop), Some(memory)) = frame_funcs {
+		frame::run(&mut WasmFrameRom {
+			store: &mut store,
+			memory,
+			start,
+			frame,
+			stop,
+		});
+	} else {
+		let main = instance
+			.get_typed_func::<(), ()>(&store, "avk_main")
+			.unwrap();
+
+		if let Err(err) = main.call(&mut store, ()) {
+			error!("ROM trapped: {err}");
+		}
+	}
+
+	// clean up after ROMs that trapped before dropping their Avk
+	let raw = store.data().raw;
+	if !raw.is_null() {
+		avk_drop(raw);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use avk_types::{BACKGROUND_CANVAS_SIZE, MAX_SPRITES};
+
+	#[test]
+	fn guest_slices_stay_in_bounds() {
+		let mut memory = [0; 16];
+		assert_eq!(guest_slice(&memory, 4, 12).unwrap().len(), 12);
+		assert_eq!(guest_slice(&memory, 16, 0).unwrap().len(), 0);
+		assert!(guest_slice(&memory, 4, 13).is_err());
+		assert!(guest_slice(&memory, 17, 0).is_err());
+		// negative pointers are past the end of any memory, not before its start
+		assert!(guest_slice(&memory, -1, 1).is_err());
+		assert!(guest_slice(&memory, 1, usize::MAX).is_err());
+		assert!(guest_slice_mut(&mut memory, 8, 9).is_err());
+		assert!(guest_slice_mut(&mut memory, i32::MIN, 0).is_err());
+		assert_eq!(guest_slice_mut(&mut memory, 8, 8).unwrap().len(), 8);
+	}
+
+	#[test]
+	fn copies_frames_out_of_guest_memory() {
+		let guest_raw = 8;
+		let mut memory = vec![0; guest_raw + WASM_RAW_SIZE];
+		let frame = &mut memory[guest_raw..];
+		frame[WASM_RAW_BACKGROUND_OFFSET..][..2].copy_from_slice(&[1, 2]);
+		frame[WASM_RAW_FOREGROUND_OFFSET - 2..][..2].copy_from_slice(&[3, 4]);
+		let last_sprite = WASM_RAW_PAN_X_OFFSET - size_of::<Sprite>();
+		frame[last_sprite..][..6].copy_from_slice(&[5, 6, 0x34, 0x12, 0xfe, 0xff]);
+		frame[WASM_RAW_PAN_X_OFFSET] = -3i8 as u8;
+		frame[WASM_RAW_PAN_Y_OFFSET] = 7;
+
+		let mut raw = Box::new(AvkRaw {
+			internal: null_mut(),
+			background: [Default::default(); BACKGROUND_CANVAS_SIZE],
+			foreground: [Default::default(); MAX_SPRITES],
+			pan_x: 0,
+			pan_y: 0,
+		});
+		copy_frame(&memory, guest_raw as i32, raw.as_mut()).unwrap();
+		let first = raw.background[0];
+		let last = raw.background[BACKGROUND_CANVAS_SIZE - 1];
+		assert_eq!((first.image_id, first.palette_id), (1, 2));
+		assert_eq!((last.image_id, last.palette_id), (3, 4));
+		let sprite = raw.foreground[MAX_SPRITES - 1];
+		assert_eq!((sprite.image_id, sprite.palette_transform), (5, 6));
+		assert_eq!((sprite.x, sprite.y), (0x1234, -2));
+		assert_eq!((raw.pan_x, raw.pan_y), (-3, 7));
+
+		// a frame running past the end of memory traps instead
+		assert!(copy_frame(&memory, guest_raw as i32 + 1, raw.as_mut()).is_err());
+	}
+}
@@ -1,122 +1,486 @@
 #ifndef AVK_H
 #define AVK_H
+
+// Generated from the avk crate by cbindgen. Do not edit, edit the Rust types instead.
+
+#include <stddef.h>
 #include <stdint.h>
 #include <stdbool.h>
-
 #define AVK_EXPORT __attribute__(( visibility("default") ))
 
-/// Square pixel size of sprites and tiles.
-static const int16_t IMAGE_SIZE = 16;
+// Square pixel size of sprites and tiles.
+#define IMAGE_SIZE 16
+
+// Tiles per row of the canvas.
+#define CANVAS_WIDTH 16
+
+// Tiles per column of the canvas.
+#define CANVAS_HEIGHT 12
+
+// The total tile count of the canvas.
+#define CANVAS_SIZE ((uintptr_t)CANVAS_WIDTH * (uintptr_t)CANVAS_HEIGHT)
+
+#define BACKGROUND_CANVAS_WIDTH (CANVAS_WIDTH + 2)
+
+#define BACKGROUND_CANVAS_HEIGHT (CANVAS_HEIGHT + 2)
+
+#define BACKGROUND_CANVAS_SIZE ((uintptr_t)BACKGROUND_CANVAS_WIDTH * (uintptr_t)BACKGROUND_CANVAS_HEIGHT)
+
+// Pixels per row of the canvas.
+#define RESOLUTION_WIDTH (IMAGE_SIZE * CANVAS_WIDTH)
+
+// Pixels per column of the canvas
+#define RESOLUTION_HEIGHT (IMAGE_SIZE * CANVAS_HEIGHT)
+
+// The total pixel count of the canvas.
+#define RESOLUTION_SIZE ((uintptr_t)RESOLUTION_WIDTH * (uintptr_t)RESOLUTION_HEIGHT)
+
+#define MAX_IMAGES 256
+
+#define MAX_PALETTES 16
+
+#define MAX_SPRITES 96
+
+// Version of the ABI between ROMs and the runner, bumped on incompatible changes and whenever
+// a call is added, since an older runner couldn't fill in the new call's function pointer.
+// ROMs export the version they were built against as `AVK_ABI_VERSION`.
+#define ABI_VERSION 2
+
+// Size of every ROM's persistent save block, in bytes.
+#define SAVE_SIZE 4096
+
+// The most text a ROM can get in a frame while text input is on, in bytes of UTF-8.
+#define MAX_TEXT_INPUT 64
+
+// Pixels per image.
+#define IMAGE_PIXEL_COUNT ((uintptr_t)IMAGE_SIZE * (uintptr_t)IMAGE_SIZE)
+
+#define METADATA_TITLE_LEN 32
+
+#define METADATA_AUTHOR_LEN 32
+
+#define METADATA_VERSION_LEN 16
+
+#define METADATA_ID_LEN 64
 
-/// Tiles per row of the canvas.
-static const int16_t CANVAS_WIDTH = 16;
+#define AvkGamepadInput_COUNT 11
 
-/// Tiles per column of the canvas.
-static const int16_t CANVAS_HEIGHT = 12;
+#define AvkGamepadAxis_COUNT 6
 
-/// The total tile count of the canvas.
-static const uintptr_t CANVAS_SIZE = ((uintptr_t)CANVAS_WIDTH * (uintptr_t)CANVAS_HEIGHT);
+// [`AudioRegister::Envelope`] flag to fade in instead of out.
+#define ENVELOPE_UP (1 << 4)
 
-static const int16_t BACKGROUND_CANVAS_WIDTH = (CANVAS_WIDTH + 2);
+// [`AudioRegister::Sweep`] flag to sweep the pitch down instead of up.
+#define SWEEP_DOWN (1 << 3)
 
-static const int16_t BACKGROUND_CANVAS_HEIGHT = (CANVAS_HEIGHT + 2);
+// Samples in the wave channel's wavetable.
+#define WAVETABLE_SIZE 32
 
-static const uintptr_t BACKGROUND_CANVAS_SIZE = ((uintptr_t)BACKGROUND_CANVAS_WIDTH * (uintptr_t)BACKGROUND_CANVAS_HEIGHT);
+#define AudioChannel_COUNT 4
 
-/// Pixels per row of the canvas.
-static const int16_t RESOLUTION_WIDTH = (IMAGE_SIZE * CANVAS_WIDTH);
+#define AudioRegister_COUNT 7
 
-/// Pixels per column of the canvas
-static const int16_t RESOLUTION_HEIGHT = (IMAGE_SIZE * CANVAS_HEIGHT);
+// Version of the song format.
+#define SONG_VERSION 1
 
-/// The total pixel count of the canvas.
-static const uintptr_t RESOLUTION_SIZE = ((uintptr_t)RESOLUTION_WIDTH * (uintptr_t)RESOLUTION_HEIGHT);
+// Largest song the runner accepts, in bytes.
+#define MAX_SONG_SIZE 65536
 
-static const uintptr_t MAX_IMAGES = 256;
+#define SONG_HEADER_SIZE 12
 
-static const uintptr_t MAX_PALETTES = 16;
+#define SONG_INSTRUMENT_SIZE 6
 
-static const uintptr_t MAX_SPRITES = 96;
+#define SONG_CELL_SIZE 3
 
-static const uintptr_t IMAGE_PIXEL_COUNT = ((uintptr_t)IMAGE_SIZE * (uintptr_t)IMAGE_SIZE);
+// Loop order of songs that stop at the end.
+#define NO_LOOP 255
 
-enum GamepadInput {
-    DirUp,
-    DirRight,
-    DirDown,
-    DirLeft,
+// Note of a cell that silences the channel.
+#define NOTE_OFF 255
 
-    FaceUp,
-    FaceRight,
-    FaceDown,
-    FaceLeft,
+// Volume of a cell that uses the instrument's volume.
+#define INSTRUMENT_VOLUME 255
 
-    TriggerLeft,
-    TriggerRight,
+// Version of the sample format.
+#define SAMPLE_VERSION 1
 
-    Menu,
-};
+// Largest sample the runner accepts, in bytes.
+#define MAX_SAMPLE_SIZE 262144
 
-enum Player {
-    Alpha = 0,
-    Bravo = 1,
-    Charlie = 2,
-    Delta = 3,
-};
+#define SAMPLE_HEADER_SIZE 16
+
+// Number of samples the runner keeps registered at a time.
+#define MAX_SAMPLES 256
+
+// Pitch that plays a sample at the rate it was recorded at. Pitches are 8.8 fixed point.
+#define SAMPLE_BASE_PITCH 256
+
+typedef enum Player {
+  Player_Alpha,
+  Player_Bravo,
+  Player_Charlie,
+  Player_Delta,
+} Player;
+
+typedef enum AvkGamepadInput {
+  AvkGamepadInput_DirUp,
+  AvkGamepadInput_DirRight,
+  AvkGamepadInput_DirDown,
+  AvkGamepadInput_DirLeft,
+  AvkGamepadInput_FaceUp,
+  AvkGamepadInput_FaceRight,
+  AvkGamepadInput_FaceDown,
+  AvkGamepadInput_FaceLeft,
+  AvkGamepadInput_TriggerLeft,
+  AvkGamepadInput_TriggerRight,
+  AvkGamepadInput_Menu,
+} AvkGamepadInput;
+
+// An analog control of a gamepad.
+// Sticks go from -1.0 to 1.0, with positive values pointing right and down like the screen
+// does, and triggers from 0.0 (released) to 1.0 (pulled all the way).
+typedef enum AvkGamepadAxis {
+  AvkGamepadAxis_LeftX,
+  AvkGamepadAxis_LeftY,
+  AvkGamepadAxis_RightX,
+  AvkGamepadAxis_RightY,
+  AvkGamepadAxis_LeftTrigger,
+  AvkGamepadAxis_RightTrigger,
+} AvkGamepadAxis;
+
+// One of the PSG's channels.
+typedef enum AudioChannel {
+  AudioChannel_Pulse1,
+  AudioChannel_Pulse2,
+  AudioChannel_Wave,
+  AudioChannel_Noise,
+} AudioChannel;
+
+// A register of a PSG channel. The value written is always 16 bits wide.
+typedef enum AudioRegister {
+  // Pitch in Hz. For the noise channel, the rate the noise generator is clocked at.
+  AudioRegister_Frequency,
+  // Volume from 0 to 15, applied when the channel is triggered.
+  AudioRegister_Volume,
+  // Pulse channels: the duty cycle, 0 = 12.5%, 1 = 25%, 2 = 50%, 3 = 75%.
+  // Noise channel: 0 for white noise, 1 for the short, metallic mode.
+  AudioRegister_Duty,
+  // Bits 0-3: steps of 1/64 s between volume changes, 0 disables the envelope.
+  // Bit 4: set to fade in instead of out, see [`ENVELOPE_UP`].
+  AudioRegister_Envelope,
+  // Pulse channels only.
+  // Bits 0-2: shift, the frequency changes by `frequency >> shift` every step.
+  // Bit 3: set to sweep down instead of up, see [`SWEEP_DOWN`].
+  // Bits 4-6: steps of 1/128 s between changes, 0 disables the sweep.
+  AudioRegister_Sweep,
+  // Wave channel only. Bits 0-3: the sample, bits 8-12: its index in the wavetable.
+  AudioRegister_WaveTable,
+  // Writing a non-zero value (re)starts the note, writing 0 silences the channel.
+  AudioRegister_Trigger,
+} AudioRegister;
+
+// A player's light gun: the mouse, or a finger on a touch screen.
+// The position is in pixels, like the positions of sprites, so `y` counts up from the bottom
+// of the screen.
+typedef struct AvkPointer {
+  int16_t x;
+  int16_t y;
+  // Held while the mouse button is down or the screen is touched.
+  bool trigger;
+  // False while the pointer is off the screen, with the position it left at.
+  bool on_screen;
+} AvkPointer;
 
 typedef struct Tile {
-    uint8_t image_id;
-    uint8_t palette_id;
+  uint8_t image_id;
+  // lower bits are palette, upper bits are flip
+  uint8_t palette_id;
 } Tile;
 
+// An element of the foreground layer.
 typedef struct Sprite {
-	uint8_t image_id;
-	uint8_t palette_transform;
-	int16_t x;
-	int16_t y;
+  // The tile ID.
+  uint8_t image_id;
+  // padding (2 bits) | flip-X (1 bit) | flip-Y (1 bit) | Palette ID (4 bits)
+  uint8_t palette_transform;
+  int16_t x;
+  int16_t y;
 } Sprite;
+#define Sprite_FLIP_X_MASK 32
+#define Sprite_FLIP_Y_MASK 16
+#define Sprite_PALETTE_MASK 15
 
 typedef struct AvkRaw {
-    void *internal;
-    Tile background[252];
-    Sprite foreground[96];
-    int8_t pan_x,
-    int8_t pan_y,
+  void *internal;
+  struct Tile background[BACKGROUND_CANVAS_SIZE];
+  struct Sprite foreground[MAX_SPRITES];
+  int8_t pan_x;
+  int8_t pan_y;
 } AvkRaw;
 
-typedef uint8_t Image[256];
-typedef uint16_t Palette[4];
+// Image data usable by any tile or sprite.
+typedef uint8_t Image[IMAGE_PIXEL_COUNT];
+#define Image_PIXEL_COUNT IMAGE_PIXEL_COUNT
+
+// A 16-color palette usable by any tile or sprite.
+// Each color is a 16-bit integer-- 4 bits per channel.
+typedef uint16_t Palette[16];
+
+// A ROM's window icon, built from AVK images.
+typedef struct Icon {
+  // Images per side: 0 for no icon, 1 for 16x16, 2 for 32x32.
+  uint8_t size;
+  Image images[4];
+  Palette palette;
+} Icon;
+
+// Information about a ROM, read by the runner before the ROM starts.
+// ROMs declare it by exporting a static named `AVK_METADATA`.
+// The strings are stored nul-padded, so they must fit their fields.
+typedef struct Metadata {
+  uint8_t title[METADATA_TITLE_LEN];
+  uint8_t author[METADATA_AUTHOR_LEN];
+  uint8_t version[METADATA_VERSION_LEN];
+  // Unique reverse-DNS identifier, e.g. `computer.living.pong`.
+  // Save data is stored under it.
+  uint8_t id[METADATA_ID_LEN];
+  struct Icon icon;
+} Metadata;
+
+#define Player_ALL { Player_Alpha, Player_Bravo, Player_Charlie, Player_Delta, }
 
-// Function pointers to be filled in  by the AVK loader.
+#define AvkGamepadInput_ALL { AvkGamepadInput_DirUp, AvkGamepadInput_DirRight, AvkGamepadInput_DirDown, AvkGamepadInput_DirLeft, AvkGamepadInput_FaceUp, AvkGamepadInput_FaceRight, AvkGamepadInput_FaceDown, AvkGamepadInput_FaceLeft, AvkGamepadInput_TriggerLeft, AvkGamepadInput_TriggerRight, AvkGamepadInput_Menu, }
+
+#define AvkGamepadAxis_ALL { AvkGamepadAxis_LeftX, AvkGamepadAxis_LeftY, AvkGamepadAxis_RightX, AvkGamepadAxis_RightY, AvkGamepadAxis_LeftTrigger, AvkGamepadAxis_RightTrigger, }
+
+#define AudioChannel_ALL { AudioChannel_Pulse1, AudioChannel_Pulse2, AudioChannel_Wave, AudioChannel_Noise, }
+
+#define AudioRegister_ALL { AudioRegister_Frequency, AudioRegister_Volume, AudioRegister_Duty, AudioRegister_Envelope, AudioRegister_Sweep, AudioRegister_WaveTable, AudioRegister_Trigger, }
+
+// Function pointers to be filled in by the AVK loader.
 AVK_EXPORT void *AVK_INIT = 0;
 AVK_EXPORT void *AVK_DROP = 0;
 AVK_EXPORT void *AVK_UPDATE = 0;
 AVK_EXPORT void *AVK_GET_TIME = 0;
 AVK_EXPORT void *AVK_GET_INPUT = 0;
+AVK_EXPORT void *AVK_SAVE_READ = 0;
+AVK_EXPORT void *AVK_SAVE_WRITE = 0;
+AVK_EXPORT void *AVK_REPORT_PANIC = 0;
+AVK_EXPORT void *AVK_AUDIO_WRITE = 0;
+AVK_EXPORT void *AVK_PLAY_MUSIC = 0;
+AVK_EXPORT void *AVK_STOP_MUSIC = 0;
+AVK_EXPORT void *AVK_SET_MUSIC_VOLUME = 0;
+AVK_EXPORT void *AVK_REGISTER_SAMPLE = 0;
+AVK_EXPORT void *AVK_PLAY_SAMPLE = 0;
+AVK_EXPORT void *AVK_STOP_SAMPLES = 0;
+AVK_EXPORT void *AVK_PLAY_EFFECT = 0;
+AVK_EXPORT void *AVK_STOP_EFFECTS = 0;
+AVK_EXPORT void *AVK_SET_SFX_VOLUME = 0;
+AVK_EXPORT void *AVK_HELD_FRAMES = 0;
+AVK_EXPORT void *AVK_JUST_RELEASED = 0;
+AVK_EXPORT void *AVK_GET_AXIS = 0;
+AVK_EXPORT void *AVK_SET_DEADZONE = 0;
+AVK_EXPORT void *AVK_IS_CONNECTED = 0;
+AVK_EXPORT void *AVK_RUMBLE = 0;
+AVK_EXPORT void *AVK_GET_POINTER = 0;
+AVK_EXPORT void *AVK_SET_TEXT_INPUT = 0;
+AVK_EXPORT void *AVK_READ_TEXT = 0;
 
-inline static AvkRaw *avk_init(const Image images[4], const Palette palettes[4]) {
-    AvkRaw *(*fp)(const Image[4], const Palette[4]) = (AvkRaw *(*)(const Image *, const Palette *))AVK_INIT;
+// Starts the console with the ROM's MAX_IMAGES images and MAX_PALETTES palettes.
+inline static AvkRaw *avk_init(const Image *images, const Palette *palettes) {
+    AvkRaw *(*fp)(const Image *, const Palette *) = (AvkRaw *(*)(const Image *, const Palette *))AVK_INIT;
     return fp(images, palettes);
 }
 
 inline static void avk_drop(AvkRaw *avk) {
-    void (*fp)(AvkRaw *avk) = (void (*)(AvkRaw *avk))AVK_DROP;
-    return fp(avk);
+    void (*fp)(AvkRaw *) = (void (*)(AvkRaw *))AVK_DROP;
+    fp(avk);
 }
 
 inline static bool avk_update(AvkRaw *avk) {
-    bool (*fp)(AvkRaw *avk) = (bool (*)(AvkRaw *avk))AVK_UPDATE;
+    bool (*fp)(AvkRaw *) = (bool (*)(AvkRaw *))AVK_UPDATE;
     return fp(avk);
 }
 
 inline static uint64_t avk_get_time(const AvkRaw *avk) {
-    uint64_t (*fp)(const AvkRaw *avk) = (uint64_t (*)(const AvkRaw *avk))AVK_GET_TIME;
+    uint64_t (*fp)(const AvkRaw *) = (uint64_t (*)(const AvkRaw *))AVK_GET_TIME;
     return fp(avk);
 }
 
-inline static bool avk_get_input(const AvkRaw *avk, enum Player player, enum GamepadInput input) {
-    bool (*fp)(const AvkRaw*, enum Player, enum GamepadInput) = (bool (*)(const AvkRaw*, enum Player, enum GamepadInput))AVK_GET_INPUT;
+inline static bool avk_get_input(const AvkRaw *avk, Player player, AvkGamepadInput input) {
+    bool (*fp)(const AvkRaw *, Player, AvkGamepadInput) = (bool (*)(const AvkRaw *, Player, AvkGamepadInput))AVK_GET_INPUT;
+    return fp(avk, player, input);
+}
+
+inline static uintptr_t avk_save_read(const AvkRaw *avk, uintptr_t offset, uint8_t *buf, uintptr_t len) {
+    uintptr_t (*fp)(const AvkRaw *, uintptr_t, uint8_t *, uintptr_t) = (uintptr_t (*)(const AvkRaw *, uintptr_t, uint8_t *, uintptr_t))AVK_SAVE_READ;
+    return fp(avk, offset, buf, len);
+}
+
+inline static uintptr_t avk_save_write(AvkRaw *avk, uintptr_t offset, const uint8_t *data, uintptr_t len) {
+    uintptr_t (*fp)(AvkRaw *, uintptr_t, const uint8_t *, uintptr_t) = (uintptr_t (*)(AvkRaw *, uintptr_t, const uint8_t *, uintptr_t))AVK_SAVE_WRITE;
+    return fp(avk, offset, data, len);
+}
+
+// Writes a register of the sound generator.
+inline static void avk_audio_write(AvkRaw *avk, AudioChannel channel, AudioRegister reg, uint16_t value) {
+    void (*fp)(AvkRaw *, AudioChannel, AudioRegister, uint16_t) = (void (*)(AvkRaw *, AudioChannel, AudioRegister, uint16_t))AVK_AUDIO_WRITE;
+    fp(avk, channel, reg, value);
+}
+
+// Starts playing an encoded song from the beginning. The runner keeps its own copy.
+inline static void avk_play_music(AvkRaw *avk, const uint8_t *data, uintptr_t len) {
+    void (*fp)(AvkRaw *, const uint8_t *, uintptr_t) = (void (*)(AvkRaw *, const uint8_t *, uintptr_t))AVK_PLAY_MUSIC;
+    fp(avk, data, len);
+}
+
+inline static void avk_stop_music(AvkRaw *avk) {
+    void (*fp)(AvkRaw *) = (void (*)(AvkRaw *))AVK_STOP_MUSIC;
+    fp(avk);
+}
+
+// Sets the loudness of the music, from 0 (silent) to 255 (the default).
+inline static void avk_set_music_volume(AvkRaw *avk, uint8_t volume) {
+    void (*fp)(AvkRaw *, uint8_t) = (void (*)(AvkRaw *, uint8_t))AVK_SET_MUSIC_VOLUME;
+    fp(avk, volume);
+}
+
+// Copies an encoded sample into the runner, replacing the one registered under `id`.
+inline static void avk_register_sample(AvkRaw *avk, uint8_t id, const uint8_t *data, uintptr_t len) {
+    void (*fp)(AvkRaw *, uint8_t, const uint8_t *, uintptr_t) = (void (*)(AvkRaw *, uint8_t, const uint8_t *, uintptr_t))AVK_REGISTER_SAMPLE;
+    fp(avk, id, data, len);
+}
+
+// Plays a registered sample. `pitch` is 8.8 fixed point, `volume` 0-15 and `pan` -127 (left) to
+// 127 (right).
+inline static void avk_play_sample(AvkRaw *avk, uint8_t id, uint16_t pitch, uint8_t volume, int8_t pan) {
+    void (*fp)(AvkRaw *, uint8_t, uint16_t, uint8_t, int8_t) = (void (*)(AvkRaw *, uint8_t, uint16_t, uint8_t, int8_t))AVK_PLAY_SAMPLE;
+    fp(avk, id, pitch, volume, pan);
+}
+
+inline static void avk_stop_samples(AvkRaw *avk) {
+    void (*fp)(AvkRaw *) = (void (*)(AvkRaw *))AVK_STOP_SAMPLES;
+    fp(avk);
+}
+
+// Plays an encoded song once as a sound effect, on the channels it has notes on, unless an effect
+// with a higher priority is playing on them.
+inline static void avk_play_effect(AvkRaw *avk, const uint8_t *data, uintptr_t len, uint8_t priority) {
+    void (*fp)(AvkRaw *, const uint8_t *, uintptr_t, uint8_t) = (void (*)(AvkRaw *, const uint8_t *, uintptr_t, uint8_t))AVK_PLAY_EFFECT;
+    fp(avk, data, len, priority);
+}
+
+inline static void avk_stop_effects(AvkRaw *avk) {
+    void (*fp)(AvkRaw *) = (void (*)(AvkRaw *))AVK_STOP_EFFECTS;
+    fp(avk);
+}
+
+// Sets the loudness of sound effects and samples, from 0 (silent) to 255 (the default).
+inline static void avk_set_sfx_volume(AvkRaw *avk, uint8_t volume) {
+    void (*fp)(AvkRaw *, uint8_t) = (void (*)(AvkRaw *, uint8_t))AVK_SET_SFX_VOLUME;
+    fp(avk, volume);
+}
+
+// How many frames an input has been held for, counting the current one, or 0 if it's up.
+inline static uint32_t avk_held_frames(const AvkRaw *avk, Player player, AvkGamepadInput input) {
+    uint32_t (*fp)(const AvkRaw *, Player, AvkGamepadInput) = (uint32_t (*)(const AvkRaw *, Player, AvkGamepadInput))AVK_HELD_FRAMES;
+    return fp(avk, player, input);
+}
+
+inline static bool avk_just_released(const AvkRaw *avk, Player player, AvkGamepadInput input) {
+    bool (*fp)(const AvkRaw *, Player, AvkGamepadInput) = (bool (*)(const AvkRaw *, Player, AvkGamepadInput))AVK_JUST_RELEASED;
     return fp(avk, player, input);
 }
 
-#endif // AVK_H
\ No newline at end of file
+// The position of an analog control, -1.0 to 1.0 for sticks and 0.0 to 1.0 for triggers, with the
+// deadzone taken out.
+inline static float avk_get_axis(const AvkRaw *avk, Player player, AvkGamepadAxis axis) {
+    float (*fp)(const AvkRaw *, Player, AvkGamepadAxis) = (float (*)(const AvkRaw *, Player, AvkGamepadAxis))AVK_GET_AXIS;
+    return fp(avk, player, axis);
+}
+
+// Sets how far an analog control has to move, from 0.0 to 1.0, before it stops reading as 0.
+inline static void avk_set_deadzone(AvkRaw *avk, Player player, AvkGamepadAxis axis, float deadzone) {
+    void (*fp)(AvkRaw *, Player, AvkGamepadAxis, float) = (void (*)(AvkRaw *, Player, AvkGamepadAxis, float))AVK_SET_DEADZONE;
+    fp(avk, player, axis, deadzone);
+}
+
+// Whether a player has a gamepad plugged in. Gamepads join as the first free player when a button
+// is pressed on them.
+inline static bool avk_is_connected(const AvkRaw *avk, Player player) {
+    bool (*fp)(const AvkRaw *, Player) = (bool (*)(const AvkRaw *, Player))AVK_IS_CONNECTED;
+    return fp(avk, player);
+}
+
+// Shakes a player's gamepad for duration_ms milliseconds, low driving the heavy motor and high the
+// light one. Does nothing without a gamepad that can rumble.
+inline static void avk_rumble(AvkRaw *avk, Player player, uint16_t low, uint16_t high, uint32_t duration_ms) {
+    void (*fp)(AvkRaw *, Player, uint16_t, uint16_t, uint32_t) = (void (*)(AvkRaw *, Player, uint16_t, uint16_t, uint32_t))AVK_RUMBLE;
+    fp(avk, player, low, high, duration_ms);
+}
+
+// Turns text input on or off. While it's on, the keyboard and an on-screen keyboard type text
+// instead of pressing inputs.
+inline static void avk_set_text_input(AvkRaw *avk, bool enabled) {
+    void (*fp)(AvkRaw *, bool) = (void (*)(AvkRaw *, bool))AVK_SET_TEXT_INPUT;
+    fp(avk, enabled);
+}
+
+// Copies the UTF-8 text typed this frame into buf, without a terminating 0, and returns its
+// length. Backspace is '\b' and Return '\n'.
+inline static uintptr_t avk_read_text(const AvkRaw *avk, uint8_t *buf, uintptr_t len) {
+    uintptr_t (*fp)(const AvkRaw *, uint8_t *, uintptr_t) = (uintptr_t (*)(const AvkRaw *, uint8_t *, uintptr_t))AVK_READ_TEXT;
+    return fp(avk, buf, len);
+}
+
+// The ABI this ROM was built against, checked by the runner.
+AVK_EXPORT const uint32_t AVK_ABI_VERSION = ABI_VERSION;
+
+inline static bool avk_is_pressed(const AvkRaw *avk, Player player, AvkGamepadInput input) {
+    return avk_held_frames(avk, player, input) > 0;
+}
+
+inline static bool avk_just_pressed(const AvkRaw *avk, Player player, AvkGamepadInput input) {
+    return avk_held_frames(avk, player, input) == 1;
+}
+
+// Where a player's light gun (the mouse, or a finger on a touch screen) is aiming, in pixels from the bottom left like sprites.
+inline static AvkPointer avk_get_pointer(const AvkRaw *avk, Player player) {
+    void (*fp)(const AvkRaw *, Player, AvkPointer *) = (void (*)(const AvkRaw *, Player, AvkPointer *))AVK_GET_POINTER;
+    AvkPointer pointer;
+    fp(avk, player, &pointer);
+    return pointer;
+}
+
+// Logs a message with the runner, before the ROM aborts. Does nothing on runners that don't support it.
+inline static void avk_report_panic(const char *message, uintptr_t len) {
+    void (*fp)(const char *, uintptr_t) = (void (*)(const char *, uintptr_t))AVK_REPORT_PANIC;
+    if (fp) {
+        fp(message, len);
+    }
+}
+
+// Layout checks, mirrored by avk/src/layout.rs.
+_Static_assert(sizeof(Player) == sizeof(int), "Player has the wrong size");
+_Static_assert(sizeof(AvkGamepadInput) == sizeof(int), "AvkGamepadInput has the wrong size");
+_Static_assert(sizeof(AvkGamepadAxis) == sizeof(int), "AvkGamepadAxis has the wrong size");
+_Static_assert(sizeof(AvkPointer) == 6, "AvkPointer has the wrong size");
+_Static_assert(offsetof(AvkPointer, trigger) == 4, "AvkPointer has the wrong layout");
+_Static_assert(sizeof(AudioChannel) == sizeof(int), "AudioChannel has the wrong size");
+_Static_assert(sizeof(AudioRegister) == sizeof(int), "AudioRegister has the wrong size");
+_Static_assert(sizeof(Tile) == 2, "Tile has the wrong size");
+_Static_assert(sizeof(Sprite) == 6, "Sprite has the wrong size");
+_Static_assert(offsetof(Sprite, x) == 2, "Sprite has the wrong layout");
+_Static_assert(sizeof(Image) == IMAGE_PIXEL_COUNT, "Image has the wrong size");
+_Static_assert(sizeof(Palette) == 16 * sizeof(uint16_t), "Palette has the wrong size");
+_Static_assert(offsetof(AvkRaw, background) == sizeof(void *), "AvkRaw has the wrong layout");
+_Static_assert(offsetof(AvkRaw, foreground) == offsetof(AvkRaw, background) + BACKGROUND_CANVAS_SIZE * sizeof(Tile), "AvkRaw has the wrong layout");
+_Static_assert(offsetof(AvkRaw, pan_x) == offsetof(AvkRaw, foreground) + MAX_SPRITES * sizeof(Sprite), "AvkRaw has the wrong layout");
+_Static_assert(offsetof(AvkRaw, pan_y) == offsetof(AvkRaw, pan_x) + 1, "AvkRaw has the wrong layout");
+_Static_assert(offsetof(Icon, images) == 1, "Icon has the wrong layout");
+_Static_assert(offsetof(Icon, palette) == 1026, "Icon has the wrong layout");
+_Static_assert(sizeof(Icon) == 1058, "Icon has the wrong size");
+_Static_assert(offsetof(Metadata, icon) == 144, "Metadata has the wrong layout");
+_Static_assert(sizeof(Metadata) == 1202, "Metadata has the wrong size");
+
+#endif // AVK_H
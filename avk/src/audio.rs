@@ -0,0 +1,88 @@
+//! The programmable sound generator (PSG).
+//!
+//! The console has four channels: two pulse waves, a wave channel playing a 32-step wavetable
+//! (a triangle unless the ROM writes its own), and a noise channel. Each is controlled by writing
+//! to its registers with [`Console::audio_write`](crate::prelude::Console::audio_write).
+
+/// One of the PSG's channels.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub enum AudioChannel {
+	Pulse1,
+	Pulse2,
+	Wave,
+	Noise,
+}
+
+impl AudioChannel {
+	pub const COUNT: usize = 4;
+	pub const ALL: [AudioChannel; AudioChannel::COUNT] = [
+		AudioChannel::Pulse1,
+		AudioChannel::Pulse2,
+		AudioChannel::Wave,
+		AudioChannel::Noise,
+	];
+}
+
+/// Decodes a channel from its C value, failing on values that aren't one.
+impl TryFrom<u32> for AudioChannel {
+	type Error = ();
+
+	fn try_from(value: u32) -> Result<Self, ()> {
+		Self::ALL.get(value as usize).copied().ok_or(())
+	}
+}
+
+/// A register of a PSG channel. The value written is always 16 bits wide.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub enum AudioRegister {
+	/// Pitch in Hz. For the noise channel, the rate the noise generator is clocked at.
+	Frequency,
+	/// Volume from 0 to 15, applied when the channel is triggered.
+	Volume,
+	/// Pulse channels: the duty cycle, 0 = 12.5%, 1 = 25%, 2 = 50%, 3 = 75%.
+	/// Noise channel: 0 for white noise, 1 for the short, metallic mode.
+	Duty,
+	/// Bits 0-3: steps of 1/64 s between volume changes, 0 disables the envelope.
+	/// Bit 4: set to fade in instead of out, see [`ENVELOPE_UP`].
+	Envelope,
+	/// Pulse channels only.
+	/// Bits 0-2: shift, the frequency changes by `frequency >> shift` every step.
+	/// Bit 3: set to sweep down instead of up, see [`SWEEP_DOWN`].
+	/// Bits 4-6: steps of 1/128 s between changes, 0 disables the sweep.
+	Sweep,
+	/// Wave channel only. Bits 0-3: the sample, bits 8-12: its index in the wavetable.
+	WaveTable,
+	/// Writing a non-zero value (re)starts the note, writing 0 silences the channel.
+	Trigger,
+}
+
+impl AudioRegister {
+	pub const COUNT: usize = 7;
+	pub const ALL: [AudioRegister; AudioRegister::COUNT] = [
+		AudioRegister::Frequency,
+		AudioRegister::Volume,
+		AudioRegister::Duty,
+		AudioRegister::Envelope,
+		AudioRegister::Sweep,
+		AudioRegister::WaveTable,
+		AudioRegister::Trigger,
+	];
+}
+
+/// Decodes a register from its C value, failing on values that aren't one.
+impl TryFrom<u32> for AudioRegister {
+	type Error = ();
+
+	fn try_from(value: u32) -> Result<Self, ()> {
+		Self::ALL.get(value as usize).copied().ok_or(())
+	}
+}
+
+/// [`AudioRegister::Envelope`] flag to fade in instead of out.
+pub const ENVELOPE_UP: u16 = 1 << 4;
+/// [`AudioRegister::Sweep`] flag to sweep the pitch down instead of up.
+pub const SWEEP_DOWN: u16 = 1 << 3;
+/// Samples in the wave channel's wavetable.
+pub const WAVETABLE_SIZE: usize = 32;
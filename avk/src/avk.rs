@@ -1,8 +1,16 @@
 use crate::prelude::*;
 use crate::{BACKGROUND_CANVAS_SIZE, MAX_IMAGES, MAX_PALETTES, MAX_SPRITES};
+use core::cell::UnsafeCell;
 use core::ffi::c_void;
+use core::fmt::{self, Write};
+#[cfg(not(target_arch = "wasm32"))]
 use core::mem;
-use core::ptr::{addr_of_mut, null};
+use core::ops::{Deref, DerefMut};
+#[cfg(not(target_arch = "wasm32"))]
+use core::ptr::null_mut;
+use core::ptr::NonNull;
+#[cfg(not(target_arch = "wasm32"))]
+use core::sync::atomic::{AtomicPtr, Ordering};
 
 #[repr(C)]
 pub struct AvkRaw {
@@ -14,94 +22,806 @@ pub struct AvkRaw {
 }
 
 /// Rust wrapper around the C ABI to use the raw Rust library.
+/// ROMs can't create one themselves. [`FrameRom`]s are lent one by the runner's calls, and
+/// [`avk_main!`](crate::avk_main) ROMs get an [`Avk`], which derefs to it.
+pub struct Console {
+	raw: NonNull<AvkRaw>,
+}
+
+/// The console of a ROM running its own main loop, handed to it by
+/// [`avk_main!`](crate::avk_main). Adds [`update`](Self::update) to the [`Console`]'s calls.
 pub struct Avk {
-	raw: *mut AvkRaw,
+	console: Console,
 }
 
 // These values are function pointers written by the AVK runner/loader.
 // The AVK api is a wrapper around these function pointers.
+// They're atomics rather than `static mut`s so reading them is safe; the layout is the same.
 
-// TODO: remove AVK_INIT and AVK_DROP by passing a handle to the AVK instance in the avk_main fn.
-//		 this will require reworking how we declare main, since references != pointers
-
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_INIT: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_DROP: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_UPDATE: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_GET_TIME: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_GET_INPUT: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_SAVE_READ: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_SAVE_WRITE: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_REPORT_PANIC: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_AUDIO_WRITE: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_PLAY_MUSIC: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_STOP_MUSIC: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_SET_MUSIC_VOLUME: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_REGISTER_SAMPLE: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_PLAY_SAMPLE: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_STOP_SAMPLES: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_PLAY_EFFECT: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_STOP_EFFECTS: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_SET_SFX_VOLUME: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_HELD_FRAMES: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_JUST_RELEASED: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
 #[no_mangle]
-pub static mut AVK_INIT: *const c_void = null();
+pub static AVK_GET_AXIS: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
 #[no_mangle]
-pub static mut AVK_DROP: *const c_void = null();
+pub static AVK_SET_DEADZONE: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
 #[no_mangle]
-pub static mut AVK_UPDATE: *const c_void = null();
+pub static AVK_IS_CONNECTED: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
 #[no_mangle]
-pub static mut AVK_GET_TIME: *const c_void = null();
+pub static AVK_RUMBLE: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
 #[no_mangle]
-pub static mut AVK_GET_INPUT: *const c_void = null();
+pub static AVK_GET_POINTER: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_SET_TEXT_INPUT: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub static AVK_READ_TEXT: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+
+/// Returns the function the runner wrote into `ptr`, panicking instead of calling null.
+#[cfg(not(target_arch = "wasm32"))]
+fn host_fn(ptr: &AtomicPtr<c_void>, name: &str) -> *mut c_void {
+	let func = ptr.load(Ordering::Relaxed);
+	if func.is_null() {
+		panic!("{name} was not set by the runner!");
+	}
+	func
+}
+
+/// Starts the console with the ROM's MAX_IMAGES images and MAX_PALETTES palettes.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_init(images: *const Image, palettes: *const Palette) -> *mut AvkRaw {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(images: *const Image, palettes: *const Palette) -> *mut AvkRaw,
+	>(host_fn(&AVK_INIT, "AVK_INIT"))(images, palettes)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_drop(avk: *mut AvkRaw) {
+	// nothing to clean up if the runner never wrote the pointers
+	let func = AVK_DROP.load(Ordering::Relaxed);
+	if !func.is_null() {
+		mem::transmute::<*mut c_void, extern "C" fn(avk: *mut AvkRaw)>(func)(avk)
+	}
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_update(avk: *mut AvkRaw) -> bool {
+	mem::transmute::<*mut c_void, extern "C" fn(avk: *mut AvkRaw) -> bool>(host_fn(
+		&AVK_UPDATE,
+		"AVK_UPDATE",
+	))(avk)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_get_time(avk: *const AvkRaw) -> u64 {
+	mem::transmute::<*mut c_void, extern "C" fn(avk: *const AvkRaw) -> u64>(host_fn(
+		&AVK_GET_TIME,
+		"AVK_GET_TIME",
+	))(avk)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_get_input(avk: *const AvkRaw, player: Player, input: AvkGamepadInput) -> bool {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *const AvkRaw, player: Player, input: AvkGamepadInput) -> bool,
+	>(host_fn(&AVK_GET_INPUT, "AVK_GET_INPUT"))(avk, player, input)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_save_read(avk: *const AvkRaw, offset: usize, buf: *mut u8, len: usize) -> usize {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *const AvkRaw, offset: usize, buf: *mut u8, len: usize) -> usize,
+	>(host_fn(&AVK_SAVE_READ, "AVK_SAVE_READ"))(avk, offset, buf, len)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_save_write(avk: *mut AvkRaw, offset: usize, data: *const u8, len: usize) -> usize {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *mut AvkRaw, offset: usize, data: *const u8, len: usize) -> usize,
+	>(host_fn(&AVK_SAVE_WRITE, "AVK_SAVE_WRITE"))(avk, offset, data, len)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_report_panic(message: *const u8, len: usize) {
+	// runners that predate panic reports leave this null
+	let func = AVK_REPORT_PANIC.load(Ordering::Relaxed);
+	if !func.is_null() {
+		mem::transmute::<*mut c_void, extern "C" fn(message: *const u8, len: usize)>(func)(
+			message, len,
+		)
+	}
+}
 
-static mut HAS_INIT: bool = false;
+/// Writes a register of the sound generator.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_audio_write(avk: *mut AvkRaw, channel: AudioChannel, reg: AudioRegister, value: u16) {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *mut AvkRaw, channel: AudioChannel, reg: AudioRegister, value: u16),
+	>(host_fn(&AVK_AUDIO_WRITE, "AVK_AUDIO_WRITE"))(avk, channel, reg, value)
+}
+
+/// Starts playing an encoded song from the beginning. The runner keeps its own copy.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_play_music(avk: *mut AvkRaw, data: *const u8, len: usize) {
+	mem::transmute::<*mut c_void, extern "C" fn(avk: *mut AvkRaw, data: *const u8, len: usize)>(
+		host_fn(&AVK_PLAY_MUSIC, "AVK_PLAY_MUSIC"),
+	)(avk, data, len)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_stop_music(avk: *mut AvkRaw) {
+	mem::transmute::<*mut c_void, extern "C" fn(avk: *mut AvkRaw)>(host_fn(
+		&AVK_STOP_MUSIC,
+		"AVK_STOP_MUSIC",
+	))(avk)
+}
+
+/// Sets the loudness of the music, from 0 (silent) to 255 (the default).
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_set_music_volume(avk: *mut AvkRaw, volume: u8) {
+	mem::transmute::<*mut c_void, extern "C" fn(avk: *mut AvkRaw, volume: u8)>(host_fn(
+		&AVK_SET_MUSIC_VOLUME,
+		"AVK_SET_MUSIC_VOLUME",
+	))(avk, volume)
+}
+
+/// Copies an encoded sample into the runner, replacing the one registered under `id`.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_register_sample(avk: *mut AvkRaw, id: u8, data: *const u8, len: usize) {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *mut AvkRaw, id: u8, data: *const u8, len: usize),
+	>(host_fn(&AVK_REGISTER_SAMPLE, "AVK_REGISTER_SAMPLE"))(avk, id, data, len)
+}
+
+/// Plays a registered sample. `pitch` is 8.8 fixed point, `volume` 0-15 and `pan` -127 (left) to
+/// 127 (right).
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_play_sample(avk: *mut AvkRaw, id: u8, pitch: u16, volume: u8, pan: i8) {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *mut AvkRaw, id: u8, pitch: u16, volume: u8, pan: i8),
+	>(host_fn(&AVK_PLAY_SAMPLE, "AVK_PLAY_SAMPLE"))(avk, id, pitch, volume, pan)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_stop_samples(avk: *mut AvkRaw) {
+	mem::transmute::<*mut c_void, extern "C" fn(avk: *mut AvkRaw)>(host_fn(
+		&AVK_STOP_SAMPLES,
+		"AVK_STOP_SAMPLES",
+	))(avk)
+}
+
+/// Plays an encoded song once as a sound effect, on the channels it has notes on, unless an effect
+/// with a higher priority is playing on them.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_play_effect(avk: *mut AvkRaw, data: *const u8, len: usize, priority: u8) {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *mut AvkRaw, data: *const u8, len: usize, priority: u8),
+	>(host_fn(&AVK_PLAY_EFFECT, "AVK_PLAY_EFFECT"))(avk, data, len, priority)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_stop_effects(avk: *mut AvkRaw) {
+	mem::transmute::<*mut c_void, extern "C" fn(avk: *mut AvkRaw)>(host_fn(
+		&AVK_STOP_EFFECTS,
+		"AVK_STOP_EFFECTS",
+	))(avk)
+}
+
+/// Sets the loudness of sound effects and samples, from 0 (silent) to 255 (the default).
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_set_sfx_volume(avk: *mut AvkRaw, volume: u8) {
+	mem::transmute::<*mut c_void, extern "C" fn(avk: *mut AvkRaw, volume: u8)>(host_fn(
+		&AVK_SET_SFX_VOLUME,
+		"AVK_SET_SFX_VOLUME",
+	))(avk, volume)
+}
+
+/// How many frames an input has been held for, counting the current one, or 0 if it's up.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_held_frames(avk: *const AvkRaw, player: Player, input: AvkGamepadInput) -> u32 {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *const AvkRaw, player: Player, input: AvkGamepadInput) -> u32,
+	>(host_fn(&AVK_HELD_FRAMES, "AVK_HELD_FRAMES"))(avk, player, input)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_just_released(avk: *const AvkRaw, player: Player, input: AvkGamepadInput) -> bool {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *const AvkRaw, player: Player, input: AvkGamepadInput) -> bool,
+	>(host_fn(&AVK_JUST_RELEASED, "AVK_JUST_RELEASED"))(avk, player, input)
+}
+
+/// The position of an analog control, -1.0 to 1.0 for sticks and 0.0 to 1.0 for triggers, with the
+/// deadzone taken out.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_get_axis(avk: *const AvkRaw, player: Player, axis: AvkGamepadAxis) -> f32 {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *const AvkRaw, player: Player, axis: AvkGamepadAxis) -> f32,
+	>(host_fn(&AVK_GET_AXIS, "AVK_GET_AXIS"))(avk, player, axis)
+}
+
+/// Sets how far an analog control has to move, from 0.0 to 1.0, before it stops reading as 0.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_set_deadzone(avk: *mut AvkRaw, player: Player, axis: AvkGamepadAxis, deadzone: f32) {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *mut AvkRaw, player: Player, axis: AvkGamepadAxis, deadzone: f32),
+	>(host_fn(&AVK_SET_DEADZONE, "AVK_SET_DEADZONE"))(avk, player, axis, deadzone)
+}
+
+/// Whether a player has a gamepad plugged in. Gamepads join as the first free player when a button
+/// is pressed on them.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_is_connected(avk: *const AvkRaw, player: Player) -> bool {
+	mem::transmute::<*mut c_void, extern "C" fn(avk: *const AvkRaw, player: Player) -> bool>(
+		host_fn(&AVK_IS_CONNECTED, "AVK_IS_CONNECTED"),
+	)(avk, player)
+}
+
+/// Shakes a player's gamepad for duration_ms milliseconds, low driving the heavy motor and high the
+/// light one. Does nothing without a gamepad that can rumble.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_rumble(avk: *mut AvkRaw, player: Player, low: u16, high: u16, duration_ms: u32) {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *mut AvkRaw, player: Player, low: u16, high: u16, duration_ms: u32),
+	>(host_fn(&AVK_RUMBLE, "AVK_RUMBLE"))(avk, player, low, high, duration_ms)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_get_pointer(avk: *const AvkRaw, player: Player, pointer: *mut AvkPointer) {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *const AvkRaw, player: Player, pointer: *mut AvkPointer),
+	>(host_fn(&AVK_GET_POINTER, "AVK_GET_POINTER"))(avk, player, pointer)
+}
+
+/// Turns text input on or off. While it's on, the keyboard and an on-screen keyboard type text
+/// instead of pressing inputs.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_set_text_input(avk: *mut AvkRaw, enabled: bool) {
+	mem::transmute::<*mut c_void, extern "C" fn(avk: *mut AvkRaw, enabled: bool)>(host_fn(
+		&AVK_SET_TEXT_INPUT,
+		"AVK_SET_TEXT_INPUT",
+	))(avk, enabled)
+}
+
+/// Copies the UTF-8 text typed this frame into buf, without a terminating 0, and returns its
+/// length. Backspace is '\b' and Return '\n'.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn avk_read_text(avk: *const AvkRaw, buf: *mut u8, len: usize) -> usize {
+	mem::transmute::<
+		*mut c_void,
+		extern "C" fn(avk: *const AvkRaw, buf: *mut u8, len: usize) -> usize,
+	>(host_fn(&AVK_READ_TEXT, "AVK_READ_TEXT"))(avk, buf, len)
+}
+
+// WebAssembly ROMs can't have their globals patched by the runner, so the same calls are
+// imported from the "avk" module instead. The runner reads the frame straight out of
+// linear memory, which means the AvkRaw has to live in the ROM.
+
+#[cfg(target_arch = "wasm32")]
+#[link(wasm_import_module = "avk")]
+extern "C" {
+	#[link_name = "init"]
+	fn avk_init_wasm(avk: *mut AvkRaw, images: *const Image, palettes: *const Palette);
+	#[link_name = "drop"]
+	fn avk_drop(avk: *mut AvkRaw);
+	#[link_name = "update"]
+	fn avk_update(avk: *mut AvkRaw) -> bool;
+	#[link_name = "get_time"]
+	fn avk_get_time(avk: *const AvkRaw) -> u64;
+	#[link_name = "get_input"]
+	fn avk_get_input(avk: *const AvkRaw, player: Player, input: AvkGamepadInput) -> bool;
+	#[link_name = "save_read"]
+	fn avk_save_read(avk: *const AvkRaw, offset: usize, buf: *mut u8, len: usize) -> usize;
+	#[link_name = "save_write"]
+	fn avk_save_write(avk: *mut AvkRaw, offset: usize, data: *const u8, len: usize) -> usize;
+	#[link_name = "report_panic"]
+	fn avk_report_panic(message: *const u8, len: usize);
+	#[link_name = "audio_write"]
+	fn avk_audio_write(
+		avk: *mut AvkRaw,
+		channel: AudioChannel,
+		register: AudioRegister,
+		value: u16,
+	);
+	#[link_name = "play_music"]
+	fn avk_play_music(avk: *mut AvkRaw, data: *const u8, len: usize);
+	#[link_name = "stop_music"]
+	fn avk_stop_music(avk: *mut AvkRaw);
+	#[link_name = "set_music_volume"]
+	fn avk_set_music_volume(avk: *mut AvkRaw, volume: u8);
+	#[link_name = "register_sample"]
+	fn avk_register_sample(avk: *mut AvkRaw, id: u8, data: *const u8, len: usize);
+	#[link_name = "play_sample"]
+	fn avk_play_sample(avk: *mut AvkRaw, id: u8, pitch: u16, volume: u8, pan: i8);
+	#[link_name = "stop_samples"]
+	fn avk_stop_samples(avk: *mut AvkRaw);
+	#[link_name = "play_effect"]
+	fn avk_play_effect(avk: *mut AvkRaw, data: *const u8, len: usize, priority: u8);
+	#[link_name = "stop_effects"]
+	fn avk_stop_effects(avk: *mut AvkRaw);
+	#[link_name = "set_sfx_volume"]
+	fn avk_set_sfx_volume(avk: *mut AvkRaw, volume: u8);
+	#[link_name = "held_frames"]
+	fn avk_held_frames(avk: *const AvkRaw, player: Player, input: AvkGamepadInput) -> u32;
+	#[link_name = "just_released"]
+	fn avk_just_released(avk: *const AvkRaw, player: Player, input: AvkGamepadInput) -> bool;
+	#[link_name = "get_axis"]
+	fn avk_get_axis(avk: *const AvkRaw, player: Player, axis: AvkGamepadAxis) -> f32;
+	#[link_name = "set_deadzone"]
+	fn avk_set_deadzone(avk: *mut AvkRaw, player: Player, axis: AvkGamepadAxis, deadzone: f32);
+	#[link_name = "is_connected"]
+	fn avk_is_connected(avk: *const AvkRaw, player: Player) -> bool;
+	#[link_name = "rumble"]
+	fn avk_rumble(avk: *mut AvkRaw, player: Player, low: u16, high: u16, duration_ms: u32);
+	#[link_name = "get_pointer"]
+	fn avk_get_pointer(avk: *const AvkRaw, player: Player, pointer: *mut AvkPointer);
+	#[link_name = "set_text_input"]
+	fn avk_set_text_input(avk: *mut AvkRaw, enabled: bool);
+	#[link_name = "read_text"]
+	fn avk_read_text(avk: *const AvkRaw, buf: *mut u8, len: usize) -> usize;
+}
+
+/// The ROM-side console of a wasm ROM.
+#[cfg(target_arch = "wasm32")]
+struct WasmRaw(UnsafeCell<AvkRaw>);
+
+// wasm ROMs are single-threaded
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for WasmRaw {}
+
+#[cfg(target_arch = "wasm32")]
+static WASM_RAW: WasmRaw = WasmRaw(UnsafeCell::new(AvkRaw {
+	internal: core::ptr::null_mut(),
+	background: [Tile {
+		image_id: 0,
+		palette_id: 0,
+	}; BACKGROUND_CANVAS_SIZE],
+	foreground: [Sprite {
+		image_id: 0,
+		palette_transform: 0,
+		x: 0,
+		y: 0,
+	}; MAX_SPRITES],
+	pan_x: 0,
+	pan_y: 0,
+}));
+
+#[cfg(target_arch = "wasm32")]
+unsafe fn avk_init(images: *const Image, palettes: *const Palette) -> *mut AvkRaw {
+	let raw = WASM_RAW.0.get();
+	avk_init_wasm(raw, images, palettes);
+	raw
+}
 
 impl Avk {
-	pub fn init(images: [Image; MAX_IMAGES], palettes: [Palette; MAX_PALETTES]) -> Self {
-		unsafe {
-			if HAS_INIT {
-				panic!("AVK has already been initialized!");
-			}
-			*(addr_of_mut!(HAS_INIT)) = true;
-			let avk_init = mem::transmute::<
-				*const c_void,
-				fn(
-					images: *const [u8; Image::PIXEL_COUNT],
-					palettes: *const [u16; 16],
-				) -> *mut AvkRaw,
-			>(AVK_INIT);
-			let raw = avk_init(
-				images.map(|e| e.0).as_mut_ptr(),
-				palettes.map(|e| e.0).as_mut_ptr(),
-			);
-			Self { raw }
+	/// Starts the console with the ROM's resources.
+	///
+	/// # Safety
+	/// See [`Console::__init`].
+	#[doc(hidden)]
+	pub unsafe fn __init(images: &[Image; MAX_IMAGES], palettes: &[Palette; MAX_PALETTES]) -> Self {
+		Self {
+			console: Console::__init(images, palettes),
 		}
 	}
 
+	/// Presents the frame and polls input. Returns false once the ROM should quit.
 	pub fn update(&mut self) -> bool {
-		unsafe {
-			mem::transmute::<*const c_void, extern "C" fn(avk: *mut AvkRaw) -> bool>(AVK_UPDATE)(
-				self.raw,
-			)
-			// avk_update(self.raw)
+		unsafe { avk_update(self.console.raw.as_ptr()) }
+	}
+}
+
+impl Deref for Avk {
+	type Target = Console;
+
+	fn deref(&self) -> &Console {
+		&self.console
+	}
+}
+
+impl DerefMut for Avk {
+	fn deref_mut(&mut self) -> &mut Console {
+		&mut self.console
+	}
+}
+
+impl Console {
+	/// Starts the console with the ROM's resources.
+	///
+	/// # Safety
+	/// Must only be called once, by the ROM's entry point. Use [`avk_main!`](crate::avk_main)
+	/// or [`avk_frame_rom!`](crate::avk_frame_rom), which hand the handle to the ROM, instead.
+	#[doc(hidden)]
+	pub unsafe fn __init(images: &[Image; MAX_IMAGES], palettes: &[Palette; MAX_PALETTES]) -> Self {
+		let raw = avk_init(images.as_ptr(), palettes.as_ptr());
+		Self {
+			raw: NonNull::new(raw).expect("The runner failed to initialize AVK!"),
+		}
+	}
+
+	/// Gives access to the frame being drawn.
+	/// The frame is presented once it's over, which can't happen while it's borrowed.
+	pub fn frame(&mut self) -> Frame<'_> {
+		Frame {
+			raw: unsafe { self.raw.as_mut() },
 		}
 	}
 
 	pub fn get_input(&self, player: Player, input: AvkGamepadInput) -> bool {
-		unsafe {
-			mem::transmute::<
-				*const c_void,
-				extern "C" fn(avk: *const AvkRaw, player: Player, input: AvkGamepadInput) -> bool,
-			>(AVK_GET_INPUT)(self.raw, player, input)
-			// avk_get_input(self.raw, player, input)
+		unsafe { avk_get_input(self.raw.as_ptr(), player, input) }
+	}
+
+	/// Returns true if an input is held on the current frame.
+	/// Unlike [`get_input`](Self::get_input), which reads the input as it is right now, this
+	/// agrees with [`just_pressed`](Self::just_pressed) and the other per-frame queries.
+	pub fn is_pressed(&self, player: Player, input: AvkGamepadInput) -> bool {
+		self.held_frames(player, input) > 0
+	}
+
+	/// Returns true if an input went down on the current frame.
+	pub fn just_pressed(&self, player: Player, input: AvkGamepadInput) -> bool {
+		self.held_frames(player, input) == 1
+	}
+
+	/// Returns true if an input went up on the current frame.
+	pub fn just_released(&self, player: Player, input: AvkGamepadInput) -> bool {
+		unsafe { avk_just_released(self.raw.as_ptr(), player, input) }
+	}
+
+	/// Returns how many frames an input has been held for, counting the current one, or 0 if
+	/// it's up. Handy for charging attacks and repeating menu cursors.
+	pub fn held_frames(&self, player: Player, input: AvkGamepadInput) -> u32 {
+		unsafe { avk_held_frames(self.raw.as_ptr(), player, input) }
+	}
+
+	/// Returns true if a player has a gamepad plugged in.
+	/// Gamepads join as the first player without one when a button is pressed on them, and get
+	/// the same player back if they're unplugged and plugged back in. Keyboard players don't
+	/// count, the keyboard always plays.
+	pub fn is_connected(&self, player: Player) -> bool {
+		unsafe { avk_is_connected(self.raw.as_ptr(), player) }
+	}
+
+	/// Shakes a player's gamepad for `duration_ms` milliseconds, replacing any rumble it was
+	/// already doing. `low_freq` drives the heavy motor and `high_freq` the light one, from 0
+	/// (still) to 65535. A duration of 0 stops the gamepad.
+	///
+	/// Nothing happens for players without a gamepad, gamepads that can't rumble, or when the
+	/// player turned rumble off.
+	pub fn rumble(&mut self, player: Player, low_freq: u16, high_freq: u16, duration_ms: u32) {
+		unsafe { avk_rumble(self.raw.as_ptr(), player, low_freq, high_freq, duration_ms) }
+	}
+
+	/// Returns the position of an analog control, with its deadzone taken out: it reads 0.0
+	/// until the control leaves the deadzone, then goes smoothly up to 1.0 (or down to -1.0).
+	/// See [`AvkGamepadAxis`] for the ranges. Always 0.0 for players without a gamepad.
+	///
+	/// The left stick also presses the `Dir` inputs once it reads 0.5 or more in their direction.
+	pub fn get_axis(&self, player: Player, axis: AvkGamepadAxis) -> f32 {
+		unsafe { avk_get_axis(self.raw.as_ptr(), player, axis) }
+	}
+
+	/// Returns where a player's light gun is aiming, and whether its trigger is held.
+	/// The mouse and touch screen are the light gun of one player, Alpha unless the runner was
+	/// told otherwise. The other players' pointers are always off the screen.
+	pub fn get_pointer(&self, player: Player) -> AvkPointer {
+		let mut pointer = AvkPointer::default();
+		unsafe { avk_get_pointer(self.raw.as_ptr(), player, &mut pointer) };
+		pointer
+	}
+
+	/// Turns text input on or off, for name entry screens and the like. It's off by default.
+	/// While it's on, the keyboard types text instead of pressing inputs, and gamepad players
+	/// get an on-screen keyboard as soon as they press a button, which takes their gamepad over
+	/// until text input is turned off. Read what was typed with [`read_text`](Self::read_text).
+	pub fn set_text_input(&mut self, enabled: bool) {
+		unsafe { avk_set_text_input(self.raw.as_ptr(), enabled) }
+	}
+
+	/// Returns the text typed during the current frame, up to
+	/// [`MAX_TEXT_INPUT`](crate::MAX_TEXT_INPUT) bytes and cut short to fit `buf`.
+	/// Backspace is typed as `'\x08'` and Return as `'\n'`. Always empty while text input is off.
+	pub fn read_text<'a>(&self, buf: &'a mut [u8]) -> &'a str {
+		let len = unsafe { avk_read_text(self.raw.as_ptr(), buf.as_mut_ptr(), buf.len()) };
+		// the runner only hands out whole characters
+		match core::str::from_utf8(&buf[..len]) {
+			Ok(text) => text,
+			Err(err) => unsafe { core::str::from_utf8_unchecked(&buf[..err.valid_up_to()]) },
 		}
 	}
 
+	/// Sets how far an analog control has to move, from 0.0 to 1.0, before it stops reading as
+	/// 0.0. Sticks default to 0.25 and triggers to 0.05.
+	pub fn set_deadzone(&mut self, player: Player, axis: AvkGamepadAxis, deadzone: f32) {
+		unsafe { avk_set_deadzone(self.raw.as_ptr(), player, axis, deadzone) }
+	}
+
 	/// Returns the current time, in milliseconds.
 	pub fn get_time(&self) -> u64 {
-		unsafe {
-			mem::transmute::<*const c_void, extern "C" fn(avk: *const AvkRaw) -> u64>(AVK_GET_TIME)(
-				self.raw,
-			)
-			// avk_get_time(self.raw)
+		unsafe { avk_get_time(self.raw.as_ptr()) }
+	}
+
+	/// Reads from the ROM's save block into `buf`, starting `offset` bytes in.
+	/// Returns the number of bytes read, which is less than `buf.len()` near the end of the
+	/// block ([`SAVE_SIZE`](crate::SAVE_SIZE) bytes).
+	pub fn save_read(&self, offset: usize, buf: &mut [u8]) -> usize {
+		unsafe { avk_save_read(self.raw.as_ptr(), offset, buf.as_mut_ptr(), buf.len()) }
+	}
+
+	/// Writes `data` to the ROM's save block, starting `offset` bytes in.
+	/// The runner keeps the block on disk, so it survives between runs.
+	/// Returns the number of bytes written.
+	pub fn save_write(&mut self, offset: usize, data: &[u8]) -> usize {
+		unsafe { avk_save_write(self.raw.as_ptr(), offset, data.as_ptr(), data.len()) }
+	}
+
+	/// Writes a register of the sound generator. See [`AudioRegister`] for what each one does.
+	pub fn audio_write(&mut self, channel: AudioChannel, register: AudioRegister, value: u16) {
+		unsafe { avk_audio_write(self.raw.as_ptr(), channel, register, value) }
+	}
+
+	/// Starts playing a song from the beginning, replacing the one playing.
+	/// The runner keeps its own copy, so the song doesn't have to outlive the call.
+	/// While music plays, its notes replace whatever was written to the channels it uses.
+	pub fn play_music(&mut self, song: &Song) {
+		let data = song.as_bytes();
+		unsafe { avk_play_music(self.raw.as_ptr(), data.as_ptr(), data.len()) }
+	}
+
+	/// Stops the music, silencing the channels it was playing on.
+	pub fn stop_music(&mut self) {
+		unsafe { avk_stop_music(self.raw.as_ptr()) }
+	}
+
+	/// Sets the loudness of the music, from 0 (silent) to 255 (the default).
+	/// It applies from the next note on.
+	pub fn set_music_volume(&mut self, volume: u8) {
+		unsafe { avk_set_music_volume(self.raw.as_ptr(), volume) }
+	}
+
+	/// Copies a sample into the runner, replacing the one registered under `id`.
+	/// Registered samples are played with [`play_sample`](Self::play_sample).
+	pub fn register_sample(&mut self, id: u8, sample: &Sample) {
+		let data = sample.as_bytes();
+		unsafe { avk_register_sample(self.raw.as_ptr(), id, data.as_ptr(), data.len()) }
+	}
+
+	/// Plays a registered sample on one of the sample voices, cutting off the oldest sample if
+	/// they're all busy.
+	/// - `pitch` is 8.8 fixed point, [`SAMPLE_BASE_PITCH`](crate::SAMPLE_BASE_PITCH) plays it at
+	///   the rate it was recorded at.
+	/// - `volume` goes from 0 to 15.
+	/// - `pan` goes from -127 (left) to 127 (right).
+	pub fn play_sample(&mut self, id: u8, pitch: u16, volume: u8, pan: i8) {
+		unsafe { avk_play_sample(self.raw.as_ptr(), id, pitch, volume, pan) }
+	}
+
+	/// Stops every sample playing.
+	pub fn stop_samples(&mut self) {
+		unsafe { avk_stop_samples(self.raw.as_ptr()) }
+	}
+
+	/// Plays a song once as a sound effect, over the music.
+	/// The effect takes the channels it has notes on until it's over, then the music picks back
+	/// up on them. Effects already playing on those channels are cut off, unless one of them has
+	/// a higher `priority`, in which case this one isn't played at all.
+	pub fn play_effect(&mut self, song: &Song, priority: u8) {
+		let data = song.as_bytes();
+		unsafe { avk_play_effect(self.raw.as_ptr(), data.as_ptr(), data.len(), priority) }
+	}
+
+	/// Stops every sound effect, handing their channels back to the music.
+	pub fn stop_effects(&mut self) {
+		unsafe { avk_stop_effects(self.raw.as_ptr()) }
+	}
+
+	/// Sets the loudness of sound effects and samples, from 0 (silent) to 255 (the default).
+	/// Like [`set_music_volume`](Self::set_music_volume), it applies from the next note on.
+	pub fn set_sfx_volume(&mut self, volume: u8) {
+		unsafe { avk_set_sfx_volume(self.raw.as_ptr(), volume) }
+	}
+}
+
+/// Sends a panic message to the runner, which logs it. Used by the panic hook that
+/// [`main`](crate::main) installs with `report_panics`.
+#[doc(hidden)]
+pub fn __report_panic(message: &dyn fmt::Display) {
+	let mut buf = MessageBuf {
+		bytes: [0; MessageBuf::CAPACITY],
+		len: 0,
+	};
+	// a truncated message is still better than none
+	let _ = write!(buf, "{message}");
+	unsafe { avk_report_panic(buf.bytes.as_ptr(), buf.len) }
+}
+
+/// Formats a message without allocating, truncating it once it's full.
+struct MessageBuf {
+	bytes: [u8; MessageBuf::CAPACITY],
+	len: usize,
+}
+
+impl MessageBuf {
+	const CAPACITY: usize = 512;
+}
+
+impl fmt::Write for MessageBuf {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		let mut len = s.len().min(Self::CAPACITY - self.len);
+		while !s.is_char_boundary(len) {
+			len -= 1;
+		}
+		self.bytes[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+		self.len += len;
+		if len < s.len() {
+			Err(fmt::Error)
+		} else {
+			Ok(())
 		}
 	}
+}
 
-	pub fn get_foreground(&mut self) -> &mut [Sprite; MAX_SPRITES] {
-		unsafe { &mut (*self.raw).foreground }
+/// The frame being drawn, borrowed from a [`Console`].
+pub struct Frame<'a> {
+	raw: &'a mut AvkRaw,
+}
+
+impl Frame<'_> {
+	pub fn foreground(&mut self) -> &mut [Sprite; MAX_SPRITES] {
+		&mut self.raw.foreground
+	}
+
+	pub fn background(&mut self) -> &mut [Tile; BACKGROUND_CANVAS_SIZE] {
+		&mut self.raw.background
+	}
+
+	/// Returns the background scroll offset, in pixels.
+	pub fn pan(&self) -> (i8, i8) {
+		(self.raw.pan_x, self.raw.pan_y)
 	}
 
-	pub fn get_background(&mut self) -> &mut [Tile; BACKGROUND_CANVAS_SIZE] {
-		unsafe { &mut (*self.raw).background }
+	/// Scrolls the background by up to a tile in each direction.
+	pub fn set_pan(&mut self, x: i8, y: i8) {
+		self.raw.pan_x = x;
+		self.raw.pan_y = y;
 	}
 }
 
-impl Drop for Avk {
+impl Drop for Console {
 	fn drop(&mut self) {
-		unsafe {
-			mem::transmute::<*const c_void, extern "C" fn(avk: *mut AvkRaw)>(AVK_DROP)(self.raw)
-			// avk_drop(self.raw);
+		unsafe { avk_drop(self.raw.as_ptr()) }
+	}
+}
+
+/// A ROM whose main loop is run by the runner instead, which lets the runner pause, step and
+/// fast-forward it. The runner presents the frame after every [`frame`](Self::frame) call, so
+/// the ROM only gets a [`Console`], without [`Avk::update`]. Declared with
+/// [`avk_frame_rom!`](crate::avk_frame_rom) or the `frame` form of the [`main`](crate::main)
+/// attribute.
+pub trait FrameRom: Sized {
+	/// Called once the console has started, before the first frame.
+	fn start(console: &mut Console) -> Self;
+
+	/// Called once per frame, to read the inputs and draw the frame.
+	fn frame(&mut self, console: &mut Console);
+
+	/// Called before the console shuts down, e.g. to write the save block. Does nothing by default.
+	fn stop(&mut self, _console: &mut Console) {}
+}
+
+/// Keeps a [`FrameRom`] and its console between the runner's calls.
+#[doc(hidden)]
+pub struct __FrameRomState<R>(UnsafeCell<Option<(Console, R)>>);
+
+// the runner never calls a ROM's entry points concurrently
+unsafe impl<R> Sync for __FrameRomState<R> {}
+
+impl<R: FrameRom> __FrameRomState<R> {
+	#[allow(clippy::new_without_default)]
+	pub const fn new() -> Self {
+		Self(UnsafeCell::new(None))
+	}
+
+	/// # Safety
+	/// Must only be called by the ROM's entry points, see [`Console::__init`].
+	pub unsafe fn start(&self, images: &[Image; MAX_IMAGES], palettes: &[Palette; MAX_PALETTES]) {
+		let mut console = Console::__init(images, palettes);
+		let rom = R::start(&mut console);
+		*self.0.get() = Some((console, rom));
+	}
+
+	/// # Safety
+	/// Must only be called by the ROM's entry points.
+	pub unsafe fn frame(&self) {
+		if let Some((console, rom)) = &mut *self.0.get() {
+			rom.frame(console);
+		}
+	}
+
+	/// # Safety
+	/// Must only be called by the ROM's entry points.
+	pub unsafe fn stop(&self) {
+		if let Some((mut console, mut rom)) = (*self.0.get()).take() {
+			rom.stop(&mut console);
+			// the ROM's state might still refer to the console
+			drop(rom);
 		}
 	}
 }
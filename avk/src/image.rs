@@ -2,10 +2,11 @@ use crate::*;
 
 /// Image data usable by any tile or sprite.
 #[derive(Copy, Clone)]
-pub struct Image(pub [u8; Image::PIXEL_COUNT]);
+#[repr(transparent)]
+pub struct Image(pub [u8; IMAGE_PIXEL_COUNT]);
 
 impl Image {
-	pub const PIXEL_COUNT: usize = IMAGE_SIZE as usize * IMAGE_SIZE as usize;
+	pub const PIXEL_COUNT: usize = IMAGE_PIXEL_COUNT;
 
 	pub const fn empty() -> Self {
 		Self([0; Image::PIXEL_COUNT])
@@ -23,4 +24,16 @@ impl Image {
 		}
 		Self(this)
 	}
+
+	/// Returns the image mirrored horizontally.
+	pub const fn flipped_x(self) -> Self {
+		let size = IMAGE_SIZE as usize;
+		let mut this = [0; Image::PIXEL_COUNT];
+		let mut i = 0;
+		while i < Image::PIXEL_COUNT {
+			this[i] = self.0[i - i % size + (size - 1 - i % size)];
+			i += 1;
+		}
+		Self(this)
+	}
 }
@@ -0,0 +1,79 @@
+//! Compile-time checks of the C ABI layout, mirrored by the assertions at the end of avk.h.
+//! If one of these fails, the runner and ROMs built against the header disagree on the layout.
+//! The same goes for wasm32 ROMs and the offsets the runner reads their frames at.
+
+use crate::prelude::*;
+use crate::{AvkRaw, BACKGROUND_CANVAS_SIZE, IMAGE_PIXEL_COUNT, MAX_SPRITES};
+use core::ffi::{c_int, c_void};
+use core::mem::{offset_of, size_of};
+
+// AvkRaw as laid out by a wasm32 ROM, where `internal` is only 4 bytes wide.
+pub const WASM_RAW_BACKGROUND_OFFSET: usize = 4;
+pub const WASM_RAW_FOREGROUND_OFFSET: usize =
+	WASM_RAW_BACKGROUND_OFFSET + BACKGROUND_CANVAS_SIZE * size_of::<Tile>();
+pub const WASM_RAW_PAN_X_OFFSET: usize =
+	WASM_RAW_FOREGROUND_OFFSET + MAX_SPRITES * size_of::<Sprite>();
+pub const WASM_RAW_PAN_Y_OFFSET: usize = WASM_RAW_PAN_X_OFFSET + 1;
+pub const WASM_RAW_SIZE: usize = WASM_RAW_PAN_Y_OFFSET + 1;
+
+/// Checks that the `ALL` tables of C enums are in the order of their values, which the runner
+/// decodes them with.
+macro_rules! assert_in_order {
+	($($ty:ty),*) => {$(
+		let mut i = 0;
+		while i < <$ty>::ALL.len() {
+			assert!(<$ty>::ALL[i] as usize == i);
+			i += 1;
+		}
+	)*};
+}
+
+const _: () = {
+	assert!(size_of::<Player>() == size_of::<c_int>());
+	assert!(size_of::<AvkGamepadInput>() == size_of::<c_int>());
+	assert!(size_of::<AvkGamepadAxis>() == size_of::<c_int>());
+	assert!(size_of::<AvkPointer>() == 6);
+	assert!(offset_of!(AvkPointer, trigger) == 4);
+	assert!(size_of::<AudioChannel>() == size_of::<c_int>());
+	assert!(size_of::<AudioRegister>() == size_of::<c_int>());
+	assert!(size_of::<Tile>() == 2);
+	assert!(size_of::<Sprite>() == 6);
+	assert!(offset_of!(Sprite, x) == 2);
+	assert!(size_of::<Image>() == IMAGE_PIXEL_COUNT);
+	assert!(size_of::<Palette>() == 16 * size_of::<u16>());
+
+	assert!(offset_of!(AvkRaw, background) == size_of::<*mut c_void>());
+	assert!(
+		offset_of!(AvkRaw, foreground)
+			== offset_of!(AvkRaw, background) + BACKGROUND_CANVAS_SIZE * size_of::<Tile>()
+	);
+	assert!(
+		offset_of!(AvkRaw, pan_x)
+			== offset_of!(AvkRaw, foreground) + MAX_SPRITES * size_of::<Sprite>()
+	);
+	assert!(offset_of!(AvkRaw, pan_y) == offset_of!(AvkRaw, pan_x) + 1);
+
+	assert_in_order!(
+		Player,
+		AvkGamepadInput,
+		AvkGamepadAxis,
+		AudioChannel,
+		AudioRegister
+	);
+
+	// metadata contains no pointers, so it's the same on every target
+	assert!(offset_of!(Icon, images) == 1);
+	assert!(offset_of!(Icon, palette) == 1026);
+	assert!(size_of::<Icon>() == 1058);
+	assert!(offset_of!(Metadata, icon) == 144);
+	assert!(size_of::<Metadata>() == 1202);
+};
+
+#[cfg(target_arch = "wasm32")]
+const _: () = {
+	assert!(offset_of!(AvkRaw, background) == WASM_RAW_BACKGROUND_OFFSET);
+	assert!(offset_of!(AvkRaw, foreground) == WASM_RAW_FOREGROUND_OFFSET);
+	assert!(offset_of!(AvkRaw, pan_x) == WASM_RAW_PAN_X_OFFSET);
+	assert!(offset_of!(AvkRaw, pan_y) == WASM_RAW_PAN_Y_OFFSET);
+	assert!(size_of::<AvkRaw>() == WASM_RAW_SIZE.next_multiple_of(4));
+};
@@ -1,12 +1,35 @@
 #![no_std]
 
+mod audio;
 mod avk;
 mod image;
+mod layout;
+mod metadata;
+mod music;
 mod palette;
 pub mod prelude;
+mod sample;
 mod sprite;
 
+pub use audio::{ENVELOPE_UP, SWEEP_DOWN, WAVETABLE_SIZE};
+#[doc(hidden)]
+pub use avk::__FrameRomState;
+#[doc(hidden)]
+pub use avk::__report_panic;
 pub use avk::AvkRaw;
+pub use avk_macros::main;
+pub use layout::{
+	WASM_RAW_BACKGROUND_OFFSET, WASM_RAW_FOREGROUND_OFFSET, WASM_RAW_PAN_X_OFFSET,
+	WASM_RAW_PAN_Y_OFFSET, WASM_RAW_SIZE,
+};
+pub use music::{
+	Instrument, PatternCell, INSTRUMENT_VOLUME, MAX_SONG_SIZE, NOTE_OFF, NO_LOOP, SONG_CELL_SIZE,
+	SONG_HEADER_SIZE, SONG_INSTRUMENT_SIZE, SONG_MAGIC, SONG_VERSION,
+};
+pub use sample::{
+	AdpcmState, MAX_SAMPLES, MAX_SAMPLE_SIZE, SAMPLE_BASE_PITCH, SAMPLE_HEADER_SIZE, SAMPLE_MAGIC,
+	SAMPLE_VERSION,
+};
 
 /// Square pixel size of sprites and tiles.
 pub const IMAGE_SIZE: i16 = 16; // px
@@ -37,6 +60,98 @@ pub const MAX_IMAGES: usize = 256;
 pub const MAX_PALETTES: usize = 16;
 pub const MAX_SPRITES: usize = 96;
 
+/// Version of the ABI between ROMs and the runner, bumped on incompatible changes and whenever
+/// a call is added, since an older runner couldn't fill in the new call's function pointer.
+/// ROMs export the version they were built against as `AVK_ABI_VERSION`.
+pub const ABI_VERSION: u32 = 2;
+
+/// Size of every ROM's persistent save block, in bytes.
+pub const SAVE_SIZE: usize = 4096;
+
+/// The most text a ROM can get in a frame while text input is on, in bytes of UTF-8.
+pub const MAX_TEXT_INPUT: usize = 64;
+
+/// Pixels per image.
+pub const IMAGE_PIXEL_COUNT: usize = IMAGE_SIZE as usize * IMAGE_SIZE as usize;
+
+// Maximum lengths of the metadata strings, in bytes.
+pub const METADATA_TITLE_LEN: usize = 32;
+pub const METADATA_AUTHOR_LEN: usize = 32;
+pub const METADATA_VERSION_LEN: usize = 16;
+pub const METADATA_ID_LEN: usize = 64;
+
+/// Declares the ROM's entry point, `avk_main`, and exports `AVK_ABI_VERSION`.
+/// The runner calls it once, which starts the console with `images` and `palettes` and hands
+/// `main` the [`Avk`](prelude::Avk) handle. See also the [`main`] attribute.
+/// ```ignore
+/// avk_main!(images(), palettes(), |mut avk| while avk.update() {});
+/// ```
+#[macro_export]
+macro_rules! avk_main {
+	($images:expr, $palettes:expr, $main:expr $(,)?) => {
+		// not nameable, so the ROM can't call it again
+		const _: () = {
+			#[no_mangle]
+			extern "C" fn avk_main() {
+				let images: [$crate::prelude::Image; $crate::MAX_IMAGES] = $images;
+				let palettes: [$crate::prelude::Palette; $crate::MAX_PALETTES] = $palettes;
+				let main: fn($crate::prelude::Avk) = $main;
+				// SAFETY: the symbol can only be defined once, and only the runner calls it
+				main(unsafe { $crate::prelude::Avk::__init(&images, &palettes) })
+			}
+
+			#[no_mangle]
+			static AVK_ABI_VERSION: u32 = $crate::ABI_VERSION;
+		};
+	};
+}
+
+/// Declares the per-frame entry points of a [`FrameRom`](prelude::FrameRom), `avk_start`,
+/// `avk_frame` and `avk_stop`, and exports `AVK_ABI_VERSION`.
+/// The runner starts the console with `images` and `palettes`, then calls the ROM once per frame
+/// until it quits. `setup`, if given, is a function run before the console starts.
+/// See also the `frame` form of the [`main`] attribute.
+/// ```ignore
+/// avk_frame_rom!(images(), palettes(), Game);
+/// ```
+#[macro_export]
+macro_rules! avk_frame_rom {
+	($images:expr, $palettes:expr, $rom:ty $(,)?) => {
+		$crate::avk_frame_rom!($images, $palettes, $rom, || {});
+	};
+	($images:expr, $palettes:expr, $rom:ty, $setup:expr $(,)?) => {
+		// not nameable, so the ROM can't call them itself
+		const _: () = {
+			static ROM: $crate::__FrameRomState<$rom> = $crate::__FrameRomState::new();
+
+			#[no_mangle]
+			extern "C" fn avk_start() {
+				let setup: fn() = $setup;
+				setup();
+				let images: [$crate::prelude::Image; $crate::MAX_IMAGES] = $images;
+				let palettes: [$crate::prelude::Palette; $crate::MAX_PALETTES] = $palettes;
+				// SAFETY: the symbols can only be defined once, and only the runner calls them
+				unsafe { ROM.start(&images, &palettes) }
+			}
+
+			#[no_mangle]
+			extern "C" fn avk_frame() {
+				// SAFETY: see avk_start
+				unsafe { ROM.frame() }
+			}
+
+			#[no_mangle]
+			extern "C" fn avk_stop() {
+				// SAFETY: see avk_start
+				unsafe { ROM.stop() }
+			}
+
+			#[no_mangle]
+			static AVK_ABI_VERSION: u32 = $crate::ABI_VERSION;
+		};
+	};
+}
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
 pub enum Player {
@@ -47,6 +162,8 @@ pub enum Player {
 }
 
 impl Player {
+	pub const ALL: [Player; 4] = [Player::Alpha, Player::Bravo, Player::Charlie, Player::Delta];
+
 	pub fn index(&self) -> usize {
 		match self {
 			Player::Alpha => 0,
@@ -57,6 +174,15 @@ impl Player {
 	}
 }
 
+/// Decodes a player from its C value, failing on values that aren't one.
+impl TryFrom<u32> for Player {
+	type Error = ();
+
+	fn try_from(value: u32) -> Result<Self, ()> {
+		Self::ALL.get(value as usize).copied().ok_or(())
+	}
+}
+
 #[derive(Default, Copy, Clone)]
 #[repr(C)]
 pub struct Tile {
@@ -84,6 +210,81 @@ pub enum AvkGamepadInput {
 	Menu,
 }
 
+impl AvkGamepadInput {
+	pub const COUNT: usize = 11;
+	pub const ALL: [AvkGamepadInput; AvkGamepadInput::COUNT] = [
+		AvkGamepadInput::DirUp,
+		AvkGamepadInput::DirRight,
+		AvkGamepadInput::DirDown,
+		AvkGamepadInput::DirLeft,
+		AvkGamepadInput::FaceUp,
+		AvkGamepadInput::FaceRight,
+		AvkGamepadInput::FaceDown,
+		AvkGamepadInput::FaceLeft,
+		AvkGamepadInput::TriggerLeft,
+		AvkGamepadInput::TriggerRight,
+		AvkGamepadInput::Menu,
+	];
+}
+
+/// Decodes an input from its C value, failing on values that aren't one.
+impl TryFrom<u32> for AvkGamepadInput {
+	type Error = ();
+
+	fn try_from(value: u32) -> Result<Self, ()> {
+		Self::ALL.get(value as usize).copied().ok_or(())
+	}
+}
+
+/// An analog control of a gamepad.
+/// Sticks go from -1.0 to 1.0, with positive values pointing right and down like the screen
+/// does, and triggers from 0.0 (released) to 1.0 (pulled all the way).
+#[derive(Copy, Clone, Eq, Hash, PartialEq, Debug)]
+#[repr(C)]
+pub enum AvkGamepadAxis {
+	LeftX,
+	LeftY,
+	RightX,
+	RightY,
+	LeftTrigger,
+	RightTrigger,
+}
+
+impl AvkGamepadAxis {
+	pub const COUNT: usize = 6;
+	pub const ALL: [AvkGamepadAxis; AvkGamepadAxis::COUNT] = [
+		AvkGamepadAxis::LeftX,
+		AvkGamepadAxis::LeftY,
+		AvkGamepadAxis::RightX,
+		AvkGamepadAxis::RightY,
+		AvkGamepadAxis::LeftTrigger,
+		AvkGamepadAxis::RightTrigger,
+	];
+}
+
+/// Decodes a axis from its C value, failing on values that aren't one.
+impl TryFrom<u32> for AvkGamepadAxis {
+	type Error = ();
+
+	fn try_from(value: u32) -> Result<Self, ()> {
+		Self::ALL.get(value as usize).copied().ok_or(())
+	}
+}
+
+/// A player's light gun: the mouse, or a finger on a touch screen.
+/// The position is in pixels, like the positions of sprites, so `y` counts up from the bottom
+/// of the screen.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+#[repr(C)]
+pub struct AvkPointer {
+	pub x: i16,
+	pub y: i16,
+	/// Held while the mouse button is down or the screen is touched.
+	pub trigger: bool,
+	/// False while the pointer is off the screen, with the position it left at.
+	pub on_screen: bool,
+}
+
 pub fn rgba_to_u16(mut rgba: [u8; 4]) -> u16 {
 	if rgba[3] > 7 {
 		rgba[3] = 15;
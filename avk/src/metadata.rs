@@ -0,0 +1,122 @@
+use crate::prelude::*;
+use crate::{
+	IMAGE_SIZE, METADATA_AUTHOR_LEN, METADATA_ID_LEN, METADATA_TITLE_LEN, METADATA_VERSION_LEN,
+};
+
+/// Information about a ROM, read by the runner before the ROM starts.
+/// ROMs declare it by exporting a static named `AVK_METADATA`.
+/// The strings are stored nul-padded, so they must fit their fields.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Metadata {
+	pub title: [u8; METADATA_TITLE_LEN],
+	pub author: [u8; METADATA_AUTHOR_LEN],
+	pub version: [u8; METADATA_VERSION_LEN],
+	/// Unique reverse-DNS identifier, e.g. `computer.living.pong`.
+	/// Save data is stored under it.
+	pub id: [u8; METADATA_ID_LEN],
+	pub icon: Icon,
+}
+
+impl Metadata {
+	/// Creates metadata without an icon.
+	/// Fails to compile (or panics) if a string doesn't fit its field.
+	pub const fn new(title: &str, author: &str, version: &str, id: &str) -> Self {
+		Self {
+			title: pad(title),
+			author: pad(author),
+			version: pad(version),
+			id: pad(id),
+			icon: Icon::none(),
+		}
+	}
+
+	/// Sets a 16x16 icon.
+	pub const fn with_icon_16(mut self, image: Image, palette: Palette) -> Self {
+		self.icon = Icon {
+			size: 1,
+			images: [image, Image::empty(), Image::empty(), Image::empty()],
+			palette,
+		};
+		self
+	}
+
+	/// Sets a 32x32 icon, from four images in row-major order.
+	pub const fn with_icon_32(mut self, images: [Image; 4], palette: Palette) -> Self {
+		self.icon = Icon {
+			size: 2,
+			images,
+			palette,
+		};
+		self
+	}
+
+	pub fn title(&self) -> &str {
+		unpad(&self.title)
+	}
+
+	pub fn author(&self) -> &str {
+		unpad(&self.author)
+	}
+
+	pub fn version(&self) -> &str {
+		unpad(&self.version)
+	}
+
+	pub fn id(&self) -> &str {
+		unpad(&self.id)
+	}
+}
+
+/// A ROM's window icon, built from AVK images.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct Icon {
+	/// Images per side: 0 for no icon, 1 for 16x16, 2 for 32x32.
+	pub size: u8,
+	pub images: [Image; 4],
+	pub palette: Palette,
+}
+
+impl Icon {
+	pub const fn none() -> Self {
+		Self {
+			size: 0,
+			images: [Image::empty(); 4],
+			palette: Palette::empty(),
+		}
+	}
+
+	/// Width and height in pixels, 0 if there is no icon.
+	pub fn pixel_size(&self) -> usize {
+		self.size.min(2) as usize * IMAGE_SIZE as usize
+	}
+
+	/// Returns the color of a pixel, 0 (transparent) if out of bounds.
+	pub fn get_pixel(&self, x: usize, y: usize) -> u16 {
+		let image_size = IMAGE_SIZE as usize;
+		if x >= self.pixel_size() || y >= self.pixel_size() {
+			return 0;
+		}
+		let image = &self.images[(y / image_size) * 2 + x / image_size];
+		let index = image.0[(y % image_size) * image_size + x % image_size];
+		self.palette.0[index as usize & 0b1111]
+	}
+}
+
+const fn pad<const N: usize>(string: &str) -> [u8; N] {
+	let bytes = string.as_bytes();
+	assert!(bytes.len() <= N, "metadata string is too long");
+	let mut padded = [0; N];
+	let mut i = 0;
+	while i < bytes.len() {
+		padded[i] = bytes[i];
+		i += 1;
+	}
+	padded
+}
+
+fn unpad(padded: &[u8]) -> &str {
+	let len = padded.iter().position(|&b| b == 0).unwrap_or(padded.len());
+	core::str::from_utf8(&padded[..len]).unwrap_or("")
+}
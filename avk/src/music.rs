@@ -0,0 +1,265 @@
+use crate::prelude::*;
+
+/// Identifies a song.
+pub const SONG_MAGIC: [u8; 4] = *b"AVKM";
+/// Version of the song format.
+pub const SONG_VERSION: u8 = 1;
+/// Largest song the runner accepts, in bytes.
+pub const MAX_SONG_SIZE: usize = 0x10000;
+pub const SONG_HEADER_SIZE: usize = 12;
+pub const SONG_INSTRUMENT_SIZE: usize = 6;
+pub const SONG_CELL_SIZE: usize = 3;
+/// Loop order of songs that stop at the end.
+pub const NO_LOOP: u8 = 0xFF;
+/// Note of a cell that silences the channel.
+pub const NOTE_OFF: u8 = 0xFF;
+/// Volume of a cell that uses the instrument's volume.
+pub const INSTRUMENT_VOLUME: u8 = 0xFF;
+
+/// A song played by the runner's sequencer, borrowed from its encoded form.
+///
+/// Songs are tracker-style: a list of patterns, each a fixed number of rows with one cell per
+/// sound generator channel, played in the order given by the order list. Everything is stored
+/// little-endian, so a song can be embedded as is with `include_bytes!`:
+/// ```text
+/// 0   "AVKM"
+/// 4   u8   format version, SONG_VERSION
+/// 5   u8   rows per pattern, at least 1
+/// 6   u8   instrument count
+/// 7   u8   pattern count
+/// 8   u8   order count, at least 1
+/// 9   u8   order to loop back to at the end, or NO_LOOP
+/// 10  u16  tempo, in rows per minute
+/// 12  instruments, SONG_INSTRUMENT_SIZE bytes each: volume u8, duty u8, envelope u16, sweep u16
+/// ..  patterns, rows * 4 cells of SONG_CELL_SIZE bytes each: note u8, instrument u8, volume u8
+/// ..  orders, a pattern index each
+/// ```
+#[derive(Copy, Clone)]
+pub struct Song<'a> {
+	data: &'a [u8],
+}
+
+/// The sound generator settings a note is played with.
+#[derive(Copy, Clone, Debug)]
+pub struct Instrument {
+	/// From 0 to 15.
+	pub volume: u8,
+	/// Written to [`AudioRegister::Duty`].
+	pub duty: u8,
+	/// Written to [`AudioRegister::Envelope`].
+	pub envelope: u16,
+	/// Written to [`AudioRegister::Sweep`].
+	pub sweep: u16,
+}
+
+/// One channel of a pattern row.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PatternCell {
+	/// 0 for no change, a MIDI note number from 1 to 127 to play a note, or [`NOTE_OFF`].
+	pub note: u8,
+	pub instrument: u8,
+	/// From 0 to 15, or [`INSTRUMENT_VOLUME`].
+	pub volume: u8,
+}
+
+impl<'a> Song<'a> {
+	/// Wraps an encoded song, checking its layout.
+	/// Fails to compile (or panics) if it isn't a valid song.
+	pub const fn from_resource(data: &'a [u8]) -> Self {
+		match Self::parse(data) {
+			Some(song) => song,
+			None => panic!("invalid song"),
+		}
+	}
+
+	/// Wraps an encoded song, returning None if it isn't a valid song.
+	pub const fn parse(data: &'a [u8]) -> Option<Self> {
+		if data.len() < SONG_HEADER_SIZE || data.len() > MAX_SONG_SIZE {
+			return None;
+		}
+		if data[0] != SONG_MAGIC[0]
+			|| data[1] != SONG_MAGIC[1]
+			|| data[2] != SONG_MAGIC[2]
+			|| data[3] != SONG_MAGIC[3]
+			|| data[4] != SONG_VERSION
+		{
+			return None;
+		}
+
+		let song = Self { data };
+		if song.rows_per_pattern() == 0 || song.order_count() == 0 || song.tempo() == 0 {
+			return None;
+		}
+		if data.len() != song.orders_offset() + song.order_count() {
+			return None;
+		}
+		if data[9] != NO_LOOP && data[9] as usize >= song.order_count() {
+			return None;
+		}
+		let mut i = 0;
+		while i < song.order_count() {
+			if song.order(i) >= song.pattern_count() {
+				return None;
+			}
+			i += 1;
+		}
+		Some(song)
+	}
+
+	/// Returns the encoded song.
+	pub const fn as_bytes(&self) -> &'a [u8] {
+		self.data
+	}
+
+	pub const fn rows_per_pattern(&self) -> usize {
+		self.data[5] as usize
+	}
+
+	pub const fn instrument_count(&self) -> usize {
+		self.data[6] as usize
+	}
+
+	pub const fn pattern_count(&self) -> usize {
+		self.data[7] as usize
+	}
+
+	pub const fn order_count(&self) -> usize {
+		self.data[8] as usize
+	}
+
+	/// Returns the order the song loops back to once it's over, or None if it stops.
+	pub const fn loop_order(&self) -> Option<usize> {
+		match self.data[9] {
+			NO_LOOP => None,
+			order => Some(order as usize),
+		}
+	}
+
+	/// Returns the tempo, in rows per minute.
+	pub const fn tempo(&self) -> u16 {
+		u16::from_le_bytes([self.data[10], self.data[11]])
+	}
+
+	/// Returns the pattern played at an order.
+	pub const fn order(&self, order: usize) -> usize {
+		self.data[self.orders_offset() + order] as usize
+	}
+
+	pub fn instrument(&self, index: usize) -> Option<Instrument> {
+		if index >= self.instrument_count() {
+			return None;
+		}
+		let offset = SONG_HEADER_SIZE + index * SONG_INSTRUMENT_SIZE;
+		let bytes = &self.data[offset..offset + SONG_INSTRUMENT_SIZE];
+		Some(Instrument {
+			volume: bytes[0],
+			duty: bytes[1],
+			envelope: u16::from_le_bytes([bytes[2], bytes[3]]),
+			sweep: u16::from_le_bytes([bytes[4], bytes[5]]),
+		})
+	}
+
+	/// Returns a cell of a pattern. Panics if it's out of bounds.
+	pub fn cell(&self, pattern: usize, row: usize, channel: AudioChannel) -> PatternCell {
+		assert!(pattern < self.pattern_count() && row < self.rows_per_pattern());
+		let row_index = pattern * self.rows_per_pattern() + row;
+		let offset = self.patterns_offset()
+			+ (row_index * AudioChannel::COUNT + channel as usize) * SONG_CELL_SIZE;
+		PatternCell {
+			note: self.data[offset],
+			instrument: self.data[offset + 1],
+			volume: self.data[offset + 2],
+		}
+	}
+
+	const fn patterns_offset(&self) -> usize {
+		SONG_HEADER_SIZE + self.instrument_count() * SONG_INSTRUMENT_SIZE
+	}
+
+	const fn orders_offset(&self) -> usize {
+		self.patterns_offset()
+			+ self.pattern_count() * self.rows_per_pattern() * AudioChannel::COUNT * SONG_CELL_SIZE
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate std;
+
+	use super::*;
+	use std::vec::Vec;
+
+	/// A song with 2 rows per pattern, 1 instrument, 2 patterns and 3 orders.
+	fn song() -> Vec<u8> {
+		let mut data = Vec::from(SONG_MAGIC);
+		data.extend_from_slice(&[SONG_VERSION, 2, 1, 2, 3, NO_LOOP]);
+		data.extend_from_slice(&120u16.to_le_bytes());
+		data.extend_from_slice(&[15, 2, 0x12, 0, 0x34, 0]);
+		for cell in 0..2 * 2 * AudioChannel::COUNT as u8 {
+			data.extend_from_slice(&[cell + 1, 0, INSTRUMENT_VOLUME]);
+		}
+		data.extend_from_slice(&[1, 0, 1]);
+		data
+	}
+
+	#[test]
+	fn parses_songs() {
+		let data = song();
+		let song = Song::parse(&data).unwrap();
+		assert_eq!(song.rows_per_pattern(), 2);
+		assert_eq!(song.pattern_count(), 2);
+		assert_eq!(song.order_count(), 3);
+		assert_eq!(song.loop_order(), None);
+		assert_eq!(song.tempo(), 120);
+		assert_eq!(song.order(0), 1);
+		assert_eq!(song.instrument(0).unwrap().envelope, 0x12);
+		assert!(song.instrument(1).is_none());
+		// the 4th row overall, with notes numbering the cells from 1
+		let cell = song.cell(1, 1, AudioChannel::Wave);
+		assert_eq!(cell.note, 3 * 4 + 2 + 1);
+		assert_eq!(cell.volume, INSTRUMENT_VOLUME);
+	}
+
+	#[test]
+	fn rejects_broken_songs() {
+		type Edit = fn(&mut Vec<u8>);
+		let broken: [(&str, Edit); 11] = [
+			("empty", |data| data.clear()),
+			("short header", |data| data.truncate(SONG_HEADER_SIZE - 1)),
+			("magic", |data| data[0] = b'X'),
+			("version", |data| data[4] = SONG_VERSION + 1),
+			("no rows", |data| data[5] = 0),
+			("no orders", |data| data[8] = 0),
+			("no tempo", |data| data[10..12].fill(0)),
+			("truncated", |data| {
+				data.pop();
+			}),
+			("trailing bytes", |data| data.push(0)),
+			("loop order", |data| data[9] = 3),
+			("missing pattern", |data| *data.last_mut().unwrap() = 2),
+		];
+		for (name, edit) in broken {
+			let mut data = song();
+			edit(&mut data);
+			assert!(Song::parse(&data).is_none(), "{name}");
+		}
+
+		let mut data = song();
+		data[9] = 2;
+		assert_eq!(Song::parse(&data).unwrap().loop_order(), Some(2));
+	}
+
+	#[test]
+	fn rejects_songs_over_the_size_limit() {
+		// 255 patterns of 255 rows are over the limit, even with the right length
+		let mut data = Vec::from(SONG_MAGIC);
+		data.extend_from_slice(&[SONG_VERSION, 255, 0, 255, 1, NO_LOOP]);
+		data.extend_from_slice(&120u16.to_le_bytes());
+		data.resize(
+			SONG_HEADER_SIZE + 255 * 255 * AudioChannel::COUNT * SONG_CELL_SIZE,
+			0,
+		);
+		data.push(0);
+		assert!(Song::parse(&data).is_none());
+	}
+}
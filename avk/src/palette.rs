@@ -2,6 +2,7 @@
 /// Each color is a 16-bit integer-- 4 bits per channel.
 // TODO: what should the alpha channel do?
 #[derive(Copy, Clone)]
+#[repr(transparent)]
 pub struct Palette(pub [u16; 16]);
 
 impl Palette {
@@ -1,3 +1,5 @@
 pub use crate::{
-	avk::Avk, image::Image, palette::Palette, sprite::Sprite, AvkGamepadInput, Player, Tile,
+	audio::AudioChannel, audio::AudioRegister, avk::Avk, avk::Console, avk::Frame, avk::FrameRom,
+	image::Image, metadata::Icon, metadata::Metadata, music::Song, palette::Palette,
+	sample::Sample, sprite::Sprite, AvkGamepadAxis, AvkGamepadInput, AvkPointer, Player, Tile,
 };
@@ -0,0 +1,230 @@
+/// Identifies a sample.
+pub const SAMPLE_MAGIC: [u8; 4] = *b"AVKS";
+/// Version of the sample format.
+pub const SAMPLE_VERSION: u8 = 1;
+/// Largest sample the runner accepts, in bytes.
+pub const MAX_SAMPLE_SIZE: usize = 0x40000;
+pub const SAMPLE_HEADER_SIZE: usize = 16;
+/// Number of samples the runner keeps registered at a time.
+pub const MAX_SAMPLES: usize = 256;
+/// Pitch that plays a sample at the rate it was recorded at. Pitches are 8.8 fixed point.
+pub const SAMPLE_BASE_PITCH: u16 = 0x100;
+
+/// A recorded sound played on the sample voices, borrowed from its encoded form.
+///
+/// Samples are mono and stored as 4-bit IMA ADPCM, two samples per byte with the first in the
+/// low nibble. Like songs, they're little-endian so they can be embedded with `include_bytes!`:
+/// ```text
+/// 0   "AVKS"
+/// 4   u8   format version, SAMPLE_VERSION
+/// 5   u8   initial ADPCM step index, from 0 to 88
+/// 6   u16  sample rate, in Hz
+/// 8   i16  initial ADPCM predictor
+/// 10  u16  reserved, 0
+/// 12  u32  sample count
+/// 16  ADPCM data, (sample count + 1) / 2 bytes
+/// ```
+/// `avk-convert wav` creates them from WAV files.
+#[derive(Copy, Clone)]
+pub struct Sample<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> Sample<'a> {
+	/// Wraps an encoded sample, checking its layout.
+	/// Fails to compile (or panics) if it isn't a valid sample.
+	pub const fn from_resource(data: &'a [u8]) -> Self {
+		match Self::parse(data) {
+			Some(sample) => sample,
+			None => panic!("invalid sample"),
+		}
+	}
+
+	/// Wraps an encoded sample, returning None if it isn't a valid sample.
+	pub const fn parse(data: &'a [u8]) -> Option<Self> {
+		if data.len() < SAMPLE_HEADER_SIZE || data.len() > MAX_SAMPLE_SIZE {
+			return None;
+		}
+		if data[0] != SAMPLE_MAGIC[0]
+			|| data[1] != SAMPLE_MAGIC[1]
+			|| data[2] != SAMPLE_MAGIC[2]
+			|| data[3] != SAMPLE_MAGIC[3]
+			|| data[4] != SAMPLE_VERSION
+			|| data[5] as usize >= ADPCM_STEPS.len()
+		{
+			return None;
+		}
+
+		let sample = Self { data };
+		if sample.sample_rate() == 0 || data.len() != SAMPLE_HEADER_SIZE + sample.len().div_ceil(2)
+		{
+			return None;
+		}
+		Some(sample)
+	}
+
+	/// Returns the encoded sample.
+	pub const fn as_bytes(&self) -> &'a [u8] {
+		self.data
+	}
+
+	/// Returns the rate the sample was recorded at, in Hz.
+	pub const fn sample_rate(&self) -> u16 {
+		u16::from_le_bytes([self.data[6], self.data[7]])
+	}
+
+	/// Returns the number of samples.
+	pub const fn len(&self) -> usize {
+		u32::from_le_bytes([self.data[12], self.data[13], self.data[14], self.data[15]]) as usize
+	}
+
+	pub const fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Decodes the sample to 16-bit PCM.
+	pub fn decode(&self) -> impl Iterator<Item = i16> + 'a {
+		let mut state = AdpcmState::new(
+			i16::from_le_bytes([self.data[8], self.data[9]]),
+			self.data[5],
+		);
+		self.data[SAMPLE_HEADER_SIZE..]
+			.iter()
+			.flat_map(|byte| [byte & 0b1111, byte >> 4])
+			.take(self.len())
+			.map(move |nibble| state.decode(nibble))
+	}
+}
+
+/// The state of an IMA ADPCM encoder or decoder.
+#[derive(Copy, Clone, Debug)]
+pub struct AdpcmState {
+	pub predictor: i16,
+	pub step_index: u8,
+}
+
+impl AdpcmState {
+	pub const fn new(predictor: i16, step_index: u8) -> Self {
+		Self {
+			predictor,
+			step_index,
+		}
+	}
+
+	/// Decodes the next 4-bit code.
+	pub fn decode(&mut self, nibble: u8) -> i16 {
+		let step = ADPCM_STEPS[self.step_index as usize] as i32;
+		let mut diff = step >> 3;
+		if nibble & 0b100 != 0 {
+			diff += step;
+		}
+		if nibble & 0b010 != 0 {
+			diff += step >> 1;
+		}
+		if nibble & 0b001 != 0 {
+			diff += step >> 2;
+		}
+		if nibble & 0b1000 != 0 {
+			diff = -diff;
+		}
+		self.predictor =
+			(self.predictor as i32 + diff).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+		self.step_index = (self.step_index as i32
+			+ ADPCM_INDEX_CHANGES[nibble as usize & 0b111] as i32)
+			.clamp(0, ADPCM_STEPS.len() as i32 - 1) as u8;
+		self.predictor
+	}
+
+	/// Encodes the next sample as a 4-bit code.
+	pub fn encode(&mut self, sample: i16) -> u8 {
+		let mut diff = sample as i32 - self.predictor as i32;
+		let mut nibble = 0;
+		if diff < 0 {
+			nibble = 0b1000;
+			diff = -diff;
+		}
+		let mut step = ADPCM_STEPS[self.step_index as usize] as i32;
+		for bit in [0b100, 0b010, 0b001] {
+			if diff >= step {
+				nibble |= bit;
+				diff -= step;
+			}
+			step >>= 1;
+		}
+		// keep in step with the decoder
+		self.decode(nibble);
+		nibble
+	}
+}
+
+const ADPCM_INDEX_CHANGES: [i8; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+const ADPCM_STEPS: [u16; 89] = [
+	7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+	73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+	494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+	2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+	10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+#[cfg(test)]
+mod tests {
+	extern crate std;
+
+	use super::*;
+	use std::vec::Vec;
+
+	/// A sine wave, loud enough to exercise most of the step table.
+	fn sine(len: usize) -> Vec<i16> {
+		(0..len)
+			.map(|i| ((i as f32 * 0.1).sin() * 20000.0) as i16)
+			.collect()
+	}
+
+	/// A sample of `len` silent codes, with the header fields written in directly.
+	fn encoded(step_index: u8, sample_rate: u16, len: u32) -> Vec<u8> {
+		let mut data = Vec::from(SAMPLE_MAGIC);
+		data.extend_from_slice(&[SAMPLE_VERSION, step_index]);
+		data.extend_from_slice(&sample_rate.to_le_bytes());
+		data.extend_from_slice(&[0; 4]);
+		data.extend_from_slice(&len.to_le_bytes());
+		data.resize(SAMPLE_HEADER_SIZE + (len as usize).div_ceil(2), 0);
+		data
+	}
+
+	#[test]
+	fn adpcm_decoder_follows_the_encoder() {
+		let mut encoder = AdpcmState::new(0, 0);
+		let mut decoder = encoder;
+		for sample in sine(500) {
+			assert_eq!(decoder.decode(encoder.encode(sample)), encoder.predictor);
+		}
+	}
+
+	#[test]
+	fn parses_the_header() {
+		let data = encoded(88, 8000, 11);
+		let sample = Sample::parse(&data).unwrap();
+		assert_eq!(sample.sample_rate(), 8000);
+		assert_eq!(sample.len(), 11);
+		assert_eq!(sample.decode().count(), 11);
+		assert!(Sample::parse(&encoded(0, 8000, 0)).unwrap().is_empty());
+	}
+
+	#[test]
+	fn rejects_broken_samples() {
+		assert!(Sample::parse(&encoded(ADPCM_STEPS.len() as u8, 8000, 11)).is_none());
+		assert!(Sample::parse(&encoded(0, 0, 11)).is_none());
+
+		// two codes to a byte, so 11 codes take 6 bytes
+		let mut data = encoded(0, 8000, 11);
+		data.pop();
+		assert!(Sample::parse(&data).is_none());
+		data.extend_from_slice(&[0, 0]);
+		assert!(Sample::parse(&data).is_none());
+
+		let largest = (MAX_SAMPLE_SIZE - SAMPLE_HEADER_SIZE) as u32 * 2;
+		assert!(Sample::parse(&encoded(0, 8000, largest)).is_some());
+		assert!(Sample::parse(&encoded(0, 8000, largest + 1)).is_none());
+	}
+}
@@ -0,0 +1,24 @@
+// The same ROM with its main loop run by the runner, which calls avk_frame once per frame and
+// presents the frame after it, so the runner can pause, step and fast-forward it.
+#include <stdio.h>
+#include "../avk.h"
+
+static AvkRaw *avk;
+
+AVK_EXPORT void avk_start() {
+    static Image images[MAX_IMAGES];
+    static Palette palettes[MAX_PALETTES];
+
+    avk = avk_init(images, palettes);
+}
+
+AVK_EXPORT void avk_frame() {
+    // no avk_update, the runner presents the frame
+    if (avk_just_pressed(avk, Player_Alpha, AvkGamepadInput_FaceDown)) {
+        puts("hi!");
+    }
+}
+
+AVK_EXPORT void avk_stop() {
+    avk_drop(avk);
+}
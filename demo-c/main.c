@@ -13,6 +13,8 @@ AVK_EXPORT void avk_main() {
     AvkRaw *avk = init_rom();
 
     while (avk_update(avk)) {
-        puts("hi!");
+        if (avk_just_pressed(avk, Player_Alpha, AvkGamepadInput_FaceDown)) {
+            puts("hi!");
+        }
     }
 }
\ No newline at end of file